@@ -1,3 +1,6 @@
 fn main() {
+    // `diagnostics::runtime_stats` reads blocking-pool metrics only in builds
+    // made with RUSTFLAGS="--cfg tokio_unstable"
+    println!("cargo:rustc-check-cfg=cfg(tokio_unstable)");
     tauri_build::build()
 }
@@ -203,7 +203,7 @@ mod api_parsing_tests {
         let result = parse_claim_item(&item);
         assert!(result.is_ok());
         let content = result.unwrap();
-        // Invalid URL (not starting with http) should be filtered out
+        // Invalid URL (not http(s) or protocol-relative) should be filtered out
         assert_eq!(content.thumbnail_url, None);
     }
 
@@ -227,6 +227,56 @@ mod api_parsing_tests {
         assert_eq!(content.thumbnail_url, None);
     }
 
+    #[test]
+    fn test_parse_claim_item_normalizes_thumbnail_urls() {
+        let cases = [
+            ("//img.test/a.jpg", "https://img.test/a.jpg"),
+            ("http://img.test/a.jpg", "https://img.test/a.jpg"),
+            ("https://img.test/a.jpg", "https://img.test/a.jpg"),
+        ];
+
+        for (thumbnail, expected) in cases {
+            let item = json!({
+                "claim_id": "test-claim-123",
+                "value_type": "stream",
+                "value": {
+                    "title": "Test Movie",
+                    "thumbnail": {
+                        "url": thumbnail
+                    },
+                    "hd_url": "https://example.com/video.mp4"
+                }
+            });
+
+            let content = parse_claim_item(&item).unwrap();
+            assert_eq!(content.thumbnail_url.as_deref(), Some(expected), "{}", thumbnail);
+        }
+    }
+
+    #[test]
+    fn test_parse_claim_item_thumbnail_falls_back_past_rejected_url() {
+        let item = json!({
+            "claim_id": "test-claim-123",
+            "value_type": "stream",
+            "value": {
+                "title": "Test Movie",
+                "thumbnail": {
+                    "url": "ftp://img.test/a.jpg"
+                },
+                "cover": {
+                    "url": "//img.test/cover.jpg"
+                },
+                "hd_url": "https://example.com/video.mp4"
+            }
+        });
+
+        let content = parse_claim_item(&item).unwrap();
+        assert_eq!(
+            content.thumbnail_url.as_deref(),
+            Some("https://img.test/cover.jpg")
+        );
+    }
+
     #[test]
     fn test_parse_claim_item_malformed_duration() {
         let item = json!({
@@ -678,7 +728,12 @@ mod api_parsing_tests {
         let result = parse_claim_item(&item);
         assert!(result.is_ok());
         let content = result.unwrap();
-        assert_eq!(content.title.len(), 10000);
+        // Titles are capped at the max_title_length setting
+        assert_eq!(
+            content.title.chars().count(),
+            crate::commands::DEFAULT_MAX_TITLE_LENGTH as usize
+        );
+        assert!(content.title.ends_with(crate::commands::TRUNCATION_MARKER));
     }
 
     #[test]
@@ -0,0 +1,222 @@
+//! # Command Timing Metrics
+//!
+//! Records how long each Tauri command takes and whether it succeeded, so
+//! slow operations reported from the field can be traced to a specific command.
+//!
+//! ## Features
+//!
+//! - **Per-command stats**: Call, success and failure counts plus latency percentiles
+//! - **Bounded memory**: Durations are kept in a fixed-size ring buffer per command
+//! - **Low overhead**: One `Instant` read and a short uncontended lock per call
+//!
+//! ## Usage
+//!
+//! ```rust
+//! use crate::command_metrics;
+//!
+//! #[command]
+//! pub async fn get_favorites(state: State<'_, AppState>) -> Result<Vec<FavoriteItem>> {
+//!     command_metrics::track("get_favorites", async move {
+//!         // command body
+//!     })
+//!     .await
+//! }
+//! ```
+
+use crate::error::{KiyyaError, Result};
+use crate::models::CommandMetrics;
+use crate::security_logging;
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Maximum number of recent durations kept per command
+const MAX_SAMPLES_PER_COMMAND: usize = 256;
+
+#[derive(Debug, Default)]
+struct CommandRecord {
+    /// Recent durations in microseconds, oldest first
+    samples: VecDeque<u64>,
+    success_count: u64,
+    failure_count: u64,
+    max_micros: u64,
+}
+
+static COMMAND_METRICS: Lazy<Mutex<HashMap<&'static str, CommandRecord>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Runs a command future and records its duration and outcome under `command`
+pub async fn track<T, F>(command: &'static str, future: F) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    let started = Instant::now();
+    let result = future.await;
+    record(command, started.elapsed(), result.is_ok());
+    if let Err(e @ (KiyyaError::InvalidInput { .. } | KiyyaError::Validation { .. })) = &result {
+        security_logging::record_validation_failure(command, &e.to_string());
+    }
+    result
+}
+
+/// Records a single command invocation
+pub fn record(command: &'static str, elapsed: Duration, success: bool) {
+    let micros = elapsed.as_micros().min(u64::MAX as u128) as u64;
+    let mut metrics = COMMAND_METRICS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let entry = metrics.entry(command).or_default();
+
+    if entry.samples.len() == MAX_SAMPLES_PER_COMMAND {
+        entry.samples.pop_front();
+    }
+    entry.samples.push_back(micros);
+    entry.max_micros = entry.max_micros.max(micros);
+
+    if success {
+        entry.success_count += 1;
+    } else {
+        entry.failure_count += 1;
+    }
+}
+
+/// Returns a snapshot of all recorded command metrics, slowest p95 first
+pub fn snapshot() -> Vec<CommandMetrics> {
+    let metrics = COMMAND_METRICS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let mut result: Vec<CommandMetrics> = metrics
+        .iter()
+        .map(|(command, record)| {
+            let mut sorted: Vec<u64> = record.samples.iter().copied().collect();
+            sorted.sort_unstable();
+            let total: u64 = sorted.iter().sum();
+            let mean_micros = if sorted.is_empty() {
+                0.0
+            } else {
+                total as f64 / sorted.len() as f64
+            };
+
+            CommandMetrics {
+                command: command.to_string(),
+                call_count: record.success_count + record.failure_count,
+                success_count: record.success_count,
+                failure_count: record.failure_count,
+                sample_count: sorted.len() as u32,
+                mean_ms: mean_micros / 1000.0,
+                p50_ms: percentile(&sorted, 0.50),
+                p95_ms: percentile(&sorted, 0.95),
+                max_ms: record.max_micros as f64 / 1000.0,
+            }
+        })
+        .collect();
+
+    result.sort_by(|a, b| {
+        b.p95_ms
+            .partial_cmp(&a.p95_ms)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.command.cmp(&b.command))
+    });
+    result
+}
+
+/// Formats the current metrics as a plain-text table for the debug package
+pub fn format_report() -> String {
+    let metrics = snapshot();
+    let mut report = String::from("=== Command Timing Metrics ===\n\n");
+
+    if metrics.is_empty() {
+        report.push_str("No commands recorded.\n");
+        return report;
+    }
+
+    report.push_str(&format!(
+        "Durations cover the last {} calls per command.\n\n",
+        MAX_SAMPLES_PER_COMMAND
+    ));
+    for m in metrics {
+        report.push_str(&format!(
+            "{}: calls={} ok={} failed={} mean={:.1}ms p50={:.1}ms p95={:.1}ms max={:.1}ms\n",
+            m.command,
+            m.call_count,
+            m.success_count,
+            m.failure_count,
+            m.mean_ms,
+            m.p50_ms,
+            m.p95_ms,
+            m.max_ms
+        ));
+    }
+    report
+}
+
+/// Nearest-rank percentile over sorted microsecond samples, in milliseconds
+fn percentile(sorted: &[u64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((sorted.len() as f64 * p).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1] as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::KiyyaError;
+
+    fn metrics_for(command: &str) -> CommandMetrics {
+        snapshot()
+            .into_iter()
+            .find(|m| m.command == command)
+            .expect("command should have metrics")
+    }
+
+    #[tokio::test]
+    async fn test_track_records_success_and_failure() {
+        let ok: Result<u32> = track("test_metrics_outcomes", async { Ok(7) }).await;
+        assert_eq!(ok.unwrap(), 7);
+
+        let err: Result<u32> = track("test_metrics_outcomes", async {
+            Err(KiyyaError::InvalidInput {
+                message: "bad".to_string(),
+            })
+        })
+        .await;
+        assert!(err.is_err());
+
+        let metrics = metrics_for("test_metrics_outcomes");
+        assert_eq!(metrics.call_count, 2);
+        assert_eq!(metrics.success_count, 1);
+        assert_eq!(metrics.failure_count, 1);
+    }
+
+    #[test]
+    fn test_samples_are_bounded() {
+        for i in 0..(MAX_SAMPLES_PER_COMMAND as u64 + 50) {
+            record("test_metrics_bounded", Duration::from_millis(i), true);
+        }
+
+        let metrics = metrics_for("test_metrics_bounded");
+        assert_eq!(metrics.call_count, MAX_SAMPLES_PER_COMMAND as u64 + 50);
+        assert_eq!(metrics.sample_count as usize, MAX_SAMPLES_PER_COMMAND);
+        // The oldest samples were dropped, so the median reflects recent calls
+        assert!(metrics.p50_ms >= 50.0);
+        assert_eq!(metrics.max_ms, (MAX_SAMPLES_PER_COMMAND + 49) as f64);
+    }
+
+    #[test]
+    fn test_percentiles() {
+        for ms in 1..=100 {
+            record("test_metrics_percentiles", Duration::from_millis(ms), true);
+        }
+
+        let metrics = metrics_for("test_metrics_percentiles");
+        assert_eq!(metrics.p50_ms, 50.0);
+        assert_eq!(metrics.p95_ms, 95.0);
+        assert_eq!(metrics.max_ms, 100.0);
+        assert!((metrics.mean_ms - 50.5).abs() < f64::EPSILON);
+    }
+}
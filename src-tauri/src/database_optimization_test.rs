@@ -35,6 +35,7 @@ mod database_optimization_tests {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         };
 
         item.update_content_hash();
@@ -1,12 +1,17 @@
+use crate::command_metrics;
 use crate::database::Database;
+use crate::disk_io;
 use crate::download::DownloadManager;
+use crate::encryption;
 use crate::error::Result;
 use crate::error_logging;
 use crate::gateway::GatewayClient;
-use crate::models::{CacheStats, DiagnosticsData};
+use crate::logging;
+use crate::models::{BlockingPoolStats, CacheStats, DiagnosticsData, RuntimeStats};
 use crate::server::LocalServer;
+use chrono::TimeZone;
 use std::fs;
-use std::io::Write;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use sysinfo::{DiskExt, System, SystemExt};
 use tracing::{info, warn};
@@ -21,7 +26,7 @@ pub async fn collect_diagnostics(
     download_manager: &DownloadManager,
 ) -> Result<DiagnosticsData> {
     // Gateway health
-    let gateway_health = gateway.get_health_stats().to_vec();
+    let gateway_health = gateway.get_gateway_health();
 
     // Database version (get from migrations table)
     let database_version = get_database_version(db).await?;
@@ -35,27 +40,102 @@ pub async fn collect_diagnostics(
     // Last manifest fetch (get from settings)
     let last_manifest_fetch = get_last_manifest_fetch(db).await?;
 
+    // Active CDN gateway and its source
+    let cdn_gateway = crate::commands::cdn_gateway_info();
+
     // Cache stats
     let cache_stats = get_cache_stats(db).await?;
 
+    // Cache age distribution
+    let cache_age_histogram = db.get_cache_age_histogram().await.unwrap_or_default();
+
     // Download stats
     let download_stats = download_manager.get_download_stats();
 
     // Error statistics
     let error_stats = error_logging::get_error_stats(db).await.ok();
 
+    // Search index drift
+    let cache_consistency = match db.verify_cache_consistency().await {
+        Ok(report) => Some(report),
+        Err(e) => {
+            warn!("Cache consistency check failed: {}", e);
+            None
+        }
+    };
+
+    // Schema version and migration health
+    let schema_summary = match db.get_schema_summary().await {
+        Ok(summary) => Some(summary),
+        Err(e) => {
+            warn!("Schema summary failed: {}", e);
+            None
+        }
+    };
+
+    // Scheduler load, e.g. a `spawn_blocking` backlog
+    let runtime = runtime_stats();
+
+    // Runtime available offline
+    let offline_duration = match db.get_offline_total_duration().await {
+        Ok(duration) => Some(duration),
+        Err(e) => {
+            warn!("Offline duration failed: {}", e);
+            None
+        }
+    };
+
     Ok(DiagnosticsData {
         gateway_health,
         database_version,
         free_disk_bytes,
         local_server_status,
         last_manifest_fetch,
+        cdn_gateway,
         cache_stats,
+        cache_age_histogram,
         download_stats,
         error_stats,
+        cache_consistency,
+        schema_summary,
+        runtime,
+        offline_duration,
+    })
+}
+
+/// Metrics of the Tokio runtime the caller runs on, or `None` outside one
+///
+/// Worker count, alive tasks and the global queue depth are stable Tokio
+/// metrics and always reported. Blocking-pool figures (threads, idle threads,
+/// queued `spawn_blocking` tasks) are only exposed by Tokio's unstable
+/// metrics, so they are `None` unless the app is built with
+/// `RUSTFLAGS="--cfg tokio_unstable"`.
+pub fn runtime_stats() -> Option<RuntimeStats> {
+    let handle = tokio::runtime::Handle::try_current().ok()?;
+    let metrics = handle.metrics();
+
+    Some(RuntimeStats {
+        workers: metrics.num_workers(),
+        alive_tasks: metrics.num_alive_tasks(),
+        global_queue_depth: metrics.global_queue_depth(),
+        blocking_pool: blocking_pool_stats(&metrics),
     })
 }
 
+#[cfg(tokio_unstable)]
+fn blocking_pool_stats(metrics: &tokio::runtime::RuntimeMetrics) -> Option<BlockingPoolStats> {
+    Some(BlockingPoolStats {
+        threads: metrics.num_blocking_threads(),
+        idle_threads: metrics.num_idle_blocking_threads(),
+        queue_depth: metrics.blocking_queue_depth(),
+    })
+}
+
+#[cfg(not(tokio_unstable))]
+fn blocking_pool_stats(_metrics: &tokio::runtime::RuntimeMetrics) -> Option<BlockingPoolStats> {
+    None
+}
+
 async fn get_database_version(_db: &Database) -> Result<u32> {
     // This would query the migrations table to get the current version
     // For now, return a default version
@@ -63,18 +143,25 @@ async fn get_database_version(_db: &Database) -> Result<u32> {
 }
 
 fn get_free_disk_space(vault_path: &Path) -> Result<u64> {
-    let mut system = System::new_all();
+    // If we can't find the disk, return 0
+    Ok(free_disk_space(vault_path).unwrap_or(0))
+}
+
+/// Free bytes on the disk holding `path`, or `None` if no mounted disk does
+///
+/// The most specific mount point wins, so a vault on a separate volume is not
+/// reported with the root filesystem's space.
+pub(crate) fn free_disk_space(path: &Path) -> Option<u64> {
+    let mut system = System::new();
     system.refresh_disks_list();
     system.refresh_disks();
 
-    for disk in system.disks() {
-        if vault_path.starts_with(disk.mount_point()) {
-            return Ok(disk.available_space());
-        }
-    }
-
-    // If we can't find the disk, return 0
-    Ok(0)
+    system
+        .disks()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space())
 }
 
 async fn get_last_manifest_fetch(db: &Database) -> Result<Option<i64>> {
@@ -137,12 +224,59 @@ pub async fn collect_debug_package(
     // Add configuration (sanitized)
     add_sanitized_config(&mut zip, db, options).await?;
 
+    // Add per-command timing metrics
+    zip.start_file("command_metrics.txt", options)?;
+    zip.write_all(command_metrics::format_report().as_bytes())?;
+
+    // Add disk IO counters for the download and playback paths
+    zip.start_file("disk_io.txt", options)?;
+    zip.write_all(disk_io::format_report().as_bytes())?;
+
+    // Add encryption throughput, measuring it now if it was never run
+    let benchmark_report = format_encryption_benchmark().await;
+    zip.start_file("encryption_benchmark.txt", options)?;
+    zip.write_all(benchmark_report.as_bytes())?;
+
     zip.finish()?;
 
     info!("Debug package created: {:?}", debug_package_path);
     Ok(debug_package_path)
 }
 
+/// Amount of data the debug package benchmarks when no result exists yet
+const DEBUG_PACKAGE_BENCHMARK_MB: u32 = 16;
+
+/// Describes the latest encryption benchmark, running a small one if needed
+async fn format_encryption_benchmark() -> String {
+    let benchmark = match encryption::last_benchmark() {
+        Some(benchmark) => Ok(benchmark),
+        None => encryption::benchmark(DEBUG_PACKAGE_BENCHMARK_MB).await,
+    };
+
+    let mut report = String::from("=== Encryption Benchmark ===\n");
+    match benchmark {
+        Ok(benchmark) => {
+            let measured_at = chrono::Utc
+                .timestamp_opt(benchmark.measured_at, 0)
+                .single()
+                .map(|time| time.to_rfc3339())
+                .unwrap_or_else(|| benchmark.measured_at.to_string());
+            report.push_str(&format!("Measured: {}\n", measured_at));
+            report.push_str(&format!("Size: {} MB\n", benchmark.size_mb));
+            report.push_str(&format!(
+                "Encrypt: {:.1} MB/s\n",
+                benchmark.encrypt_mb_per_sec
+            ));
+            report.push_str(&format!(
+                "Decrypt: {:.1} MB/s\n",
+                benchmark.decrypt_mb_per_sec
+            ));
+        }
+        Err(e) => report.push_str(&format!("Benchmark failed: {}\n", e)),
+    }
+    report
+}
+
 async fn add_system_info(zip: &mut ZipWriter<fs::File>, options: FileOptions) -> Result<()> {
     let mut system = System::new_all();
     system.refresh_all();
@@ -233,6 +367,14 @@ async fn add_database_metadata(
         ));
     }
 
+    // Get cache age distribution
+    if let Ok(histogram) = db.get_cache_age_histogram().await {
+        metadata.push_str(&format!("\n=== Cache Age Distribution ===\n"));
+        for (bucket, count) in histogram {
+            metadata.push_str(&format!("{}: {}\n", bucket.label(), count));
+        }
+    }
+
     // Get memory stats
     let memory_stats = db.get_memory_stats().await?;
     metadata.push_str(&format!("\n=== Memory Statistics ===\n"));
@@ -275,6 +417,79 @@ async fn add_database_metadata(
     Ok(())
 }
 
+/// Upper bound on the number of lines `read_recent_logs` returns
+pub const MAX_RECENT_LOG_LINES: usize = 2000;
+
+/// Bytes read per step while scanning a log file backwards from its end
+const LOG_TAIL_BLOCK_BYTES: u64 = 64 * 1024;
+
+/// Returns the last `lines` lines of the current application log in `log_dir`
+///
+/// The current log is the most recently modified `kiyya.log*` file written by
+/// the daily rolling appender. `lines` is capped at `MAX_RECENT_LOG_LINES` and
+/// the file is read backwards in blocks, so a large log is never loaded whole.
+/// Every line is passed through `logging::redact_secrets` before it is returned.
+pub fn read_recent_logs(log_dir: &Path, lines: usize) -> Result<Vec<String>> {
+    let lines = lines.min(MAX_RECENT_LOG_LINES);
+    if lines == 0 {
+        return Ok(Vec::new());
+    }
+
+    let path = match current_log_file(log_dir)? {
+        Some(path) => path,
+        None => return Ok(Vec::new()),
+    };
+
+    // Redact once over the joined tail; the patterns never span a line break
+    let tail = read_tail_lines(&path, lines)?.join("\n");
+    Ok(logging::redact_secrets(&tail)
+        .lines()
+        .map(|line| line.to_string())
+        .collect())
+}
+
+fn current_log_file(log_dir: &Path) -> Result<Option<PathBuf>> {
+    let entries = match fs::read_dir(log_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("kiyya.log"))
+        .filter_map(|entry| {
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((modified, entry.path()))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path))
+}
+
+fn read_tail_lines(path: &Path, lines: usize) -> Result<Vec<String>> {
+    let mut file = fs::File::open(path)?;
+    let mut start = file.metadata()?.len();
+    let mut buf: Vec<u8> = Vec::new();
+    let mut newlines = 0;
+
+    // One newline more than wanted guarantees the oldest kept line is complete
+    while start > 0 && newlines <= lines {
+        let step = LOG_TAIL_BLOCK_BYTES.min(start);
+        start -= step;
+        file.seek(SeekFrom::Start(start))?;
+        let mut block = vec![0u8; step as usize];
+        file.read_exact(&mut block)?;
+        newlines += block.iter().filter(|&&b| b == b'\n').count();
+        block.extend_from_slice(&buf);
+        buf = block;
+    }
+
+    let text = String::from_utf8_lossy(&buf);
+    let all: Vec<&str> = text.lines().collect();
+    let skip = all.len().saturating_sub(lines);
+    Ok(all[skip..].iter().map(|line| line.to_string()).collect())
+}
+
 async fn add_recent_logs(
     zip: &mut ZipWriter<fs::File>,
     app_data_path: &Path,
@@ -452,10 +667,30 @@ async fn add_sanitized_config(
     let safe_settings = vec![
         "theme",
         "last_used_quality",
+        "preferred_download_quality",
         "encrypt_downloads",
         "auto_upgrade_quality",
         "cache_ttl_minutes",
+        "tag_ttl_overrides",
         "max_cache_items",
+        "max_cache_bytes",
+        "allow_stale_on_error",
+        "min_cache_results",
+        "prebuffer_seconds",
+        "proxy_thumbnails",
+        "cache_eviction_policy",
+        "finished_threshold_percent",
+        "content_pipeline_tracing",
+        "prewarm_favorites",
+        "warm_connection_pool",
+        "raw_json_format",
+        "thumbnail_url_policy",
+        "max_title_length",
+        "max_description_length",
+        "gateway_max_requests_per_second",
+        "gateways",
+        "allow_plaintext_fallback",
+        "download_temp_dir",
     ];
 
     for setting in safe_settings {
@@ -469,6 +704,12 @@ async fn add_sanitized_config(
     config.push_str("Secondary: https://api.lbry.tv/api/v1/proxy\n");
     config.push_str("Fallback: https://api.odysee.com/api/v1/proxy\n");
 
+    let cdn = crate::commands::cdn_gateway_info();
+    config.push_str(&format!(
+        "CDN Gateway: {} (source: {:?}, default: {})\n",
+        cdn.gateway, cdn.source, cdn.is_default
+    ));
+
     zip.start_file("config.txt", options)?;
     zip.write_all(config.as_bytes())?;
 
@@ -228,3 +228,58 @@ fn test_debug_package_components_documented() {
         "All 6 required debug package components are documented"
     );
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_runtime_stats_reports_scheduler() {
+    let stats = crate::diagnostics::runtime_stats().expect("inside a runtime");
+    assert_eq!(stats.workers, 2);
+    assert_eq!(stats.blocking_pool.is_some(), cfg!(tokio_unstable));
+
+    // Outside a runtime there is nothing to report
+    let outside = std::thread::spawn(crate::diagnostics::runtime_stats)
+        .join()
+        .unwrap();
+    assert!(outside.is_none());
+}
+
+#[test]
+fn test_read_recent_logs_returns_redacted_tail_in_order() {
+    use crate::diagnostics::{read_recent_logs, MAX_RECENT_LOG_LINES};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let log_dir = temp_dir.path();
+
+    // An older rotated file must not be picked over the current one
+    let old_log = log_dir.join("kiyya.log.2026-10-16");
+    std::fs::write(&old_log, "yesterday\n").unwrap();
+    filetime::set_file_mtime(&old_log, filetime::FileTime::from_unix_time(1_000_000, 0)).unwrap();
+
+    // Large enough that the tail spans several read blocks
+    let mut content = String::new();
+    for i in 1..=10_000 {
+        content.push_str(&format!("log line {:05}\n", i));
+    }
+    content.push_str("request sent with token=abc123\n");
+    std::fs::write(log_dir.join("kiyya.log.2026-10-17"), &content).unwrap();
+
+    let tail = read_recent_logs(log_dir, 3).unwrap();
+    assert_eq!(
+        tail,
+        vec![
+            "log line 09999".to_string(),
+            "log line 10000".to_string(),
+            "request sent with token=***REDACTED***".to_string(),
+        ]
+    );
+
+    // Requests above the cap are clamped, and order is preserved across blocks
+    let capped = read_recent_logs(log_dir, usize::MAX).unwrap();
+    assert_eq!(capped.len(), MAX_RECENT_LOG_LINES);
+    let first = 10_001 - MAX_RECENT_LOG_LINES + 1;
+    assert_eq!(capped[0], format!("log line {:05}", first));
+    assert_eq!(capped[MAX_RECENT_LOG_LINES - 2], "log line 10000");
+
+    assert!(read_recent_logs(&log_dir.join("missing"), 10)
+        .unwrap()
+        .is_empty());
+}
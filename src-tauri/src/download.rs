@@ -1,18 +1,53 @@
+use crate::disk_io;
 use crate::encryption::EncryptionManager;
 use crate::error::{KiyyaError, Result};
-use crate::models::{DownloadProgress, DownloadRequest, OfflineMetadata};
+use crate::media_probe;
+use crate::models::{
+    DownloadProgress, DownloadRequest, DownloadState, MediaProbe, OfflineMetadata,
+};
 use crate::path_security;
+use crate::security_logging::{log_security_event, SecurityEvent};
+use once_cell::sync::Lazy;
 use reqwest::Client;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use sysinfo::{DiskExt, System, SystemExt};
 use tauri::Manager;
 use tokio::fs::{create_dir_all, remove_file, rename, File};
-use tokio::io::{AsyncSeekExt, AsyncWriteExt, SeekFrom};
-use tracing::{error, info, warn};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, SeekFrom};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+/// Files younger than this are never reported as orphaned, because a download
+/// that just finished may not have saved its offline metadata yet
+const ORPHAN_MIN_AGE: Duration = Duration::from_secs(3600);
+
+/// Subdirectory of the vault that holds in-progress downloads unless
+/// `download_temp_dir` points elsewhere
+pub const DEFAULT_DOWNLOAD_TEMP_SUBDIR: &str = "partial";
+
+/// Largest byte range `ContentRangeReader::read` returns in one call
+pub const MAX_CONTENT_RANGE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// How long a warmed-up CDN URL is remembered before it may be requested again
+const WARM_UP_TTL: Duration = Duration::from_secs(300);
+
+/// CDN URLs recently warmed up, with when and how many bytes were fetched
+static WARMED_URLS: Lazy<std::sync::Mutex<HashMap<String, (Instant, u64)>>> =
+    Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
+
+/// State of each download started this session, by claim id and quality
+///
+/// Kept outside `DownloadManager` because its mutex is held for a whole
+/// download, and status checks must not wait behind it.
+static DOWNLOAD_STATES: Lazy<std::sync::Mutex<HashMap<(String, String), DownloadState>>> =
+    Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
+
 pub struct DownloadManager {
     vault_path: PathBuf,
+    /// Where `.tmp` files of in-progress downloads are written
+    temp_dir: PathBuf,
     client: Client,
     encryption_manager: EncryptionManager,
     // Download statistics tracking
@@ -26,6 +61,8 @@ impl DownloadManager {
         // Use path_security module to get validated vault path
         let vault_path = path_security::validate_subdir_path("vault", "")?;
         create_dir_all(&vault_path).await?;
+        let temp_dir = vault_path.join(DEFAULT_DOWNLOAD_TEMP_SUBDIR);
+        create_dir_all(&temp_dir).await?;
 
         let client = Client::builder()
             .timeout(std::time::Duration::from_secs(30))
@@ -36,6 +73,7 @@ impl DownloadManager {
 
         Ok(Self {
             vault_path,
+            temp_dir,
             client,
             encryption_manager,
             total_downloads: std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0)),
@@ -92,8 +130,7 @@ impl DownloadManager {
         );
 
         // Check if we can resume an existing download
-        let temp_filename = format!("{}-{}.tmp", request.claim_id, request.quality);
-        let temp_path = self.vault_path.join(&temp_filename);
+        let temp_path = self.temp_file_path(&request.claim_id, &request.quality);
         let lock_filename = format!("{}-{}.lock", request.claim_id, request.quality);
         let lock_path = self.vault_path.join(&lock_filename);
 
@@ -256,6 +293,12 @@ impl DownloadManager {
         } else if !status.is_success() {
             // Clean up lock file before returning error
             let _ = remove_file(&lock_path).await;
+            if is_region_block_status(status) {
+                return Err(KiyyaError::RegionBlocked {
+                    claim_id: request.claim_id.clone(),
+                    status: status.as_u16(),
+                });
+            }
             return Err(KiyyaError::Download {
                 message: format!(
                     "HTTP {}: {}",
@@ -325,7 +368,10 @@ impl DownloadManager {
             match response.chunk().await {
                 Ok(Some(chunk)) => {
                     // Write chunk to file
-                    if let Err(e) = file.write_all(&chunk).await {
+                    let write_started = std::time::Instant::now();
+                    let write_result = file.write_all(&chunk).await;
+                    disk_io::DOWNLOAD_WRITES.record(chunk.len(), write_started.elapsed());
+                    if let Err(e) = write_result {
                         error!(
                             "Failed to write chunk for {} ({}): {}",
                             request.claim_id, request.quality, e
@@ -469,6 +515,9 @@ impl DownloadManager {
             .join(format!("{}-{}.etag", request.claim_id, request.quality));
         let _ = remove_file(&etag_path).await;
 
+        // Read the container header while the file is still plaintext
+        let probe = probe_downloaded_file(&temp_path).await;
+
         // Generate final filename
         let final_filename = if encrypt {
             format!("{}.bin", Uuid::new_v4())
@@ -480,11 +529,7 @@ impl DownloadManager {
         // Encrypt if requested
         if encrypt {
             info!("Encrypting downloaded content");
-            match self
-                .encryption_manager
-                .encrypt_file(&temp_path, &final_path)
-                .await
-            {
+            match encrypt_into_vault(&self.encryption_manager, &temp_path, &final_path).await {
                 Ok(_) => {
                     if let Err(e) = remove_file(&temp_path).await {
                         warn!("Failed to remove temp file after encryption: {}", e);
@@ -497,7 +542,6 @@ impl DownloadManager {
                     );
                     // Clean up all files on encryption failure
                     let _ = remove_file(&temp_path).await;
-                    let _ = remove_file(&final_path).await;
                     let _ = remove_file(&lock_path).await;
 
                     return Err(KiyyaError::Encryption {
@@ -506,8 +550,8 @@ impl DownloadManager {
                 }
             }
         } else {
-            // Just rename the temp file
-            if let Err(e) = rename(&temp_path, &final_path).await {
+            // Just move the temp file into the vault
+            if let Err(e) = move_into_vault(&temp_path, &final_path).await {
                 error!(
                     "Failed to rename temp file for {} ({}): {}",
                     request.claim_id, request.quality, e
@@ -546,6 +590,8 @@ impl DownloadManager {
             file_size: final_size,
             encrypted: encrypt,
             added_at: chrono::Utc::now().timestamp(),
+            probe,
+            title: None,
         };
 
         // Calculate final average throughput
@@ -622,9 +668,9 @@ impl DownloadManager {
         }
 
         // Also clean up any leftover temp files, lock files, and etag files
-        let temp_path = self
-            .vault_path
-            .join(format!("{}-{}.tmp", claim_id, quality));
+        for temp_path in self.temp_file_paths(claim_id, quality) {
+            let _ = remove_file(&temp_path).await;
+        }
         let lock_path = self
             .vault_path
             .join(format!("{}-{}.lock", claim_id, quality));
@@ -632,7 +678,6 @@ impl DownloadManager {
             .vault_path
             .join(format!("{}-{}.etag", claim_id, quality));
 
-        let _ = remove_file(&temp_path).await;
         let _ = remove_file(&lock_path).await;
         let _ = remove_file(&etag_path).await;
 
@@ -663,13 +708,76 @@ impl DownloadManager {
         Ok(())
     }
 
+    /// Total size in bytes of every file in the vault
+    pub async fn vault_usage(&self) -> Result<u64> {
+        dir_usage(&self.vault_path).await
+    }
+
+    /// Finds finished content files in the vault that no offline metadata row
+    /// refers to
+    ///
+    /// Only `.mp4` and `.bin` files older than `ORPHAN_MIN_AGE` are considered;
+    /// temp, lock and etag files belong to downloads and are handled by
+    /// `cleanup_stale_locks` and `cleanup_failed_download`.
+    pub async fn find_orphaned_files(
+        &self,
+        known_filenames: &HashSet<String>,
+    ) -> Result<Vec<(PathBuf, u64)>> {
+        let cutoff = std::time::SystemTime::now() - ORPHAN_MIN_AGE;
+        let mut orphans = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.vault_path).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let filename = match path.file_name().and_then(|n| n.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            if !(filename.ends_with(".mp4") || filename.ends_with(".bin"))
+                || known_filenames.contains(&filename)
+            {
+                continue;
+            }
+
+            let metadata = match entry.metadata().await {
+                Ok(metadata) if metadata.is_file() => metadata,
+                _ => continue,
+            };
+            if matches!(metadata.modified(), Ok(modified) if modified < cutoff) {
+                orphans.push((path, metadata.len()));
+            }
+        }
+
+        Ok(orphans)
+    }
+
+    /// Deletes orphaned vault files, returning how many were removed and
+    /// their total size
+    pub async fn remove_orphaned_files(
+        &self,
+        known_filenames: &HashSet<String>,
+    ) -> Result<(u32, u64)> {
+        let mut removed = 0;
+        let mut bytes = 0;
+
+        for (path, size) in self.find_orphaned_files(known_filenames).await? {
+            match remove_file(&path).await {
+                Ok(()) => {
+                    removed += 1;
+                    bytes += size;
+                }
+                Err(e) => warn!("Failed to remove orphaned file {:?}: {}", path, e),
+            }
+        }
+
+        info!("Removed {} orphaned vault files ({} bytes)", removed, bytes);
+        Ok((removed, bytes))
+    }
+
     /// Clean up all temporary files associated with a failed download
     pub async fn cleanup_failed_download(&self, claim_id: &str, quality: &str) -> Result<()> {
         info!("Cleaning up failed download: {} ({})", claim_id, quality);
 
-        let temp_path = self
-            .vault_path
-            .join(format!("{}-{}.tmp", claim_id, quality));
         let lock_path = self
             .vault_path
             .join(format!("{}-{}.lock", claim_id, quality));
@@ -678,9 +786,11 @@ impl DownloadManager {
             .join(format!("{}-{}.etag", claim_id, quality));
 
         // Remove all related files, ignoring errors
-        if temp_path.exists() {
-            if let Err(e) = remove_file(&temp_path).await {
-                warn!("Failed to remove temp file during cleanup: {}", e);
+        for temp_path in self.temp_file_paths(claim_id, quality) {
+            if temp_path.exists() {
+                if let Err(e) = remove_file(&temp_path).await {
+                    warn!("Failed to remove temp file during cleanup: {}", e);
+                }
             }
         }
 
@@ -699,10 +809,131 @@ impl DownloadManager {
         Ok(())
     }
 
+    /// Loads the encryption key ahead of a download and decides whether to encrypt
+    ///
+    /// Returns the value to pass to `download_content`. When `encrypt` is set
+    /// but the key cannot be set up, see `encryption_after_key_setup`.
+    pub fn prepare_encryption(
+        &mut self,
+        encrypt: bool,
+        allow_plaintext_fallback: bool,
+    ) -> Result<bool> {
+        if !encrypt || self.encryption_manager.is_encryption_enabled() {
+            return Ok(encrypt);
+        }
+
+        let key_ready = match self.encryption_manager.load_encryption_from_keystore() {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                warn!("Failed to set up the encryption key: {}", e);
+                false
+            }
+        };
+        encryption_after_key_setup(key_ready, allow_plaintext_fallback)
+    }
+
+    /// Re-encrypts the given vault files under a new passphrase
+    ///
+    /// The key is loaded from the OS keystore first if this manager has not
+    /// used it yet. See `EncryptionManager::change_passphrase`.
+    pub async fn change_encryption_passphrase(
+        &mut self,
+        old_passphrase: &str,
+        new_passphrase: &str,
+        filenames: &[String],
+    ) -> Result<usize> {
+        if !self.encryption_manager.is_encryption_enabled()
+            && !self.encryption_manager.load_encryption_from_keystore()?
+        {
+            return Err(KiyyaError::Encryption {
+                message: "Encryption is not enabled".to_string(),
+            });
+        }
+
+        let files: Vec<PathBuf> = filenames
+            .iter()
+            .map(|filename| self.vault_path.join(filename))
+            .collect();
+        self.encryption_manager
+            .change_passphrase(old_passphrase, new_passphrase, &files)
+            .await
+    }
+
+    /// Prepares a read of a downloaded file that runs without the manager
+    ///
+    /// The filename is checked and, for encrypted files, the key loaded here;
+    /// the returned reader needs nothing else from the manager, so callers can
+    /// release its lock before reading.
+    pub fn content_range_reader(
+        &mut self,
+        metadata: &OfflineMetadata,
+    ) -> Result<ContentRangeReader> {
+        if Path::new(&metadata.filename).file_name() != Some(metadata.filename.as_ref()) {
+            return Err(KiyyaError::InvalidInput {
+                message: format!("Invalid offline filename: {}", metadata.filename),
+            });
+        }
+        let path = self.vault_path.join(&metadata.filename);
+
+        let decryption = if metadata.encrypted {
+            if !self.encryption_manager.is_encryption_enabled()
+                && !self.encryption_manager.load_encryption_from_keystore()?
+            {
+                return Err(KiyyaError::Encryption {
+                    message: "Encryption is not enabled".to_string(),
+                });
+            }
+            Some(self.encryption_manager.clone())
+        } else {
+            None
+        };
+
+        Ok(ContentRangeReader { path, decryption })
+    }
+
+    /// Directory in-progress downloads are written to
+    pub fn temp_dir(&self) -> &Path {
+        &self.temp_dir
+    }
+
+    /// Writes in-progress downloads to `dir`, or to the vault default for `None`
+    ///
+    /// The caller validates `dir` with `path_security::validate_user_directory`.
+    /// Partial files left in the previous directory are not moved, so those
+    /// downloads start over.
+    pub async fn set_temp_dir(&mut self, dir: Option<PathBuf>) -> Result<()> {
+        let dir = dir.unwrap_or_else(|| self.vault_path.join(DEFAULT_DOWNLOAD_TEMP_SUBDIR));
+        create_dir_all(&dir).await?;
+        info!("Download temp directory set to {:?}", dir);
+        self.temp_dir = dir;
+        Ok(())
+    }
+
+    fn temp_file_path(&self, claim_id: &str, quality: &str) -> PathBuf {
+        self.temp_dir.join(format!("{}-{}.tmp", claim_id, quality))
+    }
+
+    /// Temp file locations to clean up: the current one, and the vault itself
+    /// where older versions kept them
+    fn temp_file_paths(&self, claim_id: &str, quality: &str) -> Vec<PathBuf> {
+        let legacy = self.vault_path.join(format!("{}-{}.tmp", claim_id, quality));
+        let current = self.temp_file_path(claim_id, quality);
+        if current == legacy {
+            vec![current]
+        } else {
+            vec![current, legacy]
+        }
+    }
+
     pub fn get_vault_path(&self) -> &Path {
         &self.vault_path
     }
 
+    /// Returns the shared HTTP client for short requests made outside a download
+    pub fn http_client(&self) -> Client {
+        self.client.clone()
+    }
+
     pub fn get_download_stats(&self) -> crate::models::DownloadStats {
         let total_downloads = self
             .total_downloads
@@ -757,6 +988,7 @@ impl DownloadManager {
         let encryption_manager = EncryptionManager::new().expect("Failed to create encryption manager");
 
         Self {
+            temp_dir: vault_path.join(DEFAULT_DOWNLOAD_TEMP_SUBDIR),
             vault_path,
             client,
             encryption_manager,
@@ -767,6 +999,333 @@ impl DownloadManager {
     }
 }
 
+/// Moves a finished download from the temp directory into the vault
+///
+/// A rename when both are on the same filesystem. Otherwise the file is copied
+/// next to its destination and renamed into place, so the vault never holds a
+/// partly copied file under its final name.
+async fn move_into_vault(from: &Path, to: &Path) -> std::io::Result<()> {
+    if rename(from, to).await.is_ok() {
+        return Ok(());
+    }
+
+    let staging = to.with_extension("moving");
+    let copied = match tokio::fs::copy(from, &staging).await {
+        Ok(_) => rename(&staging, to).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = copied {
+        let _ = remove_file(&staging).await;
+        return Err(e);
+    }
+    remove_file(from).await
+}
+
+/// Encrypts a finished download into the vault
+///
+/// The ciphertext is written next to its destination, synced to disk and
+/// then renamed into place, so the vault never holds a partly encrypted file
+/// under its final name.
+async fn encrypt_into_vault(encryption: &EncryptionManager, from: &Path, to: &Path) -> Result<()> {
+    let staging = to.with_extension("encrypting");
+    let encrypted = async {
+        encryption.encrypt_file(from, &staging).await?;
+        File::open(&staging).await?.sync_all().await?;
+        rename(&staging, to).await?;
+        Ok(())
+    }
+    .await;
+    if encrypted.is_err() {
+        let _ = remove_file(&staging).await;
+    }
+    encrypted
+}
+
+/// Whether an encrypted download goes ahead once the key has been looked up
+///
+/// Without a key the failure is logged as a security event. The download then
+/// continues unencrypted if `allow_plaintext_fallback` is set, and is recorded
+/// as unencrypted; otherwise it fails with `KeyManagement`.
+fn encryption_after_key_setup(key_ready: bool, allow_plaintext_fallback: bool) -> Result<bool> {
+    if key_ready {
+        return Ok(true);
+    }
+
+    log_security_event(SecurityEvent::EncryptionKeyOperation {
+        operation: "access".to_string(),
+        success: false,
+        details: Some(if allow_plaintext_fallback {
+            "Encryption key unavailable, downloading without encryption".to_string()
+        } else {
+            "Encryption key unavailable, download refused".to_string()
+        }),
+    });
+
+    if allow_plaintext_fallback {
+        warn!("Encryption key unavailable; falling back to a plaintext download");
+        Ok(false)
+    } else {
+        Err(KiyyaError::KeyManagement {
+            message: "Encryption key is unavailable and plaintext fallback is disabled".to_string(),
+        })
+    }
+}
+
+/// Total size in bytes of the files under `dir`, including nested
+/// directories such as the partial download directory
+///
+/// Symlinks are not followed.
+pub async fn dir_usage(dir: &Path) -> Result<u64> {
+    let mut total = 0;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if let Ok(metadata) = entry.metadata().await {
+                if metadata.is_dir() {
+                    pending.push(entry.path());
+                } else if metadata.is_file() {
+                    total += metadata.len();
+                }
+            }
+        }
+    }
+    Ok(total)
+}
+
+/// A downloaded file ready for `read`, see `DownloadManager::content_range_reader`
+pub struct ContentRangeReader {
+    path: PathBuf,
+    /// Set for encrypted files, with the key already loaded
+    decryption: Option<EncryptionManager>,
+}
+
+impl ContentRangeReader {
+    /// Reads plaintext bytes `start..=end`
+    ///
+    /// Encrypted files go through the same chunk mapping as streaming, so only
+    /// the chunks covering the range are read and decrypted. `end` is clamped
+    /// to the end of the file, and ranges longer than `MAX_CONTENT_RANGE_BYTES`
+    /// are rejected.
+    pub async fn read(&self, start: u64, end: u64) -> Result<Vec<u8>> {
+        if end < start {
+            return Err(KiyyaError::InvalidInput {
+                message: format!("Range end {} is before start {}", end, start),
+            });
+        }
+        if end - start >= MAX_CONTENT_RANGE_BYTES {
+            return Err(KiyyaError::InvalidInput {
+                message: format!(
+                    "Range of {} bytes exceeds the limit of {} bytes",
+                    end - start + 1,
+                    MAX_CONTENT_RANGE_BYTES
+                ),
+            });
+        }
+
+        if let Some(decryption) = &self.decryption {
+            let size = decryption.plaintext_size(&self.path).await?;
+            let end = clamp_range_end(start, end, size)?;
+            return decryption.decrypt_range(&self.path, start, end).await;
+        }
+
+        let size = tokio::fs::metadata(&self.path).await?.len();
+        let end = clamp_range_end(start, end, size)?;
+        let mut file = File::open(&self.path).await?;
+        file.seek(SeekFrom::Start(start)).await?;
+        let length = end - start + 1;
+        let mut buffer = Vec::with_capacity(length as usize);
+        file.take(length).read_to_end(&mut buffer).await?;
+        Ok(buffer)
+    }
+}
+
+/// Caps `end` at the last byte of a file of `size` bytes
+fn clamp_range_end(start: u64, end: u64, size: u64) -> Result<u64> {
+    if start >= size {
+        return Err(KiyyaError::InvalidInput {
+            message: format!(
+                "Range start {} is past the end of the {} byte file",
+                start, size
+            ),
+        });
+    }
+    Ok(end.min(size - 1))
+}
+
+/// Requests the first `bytes` of `url` so the CDN edge has them ready for playback
+///
+/// The body is read and discarded, and reading stops once `bytes` have
+/// arrived. A URL warmed up within `WARM_UP_TTL` with at least as many bytes
+/// is not requested again. Returns the number of bytes fetched or previously
+/// fetched.
+pub async fn warm_up_range(client: &Client, url: &str, bytes: u64) -> Result<u64> {
+    if bytes == 0 {
+        return Ok(0);
+    }
+
+    if let Some(previous) = recently_warmed(url, bytes) {
+        debug!("Skipping warm-up for recently warmed URL ({} bytes)", previous);
+        return Ok(previous);
+    }
+
+    let mut response = client
+        .get(url)
+        .header("Range", format!("bytes=0-{}", bytes - 1))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let mut fetched = 0u64;
+    while fetched < bytes {
+        match response.chunk().await? {
+            Some(chunk) => fetched += chunk.len() as u64,
+            None => break,
+        }
+    }
+
+    WARMED_URLS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(url.to_string(), (Instant::now(), fetched));
+
+    debug!("Warmed up {} bytes from CDN", fetched);
+    Ok(fetched)
+}
+
+/// How long a HEAD probe may take before the URL counts as unreachable
+const REACHABILITY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of a HEAD request to a CDN URL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UrlProbe {
+    Reachable,
+    /// The CDN refused the content in this region, with the HTTP status it sent
+    RegionBlocked(u16),
+    Unreachable,
+}
+
+/// Checks whether a CDN status means the content is blocked in the user's region
+///
+/// 451 is the dedicated status; the CDN also answers geo-restricted content
+/// with 403, which unlike 404 means the content exists.
+pub fn is_region_block_status(status: reqwest::StatusCode) -> bool {
+    matches!(status.as_u16(), 403 | 451)
+}
+
+/// Sends a HEAD request to `url` and classifies the answer
+///
+/// Timeouts, connection failures and error statuses other than region
+/// blocks count as unreachable.
+pub async fn probe_url(client: &Client, url: &str) -> UrlProbe {
+    match client.head(url).timeout(REACHABILITY_TIMEOUT).send().await {
+        Ok(response) => {
+            let status = response.status();
+            debug!("HEAD {} returned {}", url, status);
+            if status.is_success() {
+                UrlProbe::Reachable
+            } else if is_region_block_status(status) {
+                UrlProbe::RegionBlocked(status.as_u16())
+            } else {
+                UrlProbe::Unreachable
+            }
+        }
+        Err(e) => {
+            debug!("HEAD {} failed: {}", url, e);
+            UrlProbe::Unreachable
+        }
+    }
+}
+
+/// Current state of the download of `claim_id` in `quality`
+pub fn download_state(claim_id: &str, quality: &str) -> DownloadState {
+    DOWNLOAD_STATES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(&(claim_id.to_string(), quality.to_string()))
+        .copied()
+        .unwrap_or(DownloadState::NotStarted)
+}
+
+fn set_download_state(claim_id: &str, quality: &str, state: DownloadState) {
+    DOWNLOAD_STATES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert((claim_id.to_string(), quality.to_string()), state);
+}
+
+/// Records one download's progress through `download_state`
+///
+/// Created when the download is queued. Dropping it before `complete` marks
+/// the download failed, so an error or early return never leaves it shown as
+/// queued or active.
+pub struct DownloadTracker {
+    claim_id: String,
+    quality: String,
+    completed: bool,
+}
+
+impl DownloadTracker {
+    pub fn queue(claim_id: &str, quality: &str) -> Self {
+        set_download_state(claim_id, quality, DownloadState::Queued);
+        Self {
+            claim_id: claim_id.to_string(),
+            quality: quality.to_string(),
+            completed: false,
+        }
+    }
+
+    /// Marks the download active once it holds the download manager
+    pub fn start(&self) {
+        set_download_state(&self.claim_id, &self.quality, DownloadState::Active);
+    }
+
+    pub fn complete(mut self) {
+        self.completed = true;
+        set_download_state(&self.claim_id, &self.quality, DownloadState::Completed);
+    }
+}
+
+impl Drop for DownloadTracker {
+    fn drop(&mut self) {
+        if !self.completed {
+            set_download_state(&self.claim_id, &self.quality, DownloadState::Failed);
+        }
+    }
+}
+
+/// Returns the bytes fetched for `url` if it was warmed up recently enough to skip
+fn recently_warmed(url: &str, bytes: u64) -> Option<u64> {
+    let mut warmed = WARMED_URLS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    warmed.retain(|_, (at, _)| at.elapsed() < WARM_UP_TTL);
+    warmed
+        .get(url)
+        .filter(|(_, fetched)| *fetched >= bytes)
+        .map(|(_, fetched)| *fetched)
+}
+
+/// Best-effort probe of a finished download; a failed probe never fails the download
+async fn probe_downloaded_file(path: &Path) -> Option<MediaProbe> {
+    let path = path.to_path_buf();
+    let result = tokio::task::spawn_blocking(move || media_probe::probe_file(&path)).await;
+    match result {
+        Ok(Ok(probe)) => {
+            debug!("Probed downloaded file: {:?}", probe);
+            Some(probe)
+        }
+        Ok(Err(e)) => {
+            warn!("Could not probe downloaded file: {}", e);
+            None
+        }
+        Err(e) => {
+            warn!("Media probe task failed: {}", e);
+            None
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -784,6 +1343,7 @@ mod tests {
         let encryption_manager = EncryptionManager::new().unwrap();
 
         DownloadManager {
+            temp_dir: vault_path.join(DEFAULT_DOWNLOAD_TEMP_SUBDIR),
             vault_path,
             client,
             encryption_manager,
@@ -1010,4 +1570,344 @@ mod tests {
         let result = manager.cleanup_failed_download(claim_id, quality).await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_find_and_remove_orphaned_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let vault_path = temp_dir.path().to_path_buf();
+        let manager = create_test_manager(vault_path.clone());
+        let two_hours_ago = filetime::FileTime::from_system_time(
+            std::time::SystemTime::now() - std::time::Duration::from_secs(7200),
+        );
+
+        let known = vault_path.join("known-claim-720p.mp4");
+        let orphan = vault_path.join("lost-claim-720p.mp4");
+        let orphan_encrypted = vault_path.join("0b7c.bin");
+        let fresh = vault_path.join("fresh-claim-720p.mp4");
+        let partial = vault_path.join("other-claim-720p.tmp");
+        let aged = [(&known, 10), (&orphan, 20), (&orphan_encrypted, 30), (&partial, 40)];
+        for (path, size) in aged {
+            write(path, vec![0u8; size]).await.unwrap();
+            filetime::set_file_mtime(path, two_hours_ago).unwrap();
+        }
+        write(&fresh, b"new").await.unwrap();
+
+        let known_filenames: HashSet<String> =
+            ["known-claim-720p.mp4".to_string()].into_iter().collect();
+
+        let mut orphans = manager.find_orphaned_files(&known_filenames).await.unwrap();
+        orphans.sort();
+        assert_eq!(orphans, vec![(orphan_encrypted.clone(), 30), (orphan.clone(), 20)]);
+        assert_eq!(manager.vault_usage().await.unwrap(), 103);
+
+        let (removed, bytes) = manager.remove_orphaned_files(&known_filenames).await.unwrap();
+        assert_eq!((removed, bytes), (2, 50));
+        assert!(known.exists());
+        assert!(fresh.exists());
+        assert!(partial.exists());
+        assert!(!orphan.exists());
+        assert!(!orphan_encrypted.exists());
+    }
+
+    #[tokio::test]
+    async fn test_probe_downloaded_file_is_best_effort() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mp4_path = temp_dir.path().join("movie.tmp");
+        write(&mp4_path, crate::media_probe::tests::sample_mp4(true))
+            .await
+            .unwrap();
+        let probe = probe_downloaded_file(&mp4_path).await.unwrap();
+        assert_eq!(probe.width, Some(1280));
+        assert_eq!(probe.video_codec.as_deref(), Some("h264"));
+
+        let garbage_path = temp_dir.path().join("garbage.tmp");
+        write(&garbage_path, b"not a video").await.unwrap();
+        assert_eq!(probe_downloaded_file(&garbage_path).await, None);
+        assert_eq!(probe_downloaded_file(&temp_dir.path().join("missing.tmp")).await, None);
+    }
+
+    #[test]
+    fn test_missing_key_falls_back_to_plaintext_when_allowed() {
+        assert!(encryption_after_key_setup(true, true).unwrap());
+        assert!(!encryption_after_key_setup(false, true).unwrap());
+    }
+
+    #[test]
+    fn test_missing_key_fails_download_when_fallback_disabled() {
+        assert!(encryption_after_key_setup(true, false).unwrap());
+        assert!(matches!(
+            encryption_after_key_setup(false, false),
+            Err(KiyyaError::KeyManagement { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unencrypted_download_skips_key_setup() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut manager = create_test_manager(temp_dir.path().to_path_buf());
+
+        assert!(!manager.prepare_encryption(false, false).unwrap());
+        assert!(!manager.encryption_manager.is_encryption_enabled());
+    }
+
+    #[tokio::test]
+    async fn test_dir_usage_counts_nested_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let partial = temp_dir.path().join(DEFAULT_DOWNLOAD_TEMP_SUBDIR);
+        create_dir_all(partial.join("nested")).await.unwrap();
+        let files = [
+            (temp_dir.path().join("movie.mp4"), 100),
+            (partial.join("movie.tmp"), 20),
+            (partial.join("nested").join("chunk"), 3),
+        ];
+        for (path, size) in files {
+            write(path, vec![0u8; size]).await.unwrap();
+        }
+
+        assert_eq!(dir_usage(temp_dir.path()).await.unwrap(), 123);
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_into_vault_leaves_no_partial_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let plain_path = temp_dir.path().join("download.tmp");
+        write(&plain_path, vec![7u8; 4096]).await.unwrap();
+        let final_path = temp_dir.path().join("vault.bin");
+        let staging_path = final_path.with_extension("encrypting");
+
+        // Without a key nothing is left under either name
+        let disabled = EncryptionManager::new().unwrap();
+        assert!(encrypt_into_vault(&disabled, &plain_path, &final_path)
+            .await
+            .is_err());
+        assert!(!final_path.exists());
+        assert!(!staging_path.exists());
+
+        let encryption = EncryptionManager::with_throwaway_key();
+        encrypt_into_vault(&encryption, &plain_path, &final_path)
+            .await
+            .unwrap();
+        assert!(final_path.exists());
+        assert!(!staging_path.exists());
+
+        let decrypted_path = temp_dir.path().join("decrypted");
+        encryption
+            .decrypt_file(&final_path, &decrypted_path)
+            .await
+            .unwrap();
+        assert_eq!(
+            tokio::fs::read(&decrypted_path).await.unwrap(),
+            vec![7u8; 4096]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_temp_files_use_configured_dir_and_finish_in_vault() {
+        let temp_dir = TempDir::new().unwrap();
+        let vault_path = temp_dir.path().join("vault");
+        let fast_drive = temp_dir.path().join("fast-drive");
+        create_dir_all(&vault_path).await.unwrap();
+        let mut manager = create_test_manager(vault_path.clone());
+
+        assert_eq!(
+            manager.temp_file_path("claim", "720p"),
+            vault_path
+                .join(DEFAULT_DOWNLOAD_TEMP_SUBDIR)
+                .join("claim-720p.tmp")
+        );
+
+        manager
+            .set_temp_dir(Some(fast_drive.clone()))
+            .await
+            .unwrap();
+        assert!(fast_drive.is_dir());
+        let temp_path = manager.temp_file_path("claim", "720p");
+        assert_eq!(temp_path, fast_drive.join("claim-720p.tmp"));
+
+        write(&temp_path, b"downloaded content").await.unwrap();
+        let final_path = vault_path.join("claim-720p.mp4");
+        move_into_vault(&temp_path, &final_path).await.unwrap();
+
+        assert!(!temp_path.exists());
+        assert_eq!(
+            tokio::fs::read(&final_path).await.unwrap(),
+            b"downloaded content"
+        );
+        assert_eq!(
+            manager
+                .find_orphaned_files(&HashSet::new())
+                .await
+                .unwrap()
+                .len(),
+            0
+        );
+
+        // A failed download is cleaned up from the configured directory too
+        write(&temp_path, b"partial").await.unwrap();
+        manager
+            .cleanup_failed_download("claim", "720p")
+            .await
+            .unwrap();
+        assert!(!temp_path.exists());
+
+        manager.set_temp_dir(None).await.unwrap();
+        assert_eq!(
+            manager.temp_dir(),
+            vault_path.join(DEFAULT_DOWNLOAD_TEMP_SUBDIR)
+        );
+    }
+
+    fn offline_file(filename: &str, encrypted: bool) -> OfflineMetadata {
+        OfflineMetadata {
+            claim_id: "range-claim".to_string(),
+            quality: "720p".to_string(),
+            filename: filename.to_string(),
+            file_size: 0,
+            encrypted,
+            added_at: 0,
+            probe: None,
+            title: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_content_range_plaintext() {
+        let temp_dir = TempDir::new().unwrap();
+        let vault_path = temp_dir.path().to_path_buf();
+        let content: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+        write(vault_path.join("range-claim-720p.mp4"), &content)
+            .await
+            .unwrap();
+        let mut manager = create_test_manager(vault_path);
+        let metadata = offline_file("range-claim-720p.mp4", false);
+        assert!(matches!(
+            manager.content_range_reader(&offline_file("../range-claim-720p.mp4", false)),
+            Err(KiyyaError::InvalidInput { .. })
+        ));
+
+        // The reader outlives the manager, as it does the manager's lock
+        let reader = manager.content_range_reader(&metadata).unwrap();
+        drop(manager);
+
+        let bytes = reader.read(100, 199).await.unwrap();
+        assert_eq!(bytes, content[100..=199]);
+
+        // The end is clamped to the file, the start is not
+        let tail = reader.read(9_990, 20_000).await.unwrap();
+        assert_eq!(tail, content[9_990..]);
+        assert!(matches!(
+            reader.read(10_000, 10_010).await,
+            Err(KiyyaError::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            reader.read(0, MAX_CONTENT_RANGE_BYTES).await,
+            Err(KiyyaError::InvalidInput { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_read_content_range_encrypted() {
+        let temp_dir = TempDir::new().unwrap();
+        let vault_path = temp_dir.path().to_path_buf();
+        let plain_path = temp_dir.path().join("plain.mp4");
+        // Spans several encryption chunks so the range crosses a boundary
+        let content: Vec<u8> = (0..200_000).map(|i| (i % 253) as u8).collect();
+        write(&plain_path, &content).await.unwrap();
+
+        // A throwaway key keeps the OS keystore and encryption setting untouched
+        let mut manager = create_test_manager(vault_path.clone());
+        manager.encryption_manager = EncryptionManager::with_throwaway_key();
+        manager
+            .encryption_manager
+            .encrypt_file(&plain_path, &vault_path.join("range.bin"))
+            .await
+            .unwrap();
+        let metadata = offline_file("range.bin", true);
+        let reader = manager.content_range_reader(&metadata).unwrap();
+        drop(manager);
+
+        // Straddles the end of the first 64 KiB chunk
+        let start = 64 * 1024 - 10;
+        let bytes = reader.read(start, start + 99).await.unwrap();
+        assert_eq!(bytes, content[start as usize..start as usize + 100]);
+
+        let tail = reader.read(199_950, 250_000).await.unwrap();
+        assert_eq!(tail, content[199_950..]);
+    }
+
+    #[tokio::test]
+    async fn test_probe_url_classifies_responses() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let client = Client::new();
+        let server = MockServer::start().await;
+        for (route, status) in [("/missing.mp4", 404), ("/forbidden.mp4", 403), ("/legal.mp4", 451)] {
+            Mock::given(method("HEAD"))
+                .and(path(route))
+                .respond_with(ResponseTemplate::new(status))
+                .mount(&server)
+                .await;
+        }
+        let probe = |route: &str| format!("{}{}", server.uri(), route);
+        assert_eq!(
+            probe_url(&client, &probe("/missing.mp4")).await,
+            UrlProbe::Unreachable
+        );
+        assert_eq!(
+            probe_url(&client, &probe("/forbidden.mp4")).await,
+            UrlProbe::RegionBlocked(403)
+        );
+        assert_eq!(
+            probe_url(&client, &probe("/legal.mp4")).await,
+            UrlProbe::RegionBlocked(451)
+        );
+
+        // Nothing listens once the server is dropped
+        let closed = server.uri();
+        drop(server);
+        assert_eq!(
+            probe_url(&client, &format!("{}/gone.mp4", closed)).await,
+            UrlProbe::Unreachable
+        );
+
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        assert_eq!(
+            probe_url(&client, &format!("{}/ok.mp4", server.uri())).await,
+            UrlProbe::Reachable
+        );
+    }
+
+    #[test]
+    fn test_download_tracker_states() {
+        let claim_id = "tracker-test-claim";
+        assert_eq!(
+            download_state(claim_id, "master"),
+            DownloadState::NotStarted
+        );
+
+        let tracker = DownloadTracker::queue(claim_id, "master");
+        assert_eq!(download_state(claim_id, "master"), DownloadState::Queued);
+        assert!(download_state(claim_id, "master").is_downloading());
+
+        tracker.start();
+        assert_eq!(download_state(claim_id, "master"), DownloadState::Active);
+        assert!(download_state(claim_id, "master").is_downloading());
+
+        tracker.complete();
+        assert_eq!(download_state(claim_id, "master"), DownloadState::Completed);
+        assert!(!download_state(claim_id, "master").is_downloading());
+
+        // A tracker dropped without completing counts as failed
+        let tracker = DownloadTracker::queue(claim_id, "720p");
+        tracker.start();
+        drop(tracker);
+        assert_eq!(download_state(claim_id, "720p"), DownloadState::Failed);
+        assert!(!download_state(claim_id, "720p").is_downloading());
+    }
 }
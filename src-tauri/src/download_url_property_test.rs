@@ -0,0 +1,140 @@
+/// Property-Based Tests for Download URL Validation
+///
+/// Download URLs must be HTTPS with a real host. Any other scheme
+/// (`http://`, `file://`, `javascript:`, `data:`) or non-URL input must be
+/// rejected, and the allowlisted variant must only accept known CDN domains,
+/// including their subdomains but not look-alike hosts.
+
+#[cfg(test)]
+mod download_url_property_tests {
+    use crate::validation::{
+        validate_download_url, validate_download_url_allowlisted, ODYSEE_DOWNLOAD_DOMAINS,
+    };
+    use proptest::prelude::*;
+
+    /// Strategy for generating URL paths
+    fn path_strategy() -> impl Strategy<Value = String> {
+        "[a-zA-Z0-9_/-]{0,40}(\\.mp4|\\.m3u8)?".prop_map(|s| s)
+    }
+
+    /// Strategy for generating hostnames
+    fn host_strategy() -> impl Strategy<Value = String> {
+        "[a-z][a-z0-9-]{1,15}\\.(com|net|org|live)".prop_map(|s| s)
+    }
+
+    /// Strategy for generating disallowed schemes
+    fn bad_scheme_url_strategy() -> impl Strategy<Value = String> {
+        (host_strategy(), path_strategy()).prop_flat_map(|(host, path)| {
+            prop_oneof![
+                Just(format!("http://{}/{}", host, path)),
+                Just(format!("ftp://{}/{}", host, path)),
+                Just(format!("file:///{}", path)),
+                Just(format!("javascript:alert('{}')", host)),
+                Just(format!("data:text/html,{}", path)),
+                Just(format!("HTTP://{}/{}", host, path)),
+                Just(format!("//{}/{}", host, path)),
+                Just(format!("{}/{}", host, path)),
+            ]
+        })
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(50))]
+
+        /// Property: Only HTTPS URLs are accepted
+        #[test]
+        fn prop_non_https_urls_rejected(url in bad_scheme_url_strategy()) {
+            prop_assert!(
+                validate_download_url(&url).is_err(),
+                "Non-HTTPS URL should be rejected: {}",
+                url
+            );
+        }
+
+        /// Property: HTTPS URLs with a valid host are accepted unchanged
+        #[test]
+        fn prop_https_urls_accepted(host in host_strategy(), path in path_strategy()) {
+            let url = format!("https://{}/{}", host, path);
+            let validated = validate_download_url(&url);
+            prop_assert!(validated.is_ok(), "HTTPS URL should be accepted: {}", url);
+            prop_assert_eq!(validated.unwrap(), url);
+        }
+
+        /// Property: Known CDN domains and their subdomains pass the allowlist
+        #[test]
+        fn prop_allowlisted_cdn_urls_accepted(
+            domain in proptest::sample::select(ODYSEE_DOWNLOAD_DOMAINS.to_vec()),
+            subdomain in proptest::option::of("[a-z][a-z0-9]{1,10}"),
+            path in path_strategy(),
+        ) {
+            let host = match subdomain {
+                Some(sub) => format!("{}.{}", sub, domain),
+                None => domain.to_string(),
+            };
+            let url = format!("https://{}/{}", host, path);
+            prop_assert!(
+                validate_download_url_allowlisted(&url, ODYSEE_DOWNLOAD_DOMAINS).is_ok(),
+                "Allowlisted URL should be accepted: {}",
+                url
+            );
+        }
+
+        /// Property: Look-alike hosts never pass the allowlist
+        #[test]
+        fn prop_lookalike_hosts_rejected(
+            domain in proptest::sample::select(ODYSEE_DOWNLOAD_DOMAINS.to_vec()),
+            attacker in host_strategy(),
+            path in path_strategy(),
+        ) {
+            let lookalikes = vec![
+                format!("https://{}.{}/{}", domain, attacker, path),
+                format!("https://evil{}/{}", domain, path),
+                format!("https://{}@{}/{}", domain, attacker, path),
+            ];
+            for url in lookalikes {
+                prop_assert!(
+                    validate_download_url_allowlisted(&url, ODYSEE_DOWNLOAD_DOMAINS).is_err(),
+                    "Look-alike URL should be rejected: {}",
+                    url
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_known_dangerous_inputs_rejected() {
+        let dangerous = vec![
+            "http://player.odycdn.com/video.mp4",
+            "javascript:alert(1)",
+            "file:///etc/passwd",
+            "https://",
+            "not a url",
+            "",
+        ];
+
+        for url in dangerous {
+            assert!(
+                validate_download_url(url).is_err(),
+                "Dangerous input should be rejected: {}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn test_valid_cdn_urls_accepted() {
+        let valid = vec![
+            "https://player.odycdn.com/api/v4/streams/free/movie/abc123/def456.mp4",
+            "https://cloud.odysee.live/content/abc123/master.m3u8",
+            "https://odysee.com/$/download/movie/abc123",
+        ];
+
+        for url in valid {
+            assert!(
+                validate_download_url_allowlisted(url, ODYSEE_DOWNLOAD_DOMAINS).is_ok(),
+                "CDN URL should be accepted: {}",
+                url
+            );
+        }
+    }
+}
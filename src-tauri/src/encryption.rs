@@ -1,17 +1,21 @@
 // Allow unused code in encryption.rs - encryption features are planned for future use
 #![allow(dead_code)]
 
+use crate::disk_io;
 use crate::error::{KiyyaError, Result};
+use crate::models::EncryptionBenchmark;
 use crate::security_logging::{log_security_event, SecurityEvent};
 use aes_gcm::{
-    aead::{generic_array::GenericArray, Aead},
+    aead::{generic_array::GenericArray, Aead, Payload},
     Aes256Gcm, KeyInit, Nonce,
 };
 use base64::{engine::general_purpose, Engine as _};
 use keyring::Entry;
+use once_cell::sync::Lazy;
 use rand::RngCore;
-use std::path::Path;
-use tokio::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::fs::{remove_file, rename, File};
 use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, SeekFrom};
 use tracing::{info, warn};
 
@@ -19,6 +23,66 @@ const KEYRING_SERVICE: &str = "Kiyya";
 const KEYRING_USER: &str = "encryption_key";
 const NONCE_SIZE: usize = 12;
 const KEY_SIZE: usize = 32;
+/// Plaintext bytes per encrypted chunk
+const CHUNK_SIZE: usize = 64 * 1024;
+/// AES-GCM authentication tag appended to every chunk
+const TAG_SIZE: u64 = 16;
+/// Largest framed chunk a well-formed file can contain
+const MAX_ENCRYPTED_CHUNK_SIZE: u64 = CHUNK_SIZE as u64 + TAG_SIZE;
+/// Associated data authenticated with the last chunk of every file
+///
+/// A file cut short on a chunk boundary then ends in a chunk sealed without
+/// it, so the truncation fails authentication like any other tampering.
+const FINAL_CHUNK_AAD: &[u8] = b"kiyya-final-chunk";
+/// Starts every file written in the current format, followed by its version
+///
+/// Files written before the header existed start directly with the base
+/// nonce; see `ChunkFormat::Legacy`.
+const FORMAT_MAGIC: &[u8; 7] = b"KIYYAEN";
+/// Version byte written after `FORMAT_MAGIC`
+const FORMAT_VERSION: u8 = 2;
+/// Bytes of format header ahead of the base nonce
+const HEADER_SIZE: usize = FORMAT_MAGIC.len() + 1;
+
+/// Result of the most recent encryption benchmark, for the debug package
+static LAST_BENCHMARK: Lazy<std::sync::Mutex<Option<EncryptionBenchmark>>> =
+    Lazy::new(|| std::sync::Mutex::new(None));
+
+/// Chunk layout of an encrypted file, told apart by its format header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkFormat {
+    /// Written before the format header: no chunk is marked final, so an
+    /// empty input has no chunks and dropping whole chunks goes unnoticed
+    Legacy,
+    /// Version 2: the last chunk is sealed with `FINAL_CHUNK_AAD`
+    Current,
+}
+
+impl ChunkFormat {
+    /// Offset of the first chunk's size prefix
+    fn chunks_start(self) -> u64 {
+        match self {
+            Self::Legacy => NONCE_SIZE as u64,
+            Self::Current => (HEADER_SIZE + NONCE_SIZE) as u64,
+        }
+    }
+
+    /// Associated data for a chunk, marking the last one of a current file
+    fn chunk_aad(self, is_final: bool) -> &'static [u8] {
+        if is_final && self == Self::Current {
+            FINAL_CHUNK_AAD
+        } else {
+            &[]
+        }
+    }
+}
+
+/// Format and base nonce read from the start of an encrypted file
+#[derive(Debug, Clone, Copy)]
+struct FileHeader {
+    format: ChunkFormat,
+    nonce: [u8; NONCE_SIZE],
+}
 
 #[derive(Debug, Clone)]
 struct ChunkInfo {
@@ -26,13 +90,28 @@ struct ChunkInfo {
     encrypted_size: u64,
 }
 
+#[derive(Clone)]
 pub struct EncryptionManager {
     cipher: Option<Aes256Gcm>,
+    key: Option<[u8; KEY_SIZE]>,
 }
 
 impl EncryptionManager {
     pub fn new() -> Result<Self> {
-        Ok(Self { cipher: None })
+        Ok(Self {
+            cipher: None,
+            key: None,
+        })
+    }
+
+    /// Manager with a random in-memory key that never reaches the keystore
+    pub(crate) fn with_throwaway_key() -> Self {
+        let mut key = [0u8; KEY_SIZE];
+        rand::thread_rng().fill_bytes(&mut key);
+        Self {
+            cipher: Some(Aes256Gcm::new(GenericArray::from_slice(&key))),
+            key: Some(key),
+        }
     }
 
     pub fn is_encryption_enabled(&self) -> bool {
@@ -65,6 +144,7 @@ impl EncryptionManager {
         // Initialize cipher
         let key_array = GenericArray::from_slice(&key);
         self.cipher = Some(Aes256Gcm::new(key_array));
+        self.key = Some(key);
 
         info!("Encryption enabled successfully");
         Ok(())
@@ -75,6 +155,7 @@ impl EncryptionManager {
             Ok(key) => {
                 let key_array = GenericArray::from_slice(&key);
                 self.cipher = Some(Aes256Gcm::new(key_array));
+                self.key = Some(key);
 
                 log_security_event(SecurityEvent::EncryptionKeyOperation {
                     operation: "access".to_string(),
@@ -100,6 +181,7 @@ impl EncryptionManager {
 
     pub fn disable_encryption(&mut self) -> Result<()> {
         self.cipher = None;
+        self.key = None;
 
         // Remove key from keystore
         match self.remove_key_from_keystore() {
@@ -124,6 +206,119 @@ impl EncryptionManager {
         Ok(())
     }
 
+    /// Re-encrypts `files` under the key derived from `new_passphrase`
+    ///
+    /// `old_passphrase` must derive the key currently in use, otherwise
+    /// `InvalidPassphrase` is returned before any file is touched. Each file
+    /// is re-encrypted into a sibling staging file; the originals are only
+    /// swapped out, and the new key stored, once every file has been staged.
+    /// A failure at any step leaves the original files and key in place.
+    /// Returns the number of files re-encrypted.
+    pub async fn change_passphrase(
+        &mut self,
+        old_passphrase: &str,
+        new_passphrase: &str,
+        files: &[PathBuf],
+    ) -> Result<usize> {
+        let current_key = self.key.ok_or_else(|| KiyyaError::Encryption {
+            message: "Encryption not enabled".to_string(),
+        })?;
+
+        if Self::derive_key_from_passphrase(old_passphrase)? != current_key {
+            log_security_event(SecurityEvent::EncryptionKeyOperation {
+                operation: "change_passphrase".to_string(),
+                success: false,
+                details: Some("Current passphrase did not match".to_string()),
+            });
+            return Err(KiyyaError::InvalidPassphrase);
+        }
+
+        let new_key = Self::derive_key_from_passphrase(new_passphrase)?;
+        let new_cipher = Aes256Gcm::new(GenericArray::from_slice(&new_key));
+
+        let result = self
+            .swap_in_reencrypted(&new_cipher, &new_key, files, |key| {
+                self.store_key_in_keystore(key)
+            })
+            .await;
+        log_security_event(SecurityEvent::EncryptionKeyOperation {
+            operation: "change_passphrase".to_string(),
+            success: result.is_ok(),
+            details: Some(match &result {
+                Ok(_) => format!("Re-encrypted {} files", files.len()),
+                Err(e) => format!("Passphrase change rolled back: {}", e),
+            }),
+        });
+        result?;
+
+        self.cipher = Some(new_cipher);
+        self.key = Some(new_key);
+        info!("Encryption passphrase changed for {} files", files.len());
+        Ok(files.len())
+    }
+
+    /// Re-encrypts `files` under `new_cipher` and records `new_key` with `store_key`
+    ///
+    /// Either every file and the stored key change, or none do: a failure
+    /// while staging, swapping or storing the key puts the originals back.
+    async fn swap_in_reencrypted<F>(
+        &self,
+        new_cipher: &Aes256Gcm,
+        new_key: &[u8; KEY_SIZE],
+        files: &[PathBuf],
+        store_key: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&[u8; KEY_SIZE]) -> Result<()>,
+    {
+        let old_cipher = self.cipher.as_ref().ok_or_else(|| KiyyaError::Encryption {
+            message: "Encryption not enabled".to_string(),
+        })?;
+
+        // Stage every file before touching any original
+        let mut staged = Vec::with_capacity(files.len());
+        for file in files {
+            let staged_path = sibling_path(file, "rekey");
+            staged.push(staged_path.clone());
+            if let Err(e) = reencrypt_file(old_cipher, new_cipher, file, &staged_path).await {
+                remove_files(&staged).await;
+                return Err(e);
+            }
+        }
+
+        // Move the originals aside and the staged files into place
+        let mut swapped = Vec::with_capacity(files.len());
+        for (file, staged_path) in files.iter().zip(&staged) {
+            let backup_path = sibling_path(file, "bak");
+            let result = match rename(file, &backup_path).await {
+                Ok(()) => {
+                    swapped.push((file.clone(), backup_path));
+                    rename(staged_path, file).await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                restore_backups(&swapped).await;
+                remove_files(&staged).await;
+                return Err(e.into());
+            }
+        }
+
+        if let Err(e) = store_key(new_key) {
+            restore_backups(&swapped).await;
+            return Err(e);
+        }
+
+        let backups: Vec<PathBuf> = swapped.into_iter().map(|(_, backup)| backup).collect();
+        remove_files(&backups).await;
+        Ok(())
+    }
+
+    /// Encrypts `input_path` into `output_path` in 64KB chunks
+    ///
+    /// The file starts with the format header. Every chunk but the last is
+    /// full, and the last is marked final, so even an empty input produces
+    /// one (empty) chunk.
     pub async fn encrypt_file(&self, input_path: &Path, output_path: &Path) -> Result<()> {
         let cipher = self.cipher.as_ref().ok_or_else(|| KiyyaError::Encryption {
             message: "Encryption not enabled".to_string(),
@@ -135,38 +330,30 @@ impl EncryptionManager {
         // Generate random nonce
         let mut nonce_bytes = [0u8; NONCE_SIZE];
         rand::thread_rng().fill_bytes(&mut nonce_bytes);
-        let _nonce: &GenericArray<u8, aes_gcm::aead::consts::U12> = Nonce::from_slice(&nonce_bytes);
 
-        // Write nonce to beginning of file
-        output_file.write_all(&nonce_bytes).await?;
+        write_header(&mut output_file, &nonce_bytes).await?;
 
-        // Encrypt file in chunks
-        const CHUNK_SIZE: usize = 64 * 1024; // 64KB chunks
+        // Encrypt file in chunks, reading one ahead to know which is last
         let mut buffer = vec![0u8; CHUNK_SIZE];
+        let mut next_buffer = vec![0u8; CHUNK_SIZE];
+        let mut bytes_read = read_full_chunk(&mut input_file, &mut buffer).await?;
         let mut chunk_counter = 0u64;
 
         loop {
-            let bytes_read = input_file.read(&mut buffer).await?;
-            if bytes_read == 0 {
-                break;
-            }
-
-            // Create unique nonce for each chunk by combining base nonce with counter
-            let mut chunk_nonce_bytes = nonce_bytes;
-            let counter_bytes = chunk_counter.to_le_bytes();
-            for (i, &byte) in counter_bytes.iter().enumerate() {
-                if i < NONCE_SIZE {
-                    chunk_nonce_bytes[i] ^= byte;
-                }
-            }
-            let chunk_nonce = Nonce::from_slice(&chunk_nonce_bytes);
+            let next_read = if bytes_read == CHUNK_SIZE {
+                read_full_chunk(&mut input_file, &mut next_buffer).await?
+            } else {
+                0
+            };
+            let is_final = next_read == 0;
 
-            let encrypted_chunk =
-                cipher
-                    .encrypt(chunk_nonce, &buffer[..bytes_read])
-                    .map_err(|e| KiyyaError::Encryption {
-                        message: format!("Encryption failed: {}", e),
-                    })?;
+            let encrypted_chunk = seal_chunk(
+                cipher,
+                &nonce_bytes,
+                chunk_counter,
+                is_final,
+                &buffer[..bytes_read],
+            )?;
 
             // Write chunk size and encrypted data
             output_file
@@ -174,6 +361,11 @@ impl EncryptionManager {
                 .await?;
             output_file.write_all(&encrypted_chunk).await?;
 
+            if is_final {
+                break;
+            }
+            std::mem::swap(&mut buffer, &mut next_buffer);
+            bytes_read = next_read;
             chunk_counter += 1;
         }
 
@@ -188,46 +380,23 @@ impl EncryptionManager {
         })?;
 
         let mut input_file = File::open(input_path).await?;
-        let mut output_file = File::create(output_path).await?;
-
-        // Read nonce from beginning of file
-        let mut nonce_bytes = [0u8; NONCE_SIZE];
-        input_file.read_exact(&mut nonce_bytes).await?;
-
-        // Decrypt file in chunks
-        let mut chunk_counter = 0u64;
-
-        loop {
-            // Read chunk size
-            let mut size_bytes = [0u8; 4];
-            match input_file.read_exact(&mut size_bytes).await {
-                Ok(_) => {}
-                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-                Err(e) => return Err(e.into()),
-            }
-
-            let chunk_size = u32::from_le_bytes(size_bytes) as usize;
-            let mut encrypted_chunk = vec![0u8; chunk_size];
-            input_file.read_exact(&mut encrypted_chunk).await?;
 
-            // Create chunk nonce
-            let mut chunk_nonce_bytes = nonce_bytes;
-            let counter_bytes = chunk_counter.to_le_bytes();
-            for (i, &byte) in counter_bytes.iter().enumerate() {
-                if i < NONCE_SIZE {
-                    chunk_nonce_bytes[i] ^= byte;
-                }
-            }
-            let chunk_nonce = Nonce::from_slice(&chunk_nonce_bytes);
-
-            let decrypted_chunk = cipher
-                .decrypt(chunk_nonce, encrypted_chunk.as_slice())
-                .map_err(|e| KiyyaError::Encryption {
-                    message: format!("Decryption failed: {}", e),
-                })?;
+        let header = read_header(&mut input_file, input_path).await?;
+        // The framing is checked before any plaintext is written
+        let chunks = read_chunk_index(&mut input_file, input_path, header.format).await?;
 
+        let mut output_file = File::create(output_path).await?;
+        for (chunk_idx, chunk_info) in chunks.iter().enumerate() {
+            let encrypted_chunk = read_indexed_chunk(&mut input_file, chunk_info).await?;
+            let decrypted_chunk = open_chunk(
+                cipher,
+                &header,
+                chunk_idx as u64,
+                chunk_idx + 1 == chunks.len(),
+                &encrypted_chunk,
+                input_path,
+            )?;
             output_file.write_all(&decrypted_chunk).await?;
-            chunk_counter += 1;
         }
 
         output_file.flush().await?;
@@ -236,125 +405,110 @@ impl EncryptionManager {
     }
 
     pub async fn decrypt_range(&self, input_path: &Path, start: u64, end: u64) -> Result<Vec<u8>> {
+        let mut decryptor = self.open_range(input_path, start, end).await?;
+
+        let mut result = Vec::new();
+        while let Some(piece) = decryptor.next_piece().await? {
+            result.extend_from_slice(&piece);
+        }
+
+        Ok(result)
+    }
+
+    /// Prepares bytes `start..=end` of the plaintext for chunk-by-chunk decryption
+    ///
+    /// Only the chunk framing is read here; each chunk is read, authenticated
+    /// and decrypted when the returned `RangeDecryptor` asks for it, so
+    /// serving a range holds at most one chunk in memory. The decryptor owns
+    /// a copy of the cipher and does not borrow this manager.
+    pub async fn open_range(
+        &self,
+        input_path: &Path,
+        start: u64,
+        end: u64,
+    ) -> Result<RangeDecryptor> {
         let cipher = self.cipher.as_ref().ok_or_else(|| KiyyaError::Encryption {
             message: "Encryption not enabled".to_string(),
         })?;
 
-        let mut input_file = File::open(input_path).await?;
-
-        // Read nonce from beginning of file
-        let mut nonce_bytes = [0u8; NONCE_SIZE];
-        input_file.read_exact(&mut nonce_bytes).await?;
+        let mut file = File::open(input_path).await?;
+        let header = read_header(&mut file, input_path).await?;
 
         // Build chunk index to find which chunks contain the requested range
-        const CHUNK_SIZE: usize = 64 * 1024; // Must match encryption chunk size
-        let chunk_index = self.build_chunk_index(&mut input_file).await?;
+        let chunks = read_chunk_index(&mut file, input_path, header.format).await?;
+        let next_chunk = (start / CHUNK_SIZE as u64) as usize;
+        let end_chunk = ((end / CHUNK_SIZE as u64) as usize).min(chunks.len());
+
+        Ok(RangeDecryptor {
+            cipher: cipher.clone(),
+            file,
+            path: input_path.to_path_buf(),
+            header,
+            chunks,
+            next_chunk,
+            end_chunk,
+            start,
+            end,
+        })
+    }
 
-        if chunk_index.is_empty() {
-            return Ok(Vec::new());
-        }
+    /// Plaintext length of an encrypted file, worked out from its chunk framing
+    pub async fn plaintext_size(&self, path: &Path) -> Result<u64> {
+        let mut file = File::open(path).await?;
+        let chunks = self.build_chunk_index(&mut file, path).await?;
+        Ok(chunks
+            .iter()
+            .map(|chunk| chunk.encrypted_size.saturating_sub(TAG_SIZE))
+            .sum())
+    }
 
-        // Calculate which chunks we need to decrypt
-        let start_chunk = (start / CHUNK_SIZE as u64) as usize;
-        let end_chunk = (end / CHUNK_SIZE as u64) as usize;
+    /// Authenticates an encrypted file before it is streamed
+    ///
+    /// Checks that the chunk framing accounts for every byte of the file and
+    /// that the first and last chunks authenticate, the last one as the final
+    /// chunk. That catches truncation, including on a chunk boundary, appended
+    /// data and a wrong key without decrypting the whole file. Tampering
+    /// inside other chunks is caught when those chunks are served.
+    ///
+    /// A file in the legacy format has no final chunk to check, so it is
+    /// authenticated in full while being rewritten in the current format.
+    pub async fn check_integrity(&self, path: &Path) -> Result<()> {
+        let cipher = self.cipher.as_ref().ok_or_else(|| KiyyaError::Encryption {
+            message: "Encryption not enabled".to_string(),
+        })?;
 
-        // Validate chunk range
-        if start_chunk >= chunk_index.len() {
-            return Ok(Vec::new());
+        let mut file = File::open(path).await?;
+        let header = read_header(&mut file, path).await?;
+        if header.format == ChunkFormat::Legacy {
+            drop(file);
+            return upgrade_legacy_file(cipher, path).await;
         }
+        let chunk_index = read_chunk_index(&mut file, path, header.format).await?;
 
-        let end_chunk = std::cmp::min(end_chunk, chunk_index.len() - 1);
-
-        // Decrypt only the required chunks
-        let mut result = Vec::new();
-
-        for chunk_idx in start_chunk..=end_chunk {
-            let chunk_info = &chunk_index[chunk_idx];
-
-            // Seek to chunk position
-            input_file
-                .seek(SeekFrom::Start(chunk_info.file_offset))
-                .await?;
-
-            // Read chunk size
-            let mut size_bytes = [0u8; 4];
-            input_file.read_exact(&mut size_bytes).await?;
-            let chunk_size = u32::from_le_bytes(size_bytes) as usize;
-
-            // Read encrypted chunk
-            let mut encrypted_chunk = vec![0u8; chunk_size];
-            input_file.read_exact(&mut encrypted_chunk).await?;
-
-            // Create chunk nonce
-            let mut chunk_nonce_bytes = nonce_bytes;
-            let counter_bytes = (chunk_idx as u64).to_le_bytes();
-            for (i, &byte) in counter_bytes.iter().enumerate() {
-                if i < NONCE_SIZE {
-                    chunk_nonce_bytes[i] ^= byte;
-                }
-            }
-            let chunk_nonce = Nonce::from_slice(&chunk_nonce_bytes);
-
-            // Decrypt chunk
-            let decrypted_chunk = cipher
-                .decrypt(chunk_nonce, encrypted_chunk.as_slice())
-                .map_err(|e| KiyyaError::Encryption {
-                    message: format!("Decryption failed for chunk {}: {}", chunk_idx, e),
-                })?;
-
-            // Calculate which bytes from this chunk we need
-            let chunk_start_pos = chunk_idx as u64 * CHUNK_SIZE as u64;
-            let chunk_end_pos = chunk_start_pos + decrypted_chunk.len() as u64 - 1;
-
-            let copy_start = if start > chunk_start_pos {
-                (start - chunk_start_pos) as usize
-            } else {
-                0
-            };
-
-            let copy_end = if end < chunk_end_pos {
-                (end - chunk_start_pos + 1) as usize
-            } else {
-                decrypted_chunk.len()
-            };
+        let mut to_check = vec![0];
+        if chunk_index.len() > 1 {
+            to_check.push(chunk_index.len() - 1);
+        }
 
-            // Append the relevant portion of this chunk
-            if copy_start < decrypted_chunk.len() && copy_start < copy_end {
-                result.extend_from_slice(&decrypted_chunk[copy_start..copy_end]);
-            }
+        for chunk_idx in to_check {
+            let encrypted_chunk = read_indexed_chunk(&mut file, &chunk_index[chunk_idx]).await?;
+            open_chunk(
+                cipher,
+                &header,
+                chunk_idx as u64,
+                chunk_idx + 1 == chunk_index.len(),
+                &encrypted_chunk,
+                path,
+            )?;
         }
 
-        Ok(result)
+        Ok(())
     }
 
-    async fn build_chunk_index(&self, file: &mut File) -> Result<Vec<ChunkInfo>> {
-        let mut index = Vec::new();
-        let mut file_offset = NONCE_SIZE as u64; // Start after nonce
-
-        file.seek(SeekFrom::Start(file_offset)).await?;
-
-        loop {
-            // Try to read chunk size
-            let mut size_bytes = [0u8; 4];
-            match file.read_exact(&mut size_bytes).await {
-                Ok(_) => {}
-                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-                Err(e) => return Err(e.into()),
-            }
-
-            let chunk_size = u32::from_le_bytes(size_bytes) as u64;
-
-            index.push(ChunkInfo {
-                file_offset,
-                encrypted_size: chunk_size,
-            });
-
-            // Skip to next chunk (4 bytes for size + chunk data)
-            file_offset += 4 + chunk_size;
-            file.seek(SeekFrom::Start(file_offset)).await?;
-        }
-
-        Ok(index)
+    /// Lists the chunks of an encrypted file, rejecting malformed framing
+    async fn build_chunk_index(&self, file: &mut File, path: &Path) -> Result<Vec<ChunkInfo>> {
+        let header = read_header(file, path).await?;
+        read_chunk_index(file, path, header.format).await
     }
 
     fn derive_key_from_passphrase(passphrase: &str) -> Result<[u8; KEY_SIZE]> {
@@ -407,6 +561,419 @@ impl EncryptionManager {
     }
 }
 
+/// Measures how fast this machine encrypts and decrypts offline files
+///
+/// Runs `encrypt_file` and `decrypt_file` over `size_mb` of generated data in
+/// the system temp directory, using a throwaway key that never reaches the
+/// keystore. The files are removed afterwards. The result is kept for
+/// `last_benchmark`.
+pub async fn benchmark(size_mb: u32) -> Result<EncryptionBenchmark> {
+    benchmark_in(size_mb, &std::env::temp_dir()).await
+}
+
+/// `benchmark` with its scratch files in `temp_dir`
+async fn benchmark_in(size_mb: u32, temp_dir: &Path) -> Result<EncryptionBenchmark> {
+    let manager = EncryptionManager::with_throwaway_key();
+
+    let run_id = uuid::Uuid::new_v4();
+    let plain_path = temp_dir.join(format!("kiyya_benchmark_{}.plain", run_id));
+    let encrypted_path = temp_dir.join(format!("kiyya_benchmark_{}.bin", run_id));
+    let decrypted_path = temp_dir.join(format!("kiyya_benchmark_{}.out", run_id));
+    let result = async {
+        let mut block = vec![0u8; 1024 * 1024];
+        rand::thread_rng().fill_bytes(&mut block);
+        let mut plain_file = File::create(&plain_path).await?;
+        for _ in 0..size_mb {
+            plain_file.write_all(&block).await?;
+        }
+        plain_file.flush().await?;
+        drop(plain_file);
+
+        let started = Instant::now();
+        manager.encrypt_file(&plain_path, &encrypted_path).await?;
+        let encrypt_secs = started.elapsed().as_secs_f64();
+
+        let started = Instant::now();
+        manager
+            .decrypt_file(&encrypted_path, &decrypted_path)
+            .await?;
+        let decrypt_secs = started.elapsed().as_secs_f64();
+
+        Ok::<_, KiyyaError>((encrypt_secs, decrypt_secs))
+    }
+    .await;
+    remove_files(&[plain_path, encrypted_path, decrypted_path]).await;
+    let (encrypt_secs, decrypt_secs) = result?;
+
+    let benchmark = EncryptionBenchmark {
+        size_mb,
+        encrypt_mb_per_sec: f64::from(size_mb) / encrypt_secs.max(f64::EPSILON),
+        decrypt_mb_per_sec: f64::from(size_mb) / decrypt_secs.max(f64::EPSILON),
+        measured_at: chrono::Utc::now().timestamp(),
+    };
+    info!(
+        "Encryption benchmark ({} MB): encrypt {:.1} MB/s, decrypt {:.1} MB/s",
+        size_mb, benchmark.encrypt_mb_per_sec, benchmark.decrypt_mb_per_sec
+    );
+
+    if let Ok(mut last) = LAST_BENCHMARK.lock() {
+        *last = Some(benchmark.clone());
+    }
+    Ok(benchmark)
+}
+
+/// Result of the most recent `benchmark` run this session
+pub fn last_benchmark() -> Option<EncryptionBenchmark> {
+    LAST_BENCHMARK.lock().ok().and_then(|last| last.clone())
+}
+
+/// Decrypts a plaintext byte range one chunk at a time
+///
+/// Created by `EncryptionManager::open_range`.
+pub struct RangeDecryptor {
+    cipher: Aes256Gcm,
+    file: File,
+    path: PathBuf,
+    header: FileHeader,
+    chunks: Vec<ChunkInfo>,
+    next_chunk: usize,
+    /// Last chunk of the range, or `chunks.len()` when the range runs past the end
+    end_chunk: usize,
+    start: u64,
+    end: u64,
+}
+
+impl RangeDecryptor {
+    /// Decrypts the next chunk and returns the part of it inside the range
+    ///
+    /// Returns `None` once the range is exhausted. Each piece is at most one
+    /// chunk (64KB) long.
+    pub async fn next_piece(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.next_chunk > self.end_chunk || self.next_chunk >= self.chunks.len() {
+            return Ok(None);
+        }
+        let chunk_idx = self.next_chunk;
+        self.next_chunk += 1;
+        let chunk_info = &self.chunks[chunk_idx];
+
+        let read_started = Instant::now();
+        let encrypted_chunk = read_indexed_chunk(&mut self.file, chunk_info).await?;
+        disk_io::STREAM_READS.record(encrypted_chunk.len(), read_started.elapsed());
+
+        let decrypt_started = Instant::now();
+        let mut decrypted_chunk = open_chunk(
+            &self.cipher,
+            &self.header,
+            chunk_idx as u64,
+            chunk_idx + 1 == self.chunks.len(),
+            &encrypted_chunk,
+            &self.path,
+        )?;
+        disk_io::STREAM_DECRYPTION.record(decrypted_chunk.len(), decrypt_started.elapsed());
+
+        // Trim to the requested bytes
+        let chunk_start_pos = chunk_idx as u64 * CHUNK_SIZE as u64;
+        let copy_start = self.start.saturating_sub(chunk_start_pos) as usize;
+        let copy_end = if self.end < chunk_start_pos + decrypted_chunk.len() as u64 {
+            (self.end - chunk_start_pos + 1) as usize
+        } else {
+            decrypted_chunk.len()
+        };
+
+        if copy_start >= copy_end {
+            return Ok(Some(Vec::new()));
+        }
+        decrypted_chunk.truncate(copy_end);
+        decrypted_chunk.drain(..copy_start);
+        Ok(Some(decrypted_chunk))
+    }
+}
+
+/// Path next to `path` with `suffix` appended to the file name
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    name.push(format!(".{}", suffix));
+    path.with_file_name(name)
+}
+
+/// Nonce for chunk `counter` of a file whose base nonce is `base`
+fn chunk_nonce(base: &[u8; NONCE_SIZE], counter: u64) -> [u8; NONCE_SIZE] {
+    let mut nonce = *base;
+    for (i, &byte) in counter.to_le_bytes().iter().enumerate() {
+        nonce[i] ^= byte;
+    }
+    nonce
+}
+
+/// Encrypts chunk `counter` of a current-format file whose base nonce is `base`
+fn seal_chunk(
+    cipher: &Aes256Gcm,
+    base: &[u8; NONCE_SIZE],
+    counter: u64,
+    is_final: bool,
+    plaintext: &[u8],
+) -> Result<Vec<u8>> {
+    let nonce = chunk_nonce(base, counter);
+    let payload = Payload {
+        msg: plaintext,
+        aad: ChunkFormat::Current.chunk_aad(is_final),
+    };
+    cipher
+        .encrypt(Nonce::from_slice(&nonce), payload)
+        .map_err(|e| KiyyaError::Encryption {
+            message: format!("Encryption failed: {}", e),
+        })
+}
+
+/// Authenticates and decrypts chunk `counter` of the file at `path`
+fn open_chunk(
+    cipher: &Aes256Gcm,
+    header: &FileHeader,
+    counter: u64,
+    is_final: bool,
+    ciphertext: &[u8],
+    path: &Path,
+) -> Result<Vec<u8>> {
+    let nonce = chunk_nonce(&header.nonce, counter);
+    let payload = Payload {
+        msg: ciphertext,
+        aad: header.format.chunk_aad(is_final),
+    };
+    cipher
+        .decrypt(Nonce::from_slice(&nonce), payload)
+        .map_err(|_| integrity_failure(path, counter))
+}
+
+/// Fills `buffer` from `file`, stopping short only at end of file
+async fn read_full_chunk(file: &mut File, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        let read = file.read(&mut buffer[filled..]).await?;
+        if read == 0 {
+            break;
+        }
+        filled += read;
+    }
+    Ok(filled)
+}
+
+/// Reads the ciphertext of one chunk listed by `read_chunk_index`
+async fn read_indexed_chunk(file: &mut File, chunk: &ChunkInfo) -> Result<Vec<u8>> {
+    // Skip the size prefix, which the index already holds
+    file.seek(SeekFrom::Start(chunk.file_offset + 4)).await?;
+    let mut encrypted_chunk = vec![0u8; chunk.encrypted_size as usize];
+    file.read_exact(&mut encrypted_chunk).await?;
+    Ok(encrypted_chunk)
+}
+
+/// Lists the chunks of an encrypted file, rejecting malformed framing
+///
+/// Every chunk but the last holds a full `CHUNK_SIZE` of plaintext. A
+/// current-format file has at least one chunk; a legacy file of an empty
+/// input has none.
+async fn read_chunk_index(
+    file: &mut File,
+    path: &Path,
+    format: ChunkFormat,
+) -> Result<Vec<ChunkInfo>> {
+    let file_len = file.metadata().await?.len();
+    let mut index = Vec::new();
+    let mut file_offset = format.chunks_start();
+
+    file.seek(SeekFrom::Start(file_offset)).await?;
+
+    loop {
+        // Try to read chunk size
+        let mut size_bytes = [0u8; 4];
+        match file.read_exact(&mut size_bytes).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+
+        let chunk_size = u32::from_le_bytes(size_bytes) as u64;
+        if chunk_size > MAX_ENCRYPTED_CHUNK_SIZE || file_offset + 4 + chunk_size > file_len {
+            return Err(integrity_failure(path, index.len() as u64));
+        }
+
+        index.push(ChunkInfo {
+            file_offset,
+            encrypted_size: chunk_size,
+        });
+
+        // Skip to next chunk (4 bytes for size + chunk data)
+        file_offset += 4 + chunk_size;
+        file.seek(SeekFrom::Start(file_offset)).await?;
+    }
+
+    // A partial size prefix left over at the end means the file was cut short
+    if file_offset != file_len || (index.is_empty() && format == ChunkFormat::Current) {
+        return Err(integrity_failure(path, index.len() as u64));
+    }
+    let short_chunk = index[..index.len().saturating_sub(1)]
+        .iter()
+        .position(|chunk| chunk.encrypted_size != MAX_ENCRYPTED_CHUNK_SIZE);
+    if let Some(chunk_idx) = short_chunk {
+        return Err(integrity_failure(path, chunk_idx as u64));
+    }
+
+    Ok(index)
+}
+
+/// Writes the current format header and the per-file base nonce
+async fn write_header(file: &mut File, nonce: &[u8; NONCE_SIZE]) -> Result<()> {
+    file.write_all(FORMAT_MAGIC).await?;
+    file.write_all(&[FORMAT_VERSION]).await?;
+    file.write_all(nonce).await?;
+    Ok(())
+}
+
+/// Reads the format header and base nonce that start an encrypted file
+///
+/// A file without the header is a legacy file that starts with the nonce.
+async fn read_header(file: &mut File, path: &Path) -> Result<FileHeader> {
+    file.seek(SeekFrom::Start(0)).await?;
+    let mut start = [0u8; HEADER_SIZE + NONCE_SIZE];
+    let read = read_full_chunk(file, &mut start).await?;
+    if read < NONCE_SIZE {
+        return Err(integrity_failure(path, 0));
+    }
+
+    if &start[..FORMAT_MAGIC.len()] != FORMAT_MAGIC {
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce.copy_from_slice(&start[..NONCE_SIZE]);
+        return Ok(FileHeader {
+            format: ChunkFormat::Legacy,
+            nonce,
+        });
+    }
+    if start[FORMAT_MAGIC.len()] != FORMAT_VERSION {
+        return Err(KiyyaError::Encryption {
+            message: format!(
+                "Unsupported encrypted file format version {} in {:?}",
+                start[FORMAT_MAGIC.len()],
+                path
+            ),
+        });
+    }
+    if read < start.len() {
+        return Err(integrity_failure(path, 0));
+    }
+
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce.copy_from_slice(&start[HEADER_SIZE..]);
+    Ok(FileHeader {
+        format: ChunkFormat::Current,
+        nonce,
+    })
+}
+
+/// Records a chunk that failed authentication and builds the matching error
+///
+/// With AES-GCM a wrong key and modified ciphertext look the same: the tag
+/// does not verify. Either way the file cannot be trusted.
+fn integrity_failure(path: &Path, chunk: u64) -> KiyyaError {
+    log_security_event(SecurityEvent::SuspiciousActivity {
+        activity_type: "integrity_check_failed".to_string(),
+        details: format!("Chunk {} of {:?} failed authentication", chunk, path),
+        source: "encryption".to_string(),
+    });
+    KiyyaError::IntegrityCheckFailed {
+        file_path: path.display().to_string(),
+    }
+}
+
+/// Decrypts `input_path` chunk by chunk and writes it re-encrypted to `output_path`
+///
+/// The plaintext never reaches disk. The output is always in the current
+/// format, with a fresh base nonce; a legacy input with no chunks gains the
+/// empty final chunk.
+async fn reencrypt_file(
+    old_cipher: &Aes256Gcm,
+    new_cipher: &Aes256Gcm,
+    input_path: &Path,
+    output_path: &Path,
+) -> Result<()> {
+    let mut input_file = File::open(input_path).await?;
+    let old_header = read_header(&mut input_file, input_path).await?;
+    let chunks = read_chunk_index(&mut input_file, input_path, old_header.format).await?;
+
+    let mut output_file = File::create(output_path).await?;
+    let mut new_nonce = [0u8; NONCE_SIZE];
+    rand::thread_rng().fill_bytes(&mut new_nonce);
+    write_header(&mut output_file, &new_nonce).await?;
+
+    for chunk_idx in 0..chunks.len().max(1) {
+        let counter = chunk_idx as u64;
+        let is_final = chunk_idx + 1 >= chunks.len();
+        let plaintext = match chunks.get(chunk_idx) {
+            Some(chunk_info) => {
+                let encrypted_chunk = read_indexed_chunk(&mut input_file, chunk_info).await?;
+                open_chunk(
+                    old_cipher,
+                    &old_header,
+                    counter,
+                    is_final,
+                    &encrypted_chunk,
+                    input_path,
+                )?
+            }
+            None => Vec::new(),
+        };
+        let reencrypted = seal_chunk(new_cipher, &new_nonce, counter, is_final, &plaintext)?;
+
+        output_file
+            .write_all(&(reencrypted.len() as u32).to_le_bytes())
+            .await?;
+        output_file.write_all(&reencrypted).await?;
+    }
+
+    output_file.flush().await?;
+    Ok(())
+}
+
+/// Re-encrypts a legacy-format file in place in the current format
+///
+/// Every chunk is authenticated on the way. If the rewritten file cannot be
+/// moved into place the legacy file is kept, since it still reads.
+async fn upgrade_legacy_file(cipher: &Aes256Gcm, path: &Path) -> Result<()> {
+    let staging_path = sibling_path(path, "upgrade");
+    if let Err(e) = reencrypt_file(cipher, cipher, path, &staging_path).await {
+        remove_files(&[staging_path]).await;
+        return Err(e);
+    }
+
+    match rename(&staging_path, path).await {
+        Ok(()) => info!(
+            "Upgraded {:?} to encrypted file format {}",
+            path, FORMAT_VERSION
+        ),
+        Err(e) => {
+            warn!("Failed to upgrade legacy encrypted file {:?}: {}", path, e);
+            remove_files(&[staging_path]).await;
+        }
+    }
+    Ok(())
+}
+
+/// Moves each backup back over the file it was taken from
+async fn restore_backups(swapped: &[(PathBuf, PathBuf)]) {
+    for (original, backup) in swapped.iter().rev() {
+        if let Err(e) = rename(backup, original).await {
+            warn!("Failed to restore {:?} from {:?}: {}", original, backup, e);
+        }
+    }
+}
+
+async fn remove_files(paths: &[PathBuf]) {
+    for path in paths {
+        let _ = remove_file(path).await;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -794,7 +1361,7 @@ mod tests {
 
         // Build chunk index
         let mut file = File::open(&encrypted_path).await.unwrap();
-        let result = manager.build_chunk_index(&mut file).await;
+        let result = manager.build_chunk_index(&mut file, &encrypted_path).await;
         assert!(result.is_ok());
 
         let index = result.unwrap();
@@ -1267,10 +1834,10 @@ mod tests {
         // Should fail to decrypt
         assert!(result.is_err());
         match result {
-            Err(KiyyaError::Encryption { message }) => {
-                assert!(message.contains("Decryption failed"));
+            Err(KiyyaError::IntegrityCheckFailed { file_path }) => {
+                assert!(file_path.contains("encrypted.bin"));
             }
-            _ => panic!("Expected decryption error"),
+            _ => panic!("Expected integrity check error"),
         }
 
         let _ = manager2.disable_encryption();
@@ -1379,4 +1946,536 @@ mod tests {
 
         let _ = manager.disable_encryption();
     }
+
+    #[tokio::test]
+    async fn test_change_passphrase_reencrypts_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("input.txt");
+        let encrypted_path = temp_dir.path().join("content.bin");
+        let decrypted_path = temp_dir.path().join("decrypted.txt");
+
+        let original_content: Vec<u8> = (0..200 * 1024).map(|i| (i % 251) as u8).collect();
+        write(&input_path, &original_content).await.unwrap();
+
+        let mut manager = EncryptionManager::new().unwrap();
+        manager.enable_encryption("old_passphrase").unwrap();
+        manager
+            .encrypt_file(&input_path, &encrypted_path)
+            .await
+            .unwrap();
+
+        let changed = manager
+            .change_passphrase(
+                "old_passphrase",
+                "new_passphrase",
+                &[encrypted_path.clone()],
+            )
+            .await
+            .unwrap();
+        assert_eq!(changed, 1);
+        assert!(!sibling_path(&encrypted_path, "rekey").exists());
+        assert!(!sibling_path(&encrypted_path, "bak").exists());
+
+        // The file now opens with the new passphrase only
+        let mut new_manager = EncryptionManager::new().unwrap();
+        new_manager.enable_encryption("new_passphrase").unwrap();
+        new_manager
+            .decrypt_file(&encrypted_path, &decrypted_path)
+            .await
+            .unwrap();
+        assert_eq!(
+            tokio::fs::read(&decrypted_path).await.unwrap(),
+            original_content
+        );
+
+        let mut old_manager = EncryptionManager::new().unwrap();
+        old_manager.enable_encryption("old_passphrase").unwrap();
+        assert!(old_manager
+            .decrypt_file(&encrypted_path, &decrypted_path)
+            .await
+            .is_err());
+
+        let _ = manager.disable_encryption();
+    }
+
+    #[tokio::test]
+    async fn test_change_passphrase_rejects_wrong_old_passphrase() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("input.txt");
+        let encrypted_path = temp_dir.path().join("content.bin");
+
+        write(&input_path, b"content under the original passphrase")
+            .await
+            .unwrap();
+
+        let mut manager = EncryptionManager::new().unwrap();
+        manager.enable_encryption("right_passphrase").unwrap();
+        manager
+            .encrypt_file(&input_path, &encrypted_path)
+            .await
+            .unwrap();
+        let before = tokio::fs::read(&encrypted_path).await.unwrap();
+
+        let result = manager
+            .change_passphrase(
+                "wrong_passphrase",
+                "new_passphrase",
+                &[encrypted_path.clone()],
+            )
+            .await;
+        assert!(matches!(result, Err(KiyyaError::InvalidPassphrase)));
+
+        // Nothing was touched and the old key is still in use
+        assert_eq!(tokio::fs::read(&encrypted_path).await.unwrap(), before);
+        assert!(!sibling_path(&encrypted_path, "rekey").exists());
+        let decrypted = manager.decrypt_range(&encrypted_path, 0, 6).await.unwrap();
+        assert_eq!(decrypted, b"content");
+
+        let _ = manager.disable_encryption();
+    }
+
+    #[tokio::test]
+    async fn test_change_passphrase_rolls_back_on_failure() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("input.txt");
+        let first_path = temp_dir.path().join("first.bin");
+        let corrupt_path = temp_dir.path().join("corrupt.bin");
+
+        write(&input_path, b"first file content").await.unwrap();
+
+        let mut manager = EncryptionManager::new().unwrap();
+        manager.enable_encryption("old_passphrase").unwrap();
+        manager
+            .encrypt_file(&input_path, &first_path)
+            .await
+            .unwrap();
+        manager
+            .encrypt_file(&input_path, &corrupt_path)
+            .await
+            .unwrap();
+
+        // Flip a ciphertext byte so the second file fails authentication
+        let mut corrupt = tokio::fs::read(&corrupt_path).await.unwrap();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xFF;
+        write(&corrupt_path, &corrupt).await.unwrap();
+
+        let first_before = tokio::fs::read(&first_path).await.unwrap();
+        let result = manager
+            .change_passphrase(
+                "old_passphrase",
+                "new_passphrase",
+                &[first_path.clone(), corrupt_path.clone()],
+            )
+            .await;
+        assert!(result.is_err());
+
+        // The first file was staged but never swapped in
+        assert_eq!(tokio::fs::read(&first_path).await.unwrap(), first_before);
+        assert_eq!(tokio::fs::read(&corrupt_path).await.unwrap(), corrupt);
+        for path in [&first_path, &corrupt_path] {
+            assert!(!sibling_path(path, "rekey").exists());
+            assert!(!sibling_path(path, "bak").exists());
+        }
+
+        // The manager still uses the old key
+        let decrypted = manager.decrypt_range(&first_path, 0, 4).await.unwrap();
+        assert_eq!(decrypted, b"first");
+
+        let _ = manager.disable_encryption();
+    }
+
+    /// Manager keyed by `passphrase` without going through the keystore
+    fn manager_for_passphrase(passphrase: &str) -> EncryptionManager {
+        let key = EncryptionManager::derive_key_from_passphrase(passphrase).unwrap();
+        EncryptionManager {
+            cipher: Some(Aes256Gcm::new(GenericArray::from_slice(&key))),
+            key: Some(key),
+        }
+    }
+
+    /// Encrypts distinct content into `count` files under `dir`
+    async fn encrypted_files(
+        manager: &EncryptionManager,
+        dir: &Path,
+        count: usize,
+    ) -> Vec<(PathBuf, Vec<u8>)> {
+        let mut files = Vec::new();
+        for i in 0..count {
+            let input_path = dir.join(format!("input-{}.txt", i));
+            let encrypted_path = dir.join(format!("file-{}.bin", i));
+            let content = format!("content of file {}", i).into_bytes();
+            write(&input_path, &content).await.unwrap();
+            manager
+                .encrypt_file(&input_path, &encrypted_path)
+                .await
+                .unwrap();
+            files.push((encrypted_path, content));
+        }
+        files
+    }
+
+    /// Asserts every file is back in place, readable with `manager`'s key
+    async fn assert_files_restored(manager: &EncryptionManager, files: &[(PathBuf, Vec<u8>)]) {
+        for (path, content) in files {
+            let decrypted = manager
+                .decrypt_range(path, 0, content.len() as u64 - 1)
+                .await
+                .unwrap();
+            assert_eq!(&decrypted, content);
+            assert!(!sibling_path(path, "rekey").exists());
+            assert!(!sibling_path(path, "bak").exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_change_passphrase_rolls_back_failed_swap() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut manager = manager_for_passphrase("old_passphrase");
+        let files = encrypted_files(&manager, temp_dir.path(), 3).await;
+        let paths: Vec<PathBuf> = files.iter().map(|(path, _)| path.clone()).collect();
+
+        // The second original cannot be moved aside, after the first already was
+        let blocker = sibling_path(&paths[1], "bak");
+        tokio::fs::create_dir(&blocker).await.unwrap();
+        write(blocker.join("keep"), b"x").await.unwrap();
+
+        let result = manager
+            .change_passphrase("old_passphrase", "new_passphrase", &paths)
+            .await;
+        assert!(result.is_err());
+
+        tokio::fs::remove_dir_all(&blocker).await.unwrap();
+        assert_files_restored(&manager, &files).await;
+        assert_eq!(
+            manager.key,
+            Some(EncryptionManager::derive_key_from_passphrase("old_passphrase").unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_passphrase_swap_rolls_back_failed_keystore_write() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = manager_for_passphrase("old_passphrase");
+        let files = encrypted_files(&manager, temp_dir.path(), 2).await;
+        let paths: Vec<PathBuf> = files.iter().map(|(path, _)| path.clone()).collect();
+
+        let new_key = EncryptionManager::derive_key_from_passphrase("new_passphrase").unwrap();
+        let new_cipher = Aes256Gcm::new(GenericArray::from_slice(&new_key));
+        let mut stored = None;
+        let result = manager
+            .swap_in_reencrypted(&new_cipher, &new_key, &paths, |key| {
+                stored = Some(*key);
+                Err(KiyyaError::Encryption {
+                    message: "keystore unavailable".to_string(),
+                })
+            })
+            .await;
+        assert!(matches!(result, Err(KiyyaError::Encryption { .. })));
+
+        // Every file was swapped in before the key write, and every one is back
+        assert_eq!(stored, Some(new_key));
+        assert_files_restored(&manager, &files).await;
+    }
+
+    #[tokio::test]
+    async fn test_flipped_ciphertext_bit_fails_integrity_check() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("input.bin");
+        let encrypted_path = temp_dir.path().join("encrypted.bin");
+        let decrypted_path = temp_dir.path().join("decrypted.bin");
+
+        let original_content: Vec<u8> = (0..150_000).map(|i| (i % 256) as u8).collect();
+        write(&input_path, &original_content).await.unwrap();
+
+        let mut manager = EncryptionManager::new().unwrap();
+        manager
+            .enable_encryption("test_passphrase_integrity")
+            .unwrap();
+        manager
+            .encrypt_file(&input_path, &encrypted_path)
+            .await
+            .unwrap();
+        manager.check_integrity(&encrypted_path).await.unwrap();
+
+        // Flip one bit inside the first chunk's ciphertext
+        let mut tampered = tokio::fs::read(&encrypted_path).await.unwrap();
+        tampered[HEADER_SIZE + NONCE_SIZE + 4 + 100] ^= 0x01;
+        write(&encrypted_path, &tampered).await.unwrap();
+
+        assert!(matches!(
+            manager.decrypt_file(&encrypted_path, &decrypted_path).await,
+            Err(KiyyaError::IntegrityCheckFailed { .. })
+        ));
+        assert!(matches!(
+            manager.decrypt_range(&encrypted_path, 0, 1023).await,
+            Err(KiyyaError::IntegrityCheckFailed { .. })
+        ));
+        assert!(matches!(
+            manager.check_integrity(&encrypted_path).await,
+            Err(KiyyaError::IntegrityCheckFailed { .. })
+        ));
+
+        // Chunks that were not touched still decrypt
+        let second_chunk = manager
+            .decrypt_range(&encrypted_path, 70_000, 70_099)
+            .await
+            .unwrap();
+        assert_eq!(second_chunk, &original_content[70_000..70_100]);
+
+        let _ = manager.disable_encryption();
+    }
+
+    #[tokio::test]
+    async fn test_truncated_file_fails_integrity_check() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("input.bin");
+        let encrypted_path = temp_dir.path().join("encrypted.bin");
+
+        let original_content: Vec<u8> = (0..150_000).map(|i| (i % 256) as u8).collect();
+        write(&input_path, &original_content).await.unwrap();
+
+        let mut manager = EncryptionManager::new().unwrap();
+        manager
+            .enable_encryption("test_passphrase_truncated")
+            .unwrap();
+        manager
+            .encrypt_file(&input_path, &encrypted_path)
+            .await
+            .unwrap();
+
+        let encrypted = tokio::fs::read(&encrypted_path).await.unwrap();
+        write(&encrypted_path, &encrypted[..encrypted.len() - 10])
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            manager.check_integrity(&encrypted_path).await,
+            Err(KiyyaError::IntegrityCheckFailed { .. })
+        ));
+        assert!(matches!(
+            manager.decrypt_range(&encrypted_path, 0, 99).await,
+            Err(KiyyaError::IntegrityCheckFailed { .. })
+        ));
+
+        let _ = manager.disable_encryption();
+    }
+
+    #[tokio::test]
+    async fn test_file_truncated_on_chunk_boundary_fails_integrity_check() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("input.bin");
+        let encrypted_path = temp_dir.path().join("encrypted.bin");
+        let decrypted_path = temp_dir.path().join("decrypted.bin");
+
+        // Two full chunks and a partial third
+        let original_content: Vec<u8> = (0..150_000).map(|i| (i % 256) as u8).collect();
+        write(&input_path, &original_content).await.unwrap();
+
+        let mut manager = EncryptionManager::new().unwrap();
+        manager
+            .enable_encryption("test_passphrase_chunk_boundary")
+            .unwrap();
+        manager
+            .encrypt_file(&input_path, &encrypted_path)
+            .await
+            .unwrap();
+        let encrypted = tokio::fs::read(&encrypted_path).await.unwrap();
+
+        // Dropping the last chunk leaves two whole, well-framed chunks
+        let boundary = HEADER_SIZE + NONCE_SIZE + 2 * (4 + MAX_ENCRYPTED_CHUNK_SIZE as usize);
+        write(&encrypted_path, &encrypted[..boundary])
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            manager.check_integrity(&encrypted_path).await,
+            Err(KiyyaError::IntegrityCheckFailed { .. })
+        ));
+        assert!(matches!(
+            manager.decrypt_file(&encrypted_path, &decrypted_path).await,
+            Err(KiyyaError::IntegrityCheckFailed { .. })
+        ));
+        assert!(matches!(
+            manager.decrypt_range(&encrypted_path, 70_000, 70_099).await,
+            Err(KiyyaError::IntegrityCheckFailed { .. })
+        ));
+
+        // Nothing but the header and nonce left
+        write(&encrypted_path, &encrypted[..HEADER_SIZE + NONCE_SIZE])
+            .await
+            .unwrap();
+        assert!(matches!(
+            manager.check_integrity(&encrypted_path).await,
+            Err(KiyyaError::IntegrityCheckFailed { .. })
+        ));
+
+        let _ = manager.disable_encryption();
+    }
+
+    /// Encrypts `plaintext` the way files were written before the format header
+    fn seal_legacy_file(manager: &EncryptionManager, plaintext: &[u8]) -> Vec<u8> {
+        let cipher = manager.cipher.as_ref().unwrap();
+        let mut nonce = [0u8; NONCE_SIZE];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let mut file = nonce.to_vec();
+        for (counter, chunk) in plaintext.chunks(CHUNK_SIZE).enumerate() {
+            let chunk_nonce = chunk_nonce(&nonce, counter as u64);
+            let sealed = cipher
+                .encrypt(Nonce::from_slice(&chunk_nonce), chunk)
+                .unwrap();
+            file.extend_from_slice(&(sealed.len() as u32).to_le_bytes());
+            file.extend_from_slice(&sealed);
+        }
+        file
+    }
+
+    #[tokio::test]
+    async fn test_legacy_format_file_decrypts_and_upgrades() {
+        let temp_dir = TempDir::new().unwrap();
+        let legacy_path = temp_dir.path().join("legacy.bin");
+        let decrypted_path = temp_dir.path().join("decrypted.bin");
+        let manager = EncryptionManager::with_throwaway_key();
+
+        let original_content: Vec<u8> = (0..150_000).map(|i| (i % 256) as u8).collect();
+        write(&legacy_path, seal_legacy_file(&manager, &original_content))
+            .await
+            .unwrap();
+
+        manager
+            .decrypt_file(&legacy_path, &decrypted_path)
+            .await
+            .unwrap();
+        assert_eq!(
+            tokio::fs::read(&decrypted_path).await.unwrap(),
+            original_content
+        );
+        let range = manager
+            .decrypt_range(&legacy_path, 70_000, 70_099)
+            .await
+            .unwrap();
+        assert_eq!(range, &original_content[70_000..70_100]);
+        assert_eq!(
+            manager.plaintext_size(&legacy_path).await.unwrap(),
+            original_content.len() as u64
+        );
+
+        // The integrity check rewrites the file in the current format
+        manager.check_integrity(&legacy_path).await.unwrap();
+        let upgraded = tokio::fs::read(&legacy_path).await.unwrap();
+        assert_eq!(&upgraded[..FORMAT_MAGIC.len()], FORMAT_MAGIC);
+        assert_eq!(upgraded[FORMAT_MAGIC.len()], FORMAT_VERSION);
+        assert!(!sibling_path(&legacy_path, "upgrade").exists());
+
+        manager.check_integrity(&legacy_path).await.unwrap();
+        manager
+            .decrypt_file(&legacy_path, &decrypted_path)
+            .await
+            .unwrap();
+        assert_eq!(
+            tokio::fs::read(&decrypted_path).await.unwrap(),
+            original_content
+        );
+    }
+
+    #[tokio::test]
+    async fn test_legacy_format_edge_cases() {
+        let temp_dir = TempDir::new().unwrap();
+        let legacy_path = temp_dir.path().join("legacy.bin");
+        let decrypted_path = temp_dir.path().join("decrypted.bin");
+        let manager = EncryptionManager::with_throwaway_key();
+
+        // An empty input was written as the nonce alone
+        write(&legacy_path, seal_legacy_file(&manager, b""))
+            .await
+            .unwrap();
+        manager
+            .decrypt_file(&legacy_path, &decrypted_path)
+            .await
+            .unwrap();
+        assert!(tokio::fs::read(&decrypted_path).await.unwrap().is_empty());
+        manager.check_integrity(&legacy_path).await.unwrap();
+        assert_eq!(
+            manager.plaintext_size(&legacy_path).await.unwrap(),
+            0,
+            "The upgraded file holds one empty final chunk"
+        );
+
+        // A tampered legacy file is rejected and left as it was
+        let mut tampered = seal_legacy_file(&manager, b"legacy content");
+        tampered[NONCE_SIZE + 4 + 3] ^= 0x01;
+        write(&legacy_path, &tampered).await.unwrap();
+        assert!(matches!(
+            manager.check_integrity(&legacy_path).await,
+            Err(KiyyaError::IntegrityCheckFailed { .. })
+        ));
+        assert_eq!(tokio::fs::read(&legacy_path).await.unwrap(), tampered);
+        assert!(!sibling_path(&legacy_path, "upgrade").exists());
+
+        // A format version this build does not know is refused outright
+        let mut unknown = FORMAT_MAGIC.to_vec();
+        unknown.push(FORMAT_VERSION + 1);
+        unknown.extend_from_slice(&[0u8; NONCE_SIZE]);
+        write(&legacy_path, &unknown).await.unwrap();
+        assert!(matches!(
+            manager.decrypt_file(&legacy_path, &decrypted_path).await,
+            Err(KiyyaError::Encryption { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_open_range_decrypts_one_chunk_at_a_time() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("input.bin");
+        let encrypted_path = temp_dir.path().join("encrypted.bin");
+
+        let original_content: Vec<u8> = (0..1_000_000).map(|i| (i % 253) as u8).collect();
+        write(&input_path, &original_content).await.unwrap();
+
+        let mut manager = EncryptionManager::new().unwrap();
+        manager.enable_encryption("test_passphrase_pieces").unwrap();
+        manager
+            .encrypt_file(&input_path, &encrypted_path)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            manager.plaintext_size(&encrypted_path).await.unwrap(),
+            original_content.len() as u64
+        );
+
+        let (start, end) = (100_000u64, 900_000u64);
+        let mut decryptor = manager
+            .open_range(&encrypted_path, start, end)
+            .await
+            .unwrap();
+
+        let mut pieces = 0;
+        let mut result = Vec::new();
+        while let Some(piece) = decryptor.next_piece().await.unwrap() {
+            assert!(piece.len() <= CHUNK_SIZE);
+            result.extend_from_slice(&piece);
+            pieces += 1;
+        }
+
+        assert_eq!(pieces, 13);
+        assert_eq!(result, &original_content[start as usize..=end as usize]);
+
+        let _ = manager.disable_encryption();
+    }
+
+    #[tokio::test]
+    async fn test_benchmark_reports_throughput_and_cleans_up() {
+        let temp_dir = TempDir::new().unwrap();
+        let benchmark = benchmark_in(2, temp_dir.path()).await.unwrap();
+
+        assert_eq!(benchmark.size_mb, 2);
+        assert!(benchmark.encrypt_mb_per_sec > 0.0);
+        assert!(benchmark.decrypt_mb_per_sec > 0.0);
+        assert!(last_benchmark().is_some());
+
+        let leftovers = std::fs::read_dir(temp_dir.path()).unwrap().count();
+        assert_eq!(leftovers, 0);
+    }
 }
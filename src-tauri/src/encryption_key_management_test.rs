@@ -421,6 +421,8 @@ mod encryption_key_management_tests {
             file_size: 1024,
             encrypted: true,
             added_at: chrono::Utc::now().timestamp(),
+            probe: None,
+            title: None,
         };
 
         _db.save_offline_metadata(metadata).await.unwrap();
@@ -42,10 +42,14 @@ pub enum KiyyaError {
     #[error("Gateway error: {message}")]
     Gateway { message: String },
 
-    #[error("All gateways failed after {attempts} attempts. Last error: {last_error}")]
-    AllGatewaysFailed { 
+    #[error(
+        "All gateways failed after {attempts} attempts ({})",
+        summarize_gateway_failures(.failures)
+    )]
+    AllGatewaysFailed {
         attempts: u32,
-        last_error: String,
+        /// Final error of each gateway tried, in priority order
+        failures: Vec<GatewayFailure>,
     },
 
     #[error("API rate limit exceeded: retry after {retry_after_seconds} seconds")]
@@ -64,6 +68,9 @@ pub enum KiyyaError {
     #[error("Content not found: {claim_id}")]
     ContentNotFound { claim_id: String },
 
+    #[error("Content {claim_id} is blocked in this region (HTTP {status})")]
+    RegionBlocked { claim_id: String, status: u16 },
+
     #[error("Invalid content format: expected {expected}, got {actual}")]
     InvalidContentFormat { expected: String, actual: String },
 
@@ -102,6 +109,12 @@ pub enum KiyyaError {
     #[error("Key management error: {message}")]
     KeyManagement { message: String },
 
+    #[error("Invalid passphrase")]
+    InvalidPassphrase,
+
+    #[error("Integrity check failed: {file_path}")]
+    IntegrityCheckFailed { file_path: String },
+
     #[error("Security violation: {message}")]
     SecurityViolation { message: String },
 
@@ -259,6 +272,16 @@ impl KiyyaError {
         }
     }
 
+    /// Creates a not-found error for a single-item lookup
+    ///
+    /// Single-item fetches report a miss with this error, while list and
+    /// search operations return an empty result instead.
+    pub fn content_not_found(claim_id: impl Into<String>) -> Self {
+        Self::ContentNotFound {
+            claim_id: claim_id.into(),
+        }
+    }
+
     /// Creates a migration error with context
     pub fn migration_error(message: impl Into<String>) -> Self {
         Self::Migration {
@@ -288,6 +311,11 @@ impl KiyyaError {
         }
     }
 
+    /// Checks if this error means the requested item does not exist
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Self::ContentNotFound { .. })
+    }
+
     /// Checks if this error is recoverable (can be retried)
     pub fn is_recoverable(&self) -> bool {
         match self {
@@ -323,6 +351,9 @@ impl KiyyaError {
             // Content not found might be expected
             Self::ContentNotFound { .. } => true,
 
+            // Region blocks depend on where the user is, not on a fault
+            Self::RegionBlocked { .. } => true,
+
             // Stream not available is expected when content is removed
             Self::StreamNotAvailable { .. } => true,
 
@@ -355,6 +386,8 @@ impl KiyyaError {
             Self::Encryption { .. }
             | Self::DecryptionFailed { .. }
             | Self::KeyManagement { .. }
+            | Self::InvalidPassphrase
+            | Self::IntegrityCheckFailed { .. }
             | Self::SecurityViolation { .. } => "security",
 
             Self::Server { .. }
@@ -365,6 +398,7 @@ impl KiyyaError {
 
             Self::ContentParsing { .. }
             | Self::ContentNotFound { .. }
+            | Self::RegionBlocked { .. }
             | Self::InvalidContentFormat { .. }
             | Self::MissingRequiredField { .. }
             | Self::UnsupportedContentType { .. } => "content",
@@ -402,6 +436,12 @@ impl KiyyaError {
             Self::Network(_) => {
                 "Network connection failed. Please check your internet connection.".to_string()
             }
+            Self::AllGatewaysFailed { failures, .. } if !failures.is_empty() => {
+                format!(
+                    "All servers are currently unavailable ({}). Please try again later.",
+                    summarize_gateway_failures(failures)
+                )
+            }
             Self::AllGatewaysFailed { .. } => {
                 "All servers are currently unavailable. Please try again later.".to_string()
             }
@@ -416,12 +456,20 @@ impl KiyyaError {
                 )
             }
             Self::ContentNotFound { .. } => "The requested content could not be found.".to_string(),
+            Self::RegionBlocked { .. } => {
+                "This content is not available in your region.".to_string()
+            }
             Self::DownloadInterrupted { .. } => {
                 "Download was interrupted. You can resume it later.".to_string()
             }
             Self::DecryptionFailed { .. } => {
                 "Failed to decrypt content. Your encryption key may be invalid.".to_string()
             }
+            Self::InvalidPassphrase => "The current passphrase is incorrect.".to_string(),
+            Self::IntegrityCheckFailed { .. } => {
+                "This offline file has been modified or damaged. Please download it again."
+                    .to_string()
+            }
             Self::ForcedUpdateRequired { .. } => {
                 "A required update is available. Please update the application to continue."
                     .to_string()
@@ -442,6 +490,109 @@ impl KiyyaError {
     }
 }
 
+/// How one gateway failed during `GatewayClient::fetch_with_failover`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GatewayFailureKind {
+    Timeout,
+    Connection,
+    HttpStatus,
+    RateLimited,
+    /// The gateway answered with a JSON-RPC or API error
+    ApiError,
+    InvalidResponse,
+    Other,
+}
+
+/// The last error a single gateway returned before failover moved on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GatewayFailure {
+    pub gateway: String,
+    pub kind: GatewayFailureKind,
+    /// HTTP status for `HttpStatus` failures
+    pub status: Option<u16>,
+    pub message: String,
+}
+
+impl GatewayFailure {
+    /// Classifies `error` as returned by `gateway`
+    pub fn from_error(gateway: &str, error: &KiyyaError) -> Self {
+        let (kind, status) = match error {
+            KiyyaError::ApiTimeout { .. } => (GatewayFailureKind::Timeout, None),
+            KiyyaError::Network(e) if e.is_timeout() => (GatewayFailureKind::Timeout, None),
+            KiyyaError::Network(e) if e.is_connect() => (GatewayFailureKind::Connection, None),
+            KiyyaError::RateLimitExceeded { .. } => (GatewayFailureKind::RateLimited, Some(429)),
+            KiyyaError::Gateway { message } => match parse_http_status(message) {
+                Some(status) => (GatewayFailureKind::HttpStatus, Some(status)),
+                None => (GatewayFailureKind::ApiError, None),
+            },
+            KiyyaError::Json(_) | KiyyaError::InvalidApiResponse { .. } => {
+                (GatewayFailureKind::InvalidResponse, None)
+            }
+            _ => (GatewayFailureKind::Other, None),
+        };
+
+        Self {
+            gateway: gateway.to_string(),
+            kind,
+            status,
+            message: error.to_string(),
+        }
+    }
+
+    /// Short phrase for summaries, e.g. "timed out" or "returned HTTP 503"
+    fn outcome(&self) -> String {
+        match (self.kind, self.status) {
+            (GatewayFailureKind::Timeout, _) => "timed out".to_string(),
+            (GatewayFailureKind::Connection, _) => "could not be reached".to_string(),
+            (GatewayFailureKind::HttpStatus, Some(status)) => format!("returned HTTP {}", status),
+            (GatewayFailureKind::RateLimited, _) => "rate limited us".to_string(),
+            (GatewayFailureKind::ApiError, _) => "returned an API error".to_string(),
+            (GatewayFailureKind::InvalidResponse, _) => "returned an invalid response".to_string(),
+            _ => "failed".to_string(),
+        }
+    }
+}
+
+/// Parses the status out of the "HTTP 503 Service Unavailable: ..." gateway errors
+fn parse_http_status(message: &str) -> Option<u16> {
+    message
+        .strip_prefix("HTTP ")?
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Summarizes per-gateway failures, e.g. "tried 3 gateways: 2 timed out, 1 returned HTTP 503"
+pub fn summarize_gateway_failures(failures: &[GatewayFailure]) -> String {
+    if failures.is_empty() {
+        return "no gateway was tried".to_string();
+    }
+
+    let mut outcomes: Vec<(String, usize)> = Vec::new();
+    for failure in failures {
+        let outcome = failure.outcome();
+        match outcomes
+            .iter_mut()
+            .find(|(existing, _)| *existing == outcome)
+        {
+            Some((_, count)) => *count += 1,
+            None => outcomes.push((outcome, 1)),
+        }
+    }
+
+    let outcomes: Vec<String> = outcomes
+        .into_iter()
+        .map(|(outcome, count)| format!("{} {}", count, outcome))
+        .collect();
+    format!(
+        "tried {} gateway{}: {}",
+        failures.len(),
+        if failures.len() == 1 { "" } else { "s" },
+        outcomes.join(", ")
+    )
+}
+
 /// Result type alias for convenience
 pub type Result<T> = std::result::Result<T, KiyyaError>;
 
@@ -530,6 +681,40 @@ mod tests {
         assert!(message.contains("50 MB"));
     }
 
+    #[test]
+    fn test_all_gateways_failed_summarizes_each_gateway() {
+        let failure = |kind, status| GatewayFailure {
+            gateway: "https://gateway.example.com".to_string(),
+            kind,
+            status,
+            message: String::new(),
+        };
+        let error = KiyyaError::AllGatewaysFailed {
+            attempts: 3,
+            failures: vec![
+                failure(GatewayFailureKind::Timeout, None),
+                failure(GatewayFailureKind::HttpStatus, Some(503)),
+                failure(GatewayFailureKind::Timeout, None),
+            ],
+        };
+
+        assert_eq!(
+            error.to_string(),
+            concat!(
+                "All gateways failed after 3 attempts ",
+                "(tried 3 gateways: 2 timed out, 1 returned HTTP 503)"
+            )
+        );
+        assert!(error
+            .user_message()
+            .contains("tried 3 gateways: 2 timed out, 1 returned HTTP 503"));
+        assert_eq!(
+            parse_http_status("HTTP 503 Service Unavailable: Service Unavailable"),
+            Some(503)
+        );
+        assert_eq!(parse_http_status("Unknown Odysee API error"), None);
+    }
+
     #[test]
     fn test_error_context() {
         let result: std::result::Result<(), std::io::Error> = Err(std::io::Error::new(
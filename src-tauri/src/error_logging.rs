@@ -374,6 +374,7 @@ fn get_error_code(error: &KiyyaError) -> Option<String> {
         KiyyaError::ContentParsing { .. } => Some("E_CONTENT_002".to_string()),
         KiyyaError::InvalidContentFormat { .. } => Some("E_CONTENT_003".to_string()),
         KiyyaError::MissingRequiredField { .. } => Some("E_CONTENT_004".to_string()),
+        KiyyaError::RegionBlocked { .. } => Some("E_CONTENT_005".to_string()),
 
         KiyyaError::Download { .. } => Some("E_DOWNLOAD_001".to_string()),
         KiyyaError::InsufficientDiskSpace { .. } => Some("E_DOWNLOAD_002".to_string()),
@@ -383,6 +384,8 @@ fn get_error_code(error: &KiyyaError) -> Option<String> {
         KiyyaError::DecryptionFailed { .. } => Some("E_SECURITY_002".to_string()),
         KiyyaError::KeyManagement { .. } => Some("E_SECURITY_003".to_string()),
         KiyyaError::SecurityViolation { .. } => Some("E_SECURITY_004".to_string()),
+        KiyyaError::InvalidPassphrase => Some("E_SECURITY_005".to_string()),
+        KiyyaError::IntegrityCheckFailed { .. } => Some("E_SECURITY_006".to_string()),
 
         KiyyaError::Database(_) => Some("E_DATABASE_001".to_string()),
         KiyyaError::Migration { .. } => Some("E_DATABASE_002".to_string()),
@@ -529,7 +532,10 @@ mod tests {
     #[tokio::test]
     async fn test_error_codes() {
         assert_eq!(
-            get_error_code(&KiyyaError::AllGatewaysFailed { attempts: 3 }),
+            get_error_code(&KiyyaError::AllGatewaysFailed {
+                attempts: 3,
+                failures: Vec::new(),
+            }),
             Some("E_GATEWAY_001".to_string())
         );
         assert_eq!(
@@ -0,0 +1,194 @@
+//! # External URL Opener
+//!
+//! Hands a validated URL to the desktop environment so it opens in the
+//! user's default browser.
+//!
+//! ## Platforms
+//!
+//! - **macOS**: `open <url>`
+//! - **Linux/BSD**: `xdg-open <url>` (from xdg-utils)
+//! - **Windows**: `rundll32 url.dll,FileProtocolHandler <url>`
+//!
+//! The URL is always passed as a single process argument and no shell is
+//! involved, so characters such as `&` or `|` cannot start another command.
+
+use crate::error::{KiyyaError, Result};
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::Command;
+use tracing::debug;
+
+/// Mechanism used to open a URL on a given platform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExternalOpener {
+    MacOpen,
+    XdgOpen,
+    WindowsProtocolHandler,
+}
+
+impl ExternalOpener {
+    /// Selects the opener for the platform this binary was built for
+    pub fn for_current_platform() -> Option<Self> {
+        Self::for_os(std::env::consts::OS)
+    }
+
+    /// Selects the opener for an OS name as reported by `std::env::consts::OS`
+    pub fn for_os(os: &str) -> Option<Self> {
+        match os {
+            "macos" => Some(Self::MacOpen),
+            "linux" | "freebsd" | "openbsd" | "netbsd" | "dragonfly" => Some(Self::XdgOpen),
+            "windows" => Some(Self::WindowsProtocolHandler),
+            _ => None,
+        }
+    }
+
+    /// Program that is launched to open the URL
+    pub fn program(self) -> &'static str {
+        match self {
+            Self::MacOpen => "open",
+            Self::XdgOpen => "xdg-open",
+            Self::WindowsProtocolHandler => "rundll32",
+        }
+    }
+
+    /// Arguments passed to `program()`; the URL is always exactly one argument
+    pub fn args(self, url: &str) -> Vec<String> {
+        match self {
+            Self::MacOpen | Self::XdgOpen => vec![url.to_string()],
+            Self::WindowsProtocolHandler => {
+                vec!["url.dll,FileProtocolHandler".to_string(), url.to_string()]
+            }
+        }
+    }
+
+    /// Checks whether the opener program can be found on `path_var`
+    ///
+    /// `rundll32` ships with every Windows install, so it is always available.
+    pub fn is_available(self, path_var: Option<OsString>) -> bool {
+        if self == Self::WindowsProtocolHandler {
+            return true;
+        }
+
+        path_var
+            .map(|paths| {
+                std::env::split_paths(&paths).any(|dir| is_file(&dir.join(self.program())))
+            })
+            .unwrap_or(false)
+    }
+}
+
+fn is_file(path: &Path) -> bool {
+    path.metadata().map(|m| m.is_file()).unwrap_or(false)
+}
+
+/// Opens a URL with the platform's default handler
+///
+/// The URL must already have passed `validate_external_url`.
+pub fn open_url(url: &str) -> Result<()> {
+    let opener =
+        ExternalOpener::for_current_platform().ok_or_else(|| KiyyaError::PlatformCompatibility {
+            message: format!(
+                "Opening external links is not supported on {}",
+                std::env::consts::OS
+            ),
+        })?;
+
+    if !opener.is_available(std::env::var_os("PATH")) {
+        return Err(KiyyaError::PlatformCompatibility {
+            message: format!(
+                "No URL opener available: '{}' was not found. Install it to open external links",
+                opener.program()
+            ),
+        });
+    }
+
+    debug!("Opening external URL with {}", opener.program());
+    Command::new(opener.program())
+        .args(opener.args(url))
+        .spawn()
+        .map_err(|e| KiyyaError::PlatformCompatibility {
+            message: format!("Failed to launch {}: {}", opener.program(), e),
+        })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_opener_selected_per_os() {
+        assert_eq!(ExternalOpener::for_os("macos"), Some(ExternalOpener::MacOpen));
+        assert_eq!(ExternalOpener::for_os("linux"), Some(ExternalOpener::XdgOpen));
+        assert_eq!(ExternalOpener::for_os("freebsd"), Some(ExternalOpener::XdgOpen));
+        assert_eq!(
+            ExternalOpener::for_os("windows"),
+            Some(ExternalOpener::WindowsProtocolHandler)
+        );
+        assert_eq!(ExternalOpener::for_os("android"), None);
+    }
+
+    #[test]
+    fn test_url_is_a_single_argument() {
+        let url = "https://github.com/repo?a=1&b=2";
+
+        assert_eq!(ExternalOpener::MacOpen.args(url), vec![url]);
+        assert_eq!(ExternalOpener::XdgOpen.args(url), vec![url]);
+        assert_eq!(
+            ExternalOpener::WindowsProtocolHandler.args(url),
+            vec!["url.dll,FileProtocolHandler", url]
+        );
+        for opener in [
+            ExternalOpener::MacOpen,
+            ExternalOpener::XdgOpen,
+            ExternalOpener::WindowsProtocolHandler,
+        ] {
+            assert_ne!(opener.program(), "cmd", "No opener may go through a shell");
+            assert_ne!(opener.program(), "sh", "No opener may go through a shell");
+        }
+    }
+
+    #[test]
+    fn test_availability_checks_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let path_var = std::env::join_paths([temp_dir.path()]).unwrap();
+
+        assert!(!ExternalOpener::XdgOpen.is_available(Some(path_var.clone())));
+        assert!(!ExternalOpener::XdgOpen.is_available(None));
+
+        std::fs::write(temp_dir.path().join("xdg-open"), b"#!/bin/sh\n").unwrap();
+        assert!(ExternalOpener::XdgOpen.is_available(Some(path_var.clone())));
+        assert!(!ExternalOpener::MacOpen.is_available(Some(path_var)));
+
+        assert!(ExternalOpener::WindowsProtocolHandler.is_available(None));
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn test_current_platform_uses_open() {
+        assert_eq!(
+            ExternalOpener::for_current_platform(),
+            Some(ExternalOpener::MacOpen)
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_current_platform_uses_xdg_open() {
+        assert_eq!(
+            ExternalOpener::for_current_platform(),
+            Some(ExternalOpener::XdgOpen)
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_current_platform_uses_protocol_handler() {
+        assert_eq!(
+            ExternalOpener::for_current_platform(),
+            Some(ExternalOpener::WindowsProtocolHandler)
+        );
+    }
+}
@@ -1,89 +1,583 @@
-use crate::error::{KiyyaError, Result};
-use crate::models::{GatewayHealth, OdyseeRequest, OdyseeResponse};
+use crate::error::{GatewayFailure, GatewayFailureKind, KiyyaError, Result};
+use crate::models::{GatewayHealth, InflightRequestInfo, OdyseeRequest, OdyseeResponse};
 use crate::path_security;
 use crate::security_logging::{log_security_event, SecurityEvent};
+use once_cell::sync::Lazy;
 use rand::Rng;
 use reqwest::Client;
+use std::collections::{HashMap, VecDeque};
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, Mutex};
 use tokio::time::sleep;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
+
+/// Built-in gateway priority order: primary → secondary → fallback
+///
+/// Used when the `gateways` setting is unset or has no usable entry.
+pub const DEFAULT_GATEWAYS: [&str; 3] = [
+    "https://api.na-backend.odysee.com/api/v1/proxy", // Primary (index 0)
+    "https://api.lbry.tv/api/v1/proxy",               // Secondary (index 1)
+    "https://api.odysee.com/api/v1/proxy",            // Fallback (index 2)
+];
+
+/// Maximum number of gateways taken from the `gateways` setting
+pub const MAX_CUSTOM_GATEWAYS: usize = 10;
+
+/// Default cap on outbound gateway requests per second
+pub const DEFAULT_GATEWAY_MAX_REQUESTS_PER_SECOND: u32 = 5;
+
+/// Longest a request may queue for a rate-limit slot before it is rejected
+const MAX_RATE_LIMIT_QUEUE_DELAY: Duration = Duration::from_secs(5);
+
+/// Number of recent requests a gateway's success/failure counts cover
+pub const GATEWAY_HEALTH_WINDOW: usize = 20;
+
+/// Consecutive failures after which a gateway is skipped until its cooldown ends
+pub const GATEWAY_FAILURE_THRESHOLD: u32 = 3;
+
+/// How long a gateway that hit `GATEWAY_FAILURE_THRESHOLD` is skipped
+pub const GATEWAY_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Recent outcomes of one gateway, used to order and skip gateways
+#[derive(Debug, Clone, Default)]
+struct GatewayTracker {
+    /// Most recent last; `true` is a success
+    outcomes: VecDeque<bool>,
+    consecutive_failures: u32,
+    cooldown_until: Option<Instant>,
+}
+
+impl GatewayTracker {
+    fn record(&mut self, success: bool) {
+        if self.outcomes.len() == GATEWAY_HEALTH_WINDOW {
+            self.outcomes.pop_front();
+        }
+        self.outcomes.push_back(success);
+
+        if success {
+            self.consecutive_failures = 0;
+            self.cooldown_until = None;
+        } else {
+            self.consecutive_failures += 1;
+        }
+    }
+
+    fn successes(&self) -> u32 {
+        self.outcomes.iter().filter(|success| **success).count() as u32
+    }
+
+    fn failures(&self) -> u32 {
+        self.outcomes.len() as u32 - self.successes()
+    }
+
+    fn cooling_down(&self, now: Instant) -> bool {
+        self.cooldown_until.is_some_and(|until| now < until)
+    }
+}
+
+/// Whether a failure says something about the gateway rather than the request
+///
+/// API errors and 4xx responses come from a gateway that is up, so they
+/// neither count against it nor reset its failure streak.
+fn counts_against_gateway(failure: &GatewayFailure) -> bool {
+    match failure.kind {
+        GatewayFailureKind::HttpStatus => !matches!(failure.status, Some(status) if status < 500),
+        GatewayFailureKind::ApiError | GatewayFailureKind::Other => false,
+        GatewayFailureKind::Timeout
+        | GatewayFailureKind::Connection
+        | GatewayFailureKind::RateLimited
+        | GatewayFailureKind::InvalidResponse => true,
+    }
+}
+
+/// Client-side token bucket pacing outbound gateway requests
+///
+/// The bucket holds one second's worth of requests as burst. A request that
+/// finds it empty waits for the next token, or is rejected with
+/// `RateLimitExceeded` when the queue ahead of it is longer than
+/// `MAX_RATE_LIMIT_QUEUE_DELAY`.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    max_per_second: u32,
+    tokens: f64,
+    last_refill: Instant,
+    max_queue_delay: Duration,
+}
+
+impl RateLimiter {
+    pub fn new(max_per_second: u32) -> Self {
+        let max_per_second = max_per_second.max(1);
+        Self {
+            max_per_second,
+            tokens: max_per_second as f64,
+            last_refill: Instant::now(),
+            max_queue_delay: MAX_RATE_LIMIT_QUEUE_DELAY,
+        }
+    }
+
+    pub fn max_per_second(&self) -> u32 {
+        self.max_per_second
+    }
+
+    pub fn set_max_per_second(&mut self, max_per_second: u32) {
+        self.refill();
+        self.max_per_second = max_per_second.max(1);
+        self.tokens = self.tokens.min(self.max_per_second as f64);
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        let capacity = self.max_per_second as f64;
+        self.tokens = (self.tokens + elapsed * capacity).min(capacity);
+        self.last_refill = now;
+    }
+
+    /// Takes the next slot and returns how long the caller must wait before sending
+    pub fn reserve(&mut self) -> Result<Duration> {
+        self.refill();
+
+        let wait = if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / self.max_per_second as f64)
+        };
+        if wait > self.max_queue_delay {
+            return Err(KiyyaError::RateLimitExceeded {
+                retry_after_seconds: wait.as_secs().max(1),
+            });
+        }
 
+        self.tokens -= 1.0;
+        Ok(wait)
+    }
+
+    /// Waits until a request may be sent
+    pub async fn acquire(&mut self) -> Result<()> {
+        let wait = self.reserve()?;
+        if !wait.is_zero() {
+            debug!("Gateway rate limit: delaying request by {:?}", wait);
+            sleep(wait).await;
+        }
+        Ok(())
+    }
+}
+
+/// Normalizes a configured gateway URL, or returns `None` if it isn't an HTTPS
+/// URL with a host
+fn sanitize_gateway_url(gateway: &str) -> Option<String> {
+    let trimmed = gateway.trim();
+    if !trimmed.starts_with("https://") {
+        return None;
+    }
+
+    let sanitized = trimmed.trim_end_matches('/');
+    match url::Url::parse(sanitized) {
+        Ok(url) if url.scheme() == "https" && url.host_str().is_some() => {
+            Some(sanitized.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Resolves the gateway list from the `gateways` setting, a JSON array of URLs
+///
+/// Entries that aren't HTTPS URLs are dropped along with duplicates, and the
+/// list is capped at `MAX_CUSTOM_GATEWAYS`. Falls back to `DEFAULT_GATEWAYS`
+/// when the setting is unset, malformed or has no usable entry.
+pub fn resolve_gateways(setting: Option<&str>) -> Vec<String> {
+    let configured: Vec<String> = setting
+        .and_then(|value| match serde_json::from_str(value) {
+            Ok(urls) => Some(urls),
+            Err(e) => {
+                warn!("Ignoring malformed gateways setting: {}", e);
+                None
+            }
+        })
+        .unwrap_or_default();
+
+    let mut gateways: Vec<String> = Vec::new();
+    for url in &configured {
+        match sanitize_gateway_url(url) {
+            Some(gateway) if !gateways.contains(&gateway) => gateways.push(gateway),
+            Some(_) => {}
+            None => warn!("Ignoring gateway '{}': must be an HTTPS URL", url),
+        }
+    }
+    gateways.truncate(MAX_CUSTOM_GATEWAYS);
+
+    if gateways.is_empty() {
+        if !configured.is_empty() {
+            warn!("No usable gateway in settings, using built-in gateways");
+        }
+        return DEFAULT_GATEWAYS.iter().map(|url| url.to_string()).collect();
+    }
+    gateways
+}
+
+/// Response handed to coalesced callers; errors are shared as their message
+type SharedResponse = std::result::Result<OdyseeResponse, String>;
+type InflightMap = HashMap<String, InflightRequest>;
+
+/// A request on the wire and the channel its result is published on
+struct InflightRequest {
+    /// Tells a re-registered key apart from the request that was cleared
+    id: u64,
+    method: String,
+    started_at: Instant,
+    sender: broadcast::Sender<SharedResponse>,
+}
+
+/// Requests currently on the wire, keyed by method and params
+///
+/// Later identical requests subscribe to the first one's result instead of
+/// queueing for the gateway lock and sending a duplicate.
+static INFLIGHT_REQUESTS: Lazy<std::sync::Mutex<InflightMap>> =
+    Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
+
+static NEXT_INFLIGHT_ID: AtomicU64 = AtomicU64::new(0);
+
+fn inflight_requests() -> std::sync::MutexGuard<'static, InflightMap> {
+    INFLIGHT_REQUESTS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn request_key(request: &OdyseeRequest) -> String {
+    format!("{}:{}", request.method, request.params)
+}
+
+/// Removes the entry registered as `id`, leaving a newer request under the
+/// same key alone
+fn remove_inflight(key: &str, id: u64) -> Option<broadcast::Sender<SharedResponse>> {
+    let mut inflight = inflight_requests();
+    match inflight.get(key) {
+        Some(request) if request.id == id => inflight.remove(key).map(|request| request.sender),
+        _ => None,
+    }
+}
+
+/// Lists the requests currently in flight, oldest first
+pub fn get_inflight_requests() -> Vec<InflightRequestInfo> {
+    let inflight = inflight_requests();
+    let mut requests: Vec<(&String, &InflightRequest)> = inflight.iter().collect();
+    requests.sort_by_key(|(_, request)| request.started_at);
+    requests
+        .into_iter()
+        .map(|(key, request)| InflightRequestInfo {
+            key: key.clone(),
+            method: request.method.clone(),
+            age_ms: request.started_at.elapsed().as_millis() as u64,
+            waiters: request.sender.receiver_count(),
+        })
+        .collect()
+}
+
+/// Drops every in-flight entry, returning how many were removed
+///
+/// Entries are removed when their request finishes or is cancelled, so this
+/// is only a recovery tool for a request that never completes. Callers
+/// waiting on a cleared entry stop waiting and send the request themselves.
+pub fn clear_inflight_requests() -> usize {
+    let cleared: Vec<InflightRequest> = inflight_requests()
+        .drain()
+        .map(|(_, request)| request)
+        .collect();
+    if !cleared.is_empty() {
+        warn!("Cleared {} in-flight gateway requests", cleared.len());
+    }
+    cleared.len()
+}
+
+/// Removes an in-flight entry when the request finishes or is cancelled
+struct InflightEntry {
+    key: String,
+    id: u64,
+}
+
+impl Drop for InflightEntry {
+    fn drop(&mut self) {
+        remove_inflight(&self.key, self.id);
+    }
+}
+
+/// Sends `request` through `gateway`, sharing the response with identical
+/// requests issued while it is in flight
+pub async fn fetch_coalesced(
+    gateway: &Mutex<GatewayClient>,
+    request: OdyseeRequest,
+) -> Result<OdyseeResponse> {
+    let key = request_key(&request);
+
+    loop {
+        let id = NEXT_INFLIGHT_ID.fetch_add(1, Ordering::Relaxed);
+        let existing = {
+            let mut inflight = inflight_requests();
+            match inflight.get(&key) {
+                Some(inflight_request) => Some(inflight_request.sender.subscribe()),
+                None => {
+                    let (sender, _) = broadcast::channel(1);
+                    inflight.insert(
+                        key.clone(),
+                        InflightRequest {
+                            id,
+                            method: request.method.clone(),
+                            started_at: Instant::now(),
+                            sender,
+                        },
+                    );
+                    None
+                }
+            }
+        };
+
+        let mut receiver = match existing {
+            Some(receiver) => receiver,
+            None => return send_inflight(gateway, InflightEntry { key, id }, request).await,
+        };
+
+        debug!("Coalescing duplicate gateway request {}", request.method);
+        match receiver.recv().await {
+            Ok(shared) => return shared.map_err(|message| KiyyaError::Gateway { message }),
+            // The original request was cancelled or cleared before it finished;
+            // send our own
+            Err(_) => continue,
+        }
+    }
+}
+
+/// Sends a request registered as `entry` and publishes its result to any
+/// callers that coalesced onto it
+async fn send_inflight(
+    gateway: &Mutex<GatewayClient>,
+    entry: InflightEntry,
+    request: OdyseeRequest,
+) -> Result<OdyseeResponse> {
+    // The shared client is only locked long enough to clone it, so requests
+    // for different keys are sent concurrently
+    let client = gateway.lock().await.clone();
+    let result = client.fetch_with_failover(request).await;
+
+    // Unregister before publishing so nobody subscribes after the send
+    let sender = remove_inflight(&entry.key, entry.id);
+    if let Some(sender) = sender {
+        let shared = match &result {
+            Ok(response) => Ok(response.clone()),
+            Err(e) => Err(e.to_string()),
+        };
+        let _ = sender.send(shared);
+    }
+
+    result
+}
+
+/// Time source for gateway cooldowns, shared by every clone of a client
+///
+/// Reads `Instant::now()` plus an offset that only tests move forward, so
+/// cooldowns can be tested without sleeping through them.
+#[derive(Debug, Clone, Default)]
+struct GatewayClock {
+    offset_ms: Arc<AtomicU64>,
+}
+
+impl GatewayClock {
+    fn now(&self) -> Instant {
+        Instant::now() + Duration::from_millis(self.offset_ms.load(Ordering::Relaxed))
+    }
+
+    #[cfg(test)]
+    fn advance(&self, by: Duration) {
+        self.offset_ms
+            .fetch_add(by.as_millis() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Health tracking shared by every clone of a `GatewayClient`
+#[derive(Debug)]
+struct GatewayHealthState {
+    health_stats: Vec<GatewayHealth>,
+    /// Parallel to `health_stats`; decides the order gateways are tried in
+    trackers: Vec<GatewayTracker>,
+}
+
+impl GatewayHealthState {
+    fn new(gateways: &[String]) -> Self {
+        Self {
+            health_stats: GatewayClient::initial_health_stats(gateways),
+            trackers: vec![GatewayTracker::default(); gateways.len()],
+        }
+    }
+}
+
+/// Sends requests to the configured gateways with retries and failover
+///
+/// Clones share health tracking and the rate limiter, so a request can be
+/// sent from a clone without holding a lock on the shared client.
+#[derive(Clone)]
 pub struct GatewayClient {
-    /// IMMUTABLE gateway priority order: primary → secondary → fallback
-    /// This order MUST NEVER be changed to ensure consistent failover behavior
-    /// Index 0: Primary gateway (always tried first)
-    /// Index 1: Secondary gateway (tried if primary fails)
-    /// Index 2: Fallback gateway (tried if both primary and secondary fail)
+    /// Configured priority order: primary → secondary → fallback
+    ///
+    /// A `gateways` setting replaces the whole list; its order is kept as
+    /// given. Requests follow this order while every gateway is healthy;
+    /// `attempt_order` moves failing gateways back and skips those cooling down.
     gateways: Vec<String>,
-    /// Internal tracking field - does NOT affect priority order
-    /// Priority order is always: gateways[0] → gateways[1] → gateways[2]
+    /// Unused; the gateway tried first is chosen per request by `attempt_order`
     #[allow(dead_code)]
     current_gateway: usize,
     client: Client,
-    health_stats: Vec<GatewayHealth>,
-    /// Maximum number of gateway attempts per request (3 = all gateways)
+    health: Arc<std::sync::Mutex<GatewayHealthState>>,
+    /// Clock for cooldowns
+    clock: GatewayClock,
+    /// Consecutive failures that put a gateway into cooldown
+    failure_threshold: u32,
+    cooldown: Duration,
+    /// Maximum number of gateway attempts per request (defaults to all gateways)
     max_attempts: usize,
     /// Maximum number of retry attempts per individual gateway
     max_retries_per_gateway: usize,
     /// Base delay for exponential backoff in milliseconds
     base_delay_ms: u64,
+    /// Paces outbound requests, see `gateway_max_requests_per_second`
+    rate_limiter: Arc<std::sync::Mutex<RateLimiter>>,
 }
 
 impl GatewayClient {
     pub fn new() -> Self {
-        // IMMUTABLE gateway priority order - NEVER change this order
-        // Priority: primary → secondary → fallback
-        // This order is enforced by the task requirements and ensures consistent failover behavior
-        let gateways = vec![
-            "https://api.na-backend.odysee.com/api/v1/proxy".to_string(), // Primary (index 0)
-            "https://api.lbry.tv/api/v1/proxy".to_string(),               // Secondary (index 1)
-            "https://api.odysee.com/api/v1/proxy".to_string(),            // Fallback (index 2)
-        ];
-
-        let health_stats = gateways
-            .iter()
-            .map(|url| GatewayHealth {
-                url: url.clone(),
-                status: "unknown".to_string(),
-                last_success: None,
-                last_error: None,
-                response_time_ms: None,
-            })
-            .collect();
+        // Configured priority: primary → secondary → fallback
+        Self::with_gateways(DEFAULT_GATEWAYS.iter().map(|url| url.to_string()).collect())
+    }
 
+    /// Creates a client that tries `gateways` in the given order
+    ///
+    /// Callers are expected to pass a list from `resolve_gateways`; it is not
+    /// re-validated here.
+    pub fn with_gateways(gateways: Vec<String>) -> Self {
         Self {
+            health: Arc::new(std::sync::Mutex::new(GatewayHealthState::new(&gateways))),
+            clock: GatewayClock::default(),
+            failure_threshold: GATEWAY_FAILURE_THRESHOLD,
+            cooldown: GATEWAY_COOLDOWN,
+            max_attempts: gateways.len(), // Attempt every gateway
             gateways,
             current_gateway: 0,
             client: Client::builder()
                 .timeout(Duration::from_secs(10))
                 .build()
                 .expect("Failed to create HTTP client"),
-            health_stats,
-            max_attempts: 3,            // Attempt all 3 gateways
             max_retries_per_gateway: 2, // Retry each gateway up to 2 times before moving to next
             base_delay_ms: 300,         // Start with 300ms delay
+            rate_limiter: Arc::new(std::sync::Mutex::new(RateLimiter::new(
+                DEFAULT_GATEWAY_MAX_REQUESTS_PER_SECOND,
+            ))),
+        }
+    }
+
+    fn health(&self) -> std::sync::MutexGuard<'_, GatewayHealthState> {
+        self.health
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn rate_limiter(&self) -> std::sync::MutexGuard<'_, RateLimiter> {
+        self.rate_limiter
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn initial_health_stats(gateways: &[String]) -> Vec<GatewayHealth> {
+        gateways
+            .iter()
+            .map(|url| GatewayHealth {
+                url: url.clone(),
+                status: "unknown".to_string(),
+                last_success: None,
+                last_error: None,
+                response_time_ms: None,
+                recent_successes: 0,
+                recent_failures: 0,
+                consecutive_failures: 0,
+                last_failure: None,
+                cooldown_until: None,
+            })
+            .collect()
+    }
+
+    /// Replaces the gateway list after the `gateways` setting changes
+    ///
+    /// Health stats are reset since they describe the old endpoints. Requests
+    /// already in flight keep updating the old stats, not the new ones.
+    pub fn set_gateways(&mut self, gateways: Vec<String>) {
+        if gateways.is_empty() || gateways == self.gateways {
+            return;
         }
+
+        info!("Using gateways: {:?}", gateways);
+        self.health = Arc::new(std::sync::Mutex::new(GatewayHealthState::new(&gateways)));
+        self.max_attempts = gateways.len();
+        self.gateways = gateways;
+    }
+
+    /// Sets the client-side cap on outbound requests per second
+    pub fn set_max_requests_per_second(&mut self, max_per_second: u32) {
+        self.rate_limiter().set_max_per_second(max_per_second);
     }
 
-    pub async fn fetch_with_failover(&mut self, request: OdyseeRequest) -> Result<OdyseeResponse> {
-        #[allow(unused_assignments)]
+    pub fn max_requests_per_second(&self) -> u32 {
+        self.rate_limiter().max_per_second()
+    }
+
+    /// Waits for a rate-limit slot before one HTTP attempt
+    ///
+    /// Every attempt takes a token, retries and failovers included. The
+    /// limiter is locked only to reserve the slot, not while waiting for it.
+    async fn wait_for_rate_limit(&self, method: &str) -> Result<()> {
+        let reserved = self.rate_limiter().reserve();
+        let wait = match reserved {
+            Ok(wait) => wait,
+            Err(e) => {
+                if let KiyyaError::RateLimitExceeded {
+                    retry_after_seconds,
+                } = &e
+                {
+                    log_security_event(SecurityEvent::RateLimitTriggered {
+                        endpoint: format!("client:{}", method),
+                        retry_after_seconds: *retry_after_seconds,
+                    });
+                }
+                warn!("Rejected {} request: gateway rate limit queue is full", method);
+                return Err(e);
+            }
+        };
+
+        if !wait.is_zero() {
+            debug!("Gateway rate limit: delaying request by {:?}", wait);
+            sleep(wait).await;
+        }
+        Ok(())
+    }
+
+    pub async fn fetch_with_failover(&self, request: OdyseeRequest) -> Result<OdyseeResponse> {
         let mut last_error = None;
+        let mut failures = Vec::new();
         let mut gateway_attempt = 0;
         let mut total_attempts = 0;
 
-        // IMMUTABLE PRIORITY ORDER: Always try gateways in the same order
-        // primary (index 0) → secondary (index 1) → fallback (index 2)
-        // NEVER reorder based on previous success/failure
-        while gateway_attempt < self.max_attempts && gateway_attempt < self.gateways.len() {
-            let gateway_index = gateway_attempt; // Always use sequential order: 0, 1, 2
+        // The configured order is kept among healthy gateways; recently
+        // failing ones go last and ones in cooldown are skipped
+        let order = self.attempt_order();
+        let planned_attempts = order.len().min(self.max_attempts);
+
+        for &gateway_index in order.iter().take(planned_attempts) {
             let gateway_url = self.gateways[gateway_index].clone();
 
             info!(
                 "Trying gateway {} of {}: {} ({})",
                 gateway_attempt + 1,
-                self.max_attempts,
+                planned_attempts,
                 gateway_url,
                 match gateway_index {
                     0 => "PRIMARY",
@@ -96,6 +590,7 @@ impl GatewayClient {
             // Retry logic for individual gateway
             let mut retry_attempt = 0;
             while retry_attempt <= self.max_retries_per_gateway {
+                self.wait_for_rate_limit(&request.method).await?;
                 total_attempts += 1;
 
                 let attempt_label = if retry_attempt == 0 {
@@ -123,9 +618,10 @@ impl GatewayClient {
                     Ok(response) => {
                         let response_time = start_time.elapsed();
                         self.log_gateway_success(gateway_index, response_time);
+                        self.record_gateway_outcome(gateway_index, true);
 
                         // DO NOT update current_gateway - maintain immutable priority order
-                        // The next request will always start with primary gateway (index 0)
+                        // The next request starts from the top of `attempt_order` again
 
                         info!(
                             "Gateway {} ({}) succeeded on {} after {}ms (total attempts: {})",
@@ -205,8 +701,16 @@ impl GatewayClient {
                 retry_attempt += 1;
             }
 
+            if let Some(e) = last_error.take() {
+                let failure = GatewayFailure::from_error(&gateway_url, &e);
+                if counts_against_gateway(&failure) {
+                    self.record_gateway_outcome(gateway_index, false);
+                }
+                failures.push(failure);
+            }
+
             // Apply gateway failover delay before trying next gateway
-            if gateway_attempt < self.max_attempts - 1 {
+            if gateway_attempt + 1 < planned_attempts {
                 let failover_delay = match gateway_attempt {
                     0 => 300,  // 300ms before trying secondary
                     1 => 1000, // 1s before trying fallback
@@ -236,22 +740,17 @@ impl GatewayClient {
             gateway_attempt += 1;
         }
 
-        // All gateways failed - return comprehensive error with last error details
-        let last_error_msg = last_error
-            .as_ref()
-            .map(|e| format!("{:?}", e))
-            .unwrap_or_else(|| "No error details available".to_string());
-        
+        // All gateways failed - return each gateway's final error
         let final_error = KiyyaError::AllGatewaysFailed {
             attempts: total_attempts as u32,
-            last_error: last_error_msg.clone(),
+            failures,
         };
         error!(
-            "All {} gateways failed after {} total attempts ({} gateway attempts). Last error: {}",
+            "All {} gateways failed after {} total attempts ({} gateway attempts): {}",
             self.gateways.len(),
             total_attempts,
             gateway_attempt,
-            last_error_msg
+            final_error
         );
 
         // Log the final failure to dedicated gateway log
@@ -260,6 +759,91 @@ impl GatewayClient {
         Err(final_error)
     }
 
+    /// Indices of the gateways to try, in order
+    ///
+    /// Gateways keep their configured order unless they have been failing:
+    /// those move behind the healthy ones, and those in cooldown are left
+    /// out. If every gateway is cooling down they are all tried, soonest
+    /// cooldown end first, rather than failing without a request.
+    fn attempt_order(&self) -> Vec<usize> {
+        let now = self.clock.now();
+        let health = self.health();
+        let trackers = &health.trackers;
+        let (mut order, cooling): (Vec<usize>, Vec<usize>) =
+            (0..self.gateways.len()).partition(|&i| !trackers[i].cooling_down(now));
+
+        for &index in &cooling {
+            let remaining = trackers[index]
+                .cooldown_until
+                .map(|until| until.saturating_duration_since(now))
+                .unwrap_or_default();
+            info!(
+                "Skipping gateway {}: cooling down for another {}s",
+                self.gateways[index],
+                remaining.as_secs()
+            );
+        }
+
+        if order.is_empty() {
+            warn!("Every gateway is cooling down; trying them anyway");
+            order = cooling;
+            order.sort_by_key(|&i| trackers[i].cooldown_until);
+            return order;
+        }
+
+        // Stable, so ties keep the configured priority order
+        order.sort_by_key(|&i| {
+            let tracker = &trackers[i];
+            (tracker.consecutive_failures, tracker.failures())
+        });
+        order
+    }
+
+    /// Updates a gateway's rolling counts and starts its cooldown once it
+    /// has failed `failure_threshold` times in a row
+    fn record_gateway_outcome(&self, gateway_index: usize, success: bool) {
+        let mut health_state = self.health();
+        let GatewayHealthState {
+            health_stats,
+            trackers,
+        } = &mut *health_state;
+        let tracker = match trackers.get_mut(gateway_index) {
+            Some(tracker) => tracker,
+            None => return,
+        };
+        tracker.record(success);
+
+        let mut cooldown_started = false;
+        if !success && tracker.consecutive_failures >= self.failure_threshold {
+            tracker.cooldown_until = Some(self.clock.now() + self.cooldown);
+            cooldown_started = true;
+        }
+
+        if let Some(health) = health_stats.get_mut(gateway_index) {
+            let now = chrono::Utc::now().timestamp();
+            health.recent_successes = tracker.successes();
+            health.recent_failures = tracker.failures();
+            health.consecutive_failures = tracker.consecutive_failures;
+            if success {
+                health.cooldown_until = None;
+            } else {
+                health.last_failure = Some(now);
+            }
+            if cooldown_started {
+                health.cooldown_until = Some(now + self.cooldown.as_secs() as i64);
+            }
+        }
+
+        if cooldown_started {
+            warn!(
+                "Gateway {} failed {} times in a row; skipping it for {}s",
+                self.gateways[gateway_index],
+                tracker.consecutive_failures,
+                self.cooldown.as_secs()
+            );
+        }
+    }
+
     async fn make_request(
         &self,
         gateway_url: &str,
@@ -464,8 +1048,8 @@ impl GatewayClient {
         }
     }
 
-    fn log_gateway_success(&mut self, gateway_index: usize, response_time: Duration) {
-        if let Some(health) = self.health_stats.get_mut(gateway_index) {
+    fn log_gateway_success(&self, gateway_index: usize, response_time: Duration) {
+        if let Some(health) = self.health().health_stats.get_mut(gateway_index) {
             health.status = "healthy".to_string();
             health.last_success = Some(chrono::Utc::now().timestamp());
             health.response_time_ms = Some(response_time.as_millis() as u64);
@@ -492,12 +1076,12 @@ impl GatewayClient {
     }
 
     fn log_gateway_failure(
-        &mut self,
+        &self,
         gateway_index: usize,
         error: &KiyyaError,
         response_time: Duration,
     ) {
-        if let Some(health) = self.health_stats.get_mut(gateway_index) {
+        if let Some(health) = self.health().health_stats.get_mut(gateway_index) {
             health.status = "down".to_string();
             health.last_error = Some(error.to_string());
             health.response_time_ms = Some(response_time.as_millis() as u64);
@@ -592,17 +1176,41 @@ impl GatewayClient {
         error!("GATEWAY_SUMMARY: {}", summary.trim());
     }
 
-    pub fn get_health_stats(&self) -> &[GatewayHealth] {
-        &self.health_stats
+    pub fn get_health_stats(&self) -> Vec<GatewayHealth> {
+        self.health().health_stats.clone()
     }
 
+    /// Per-gateway health for diagnostics, in configured order
+    ///
+    /// A cooldown that has already ended is reported as cleared.
+    pub fn get_gateway_health(&self) -> Vec<GatewayHealth> {
+        let now = self.clock.now();
+        let health_state = self.health();
+        health_state
+            .health_stats
+            .iter()
+            .zip(&health_state.trackers)
+            .map(|(health, tracker)| {
+                let mut health = health.clone();
+                if !tracker.cooling_down(now) {
+                    health.cooldown_until = None;
+                }
+                health
+            })
+            .collect()
+    }
+
+    /// The highest-priority configured gateway
+    ///
+    /// This is not necessarily the gateway the next request goes to first:
+    /// a failing primary is tried after healthier gateways, see `attempt_order`.
     pub fn get_current_gateway(&self) -> &str {
-        // Always return the primary gateway since we maintain immutable priority order
         &self.gateways[0]
     }
 
-    /// Gets the immutable gateway priority list
-    /// This order CANNOT be changed to ensure consistent failover behavior
+    /// Gets the configured gateway priority list
+    ///
+    /// Health tracking reorders attempts per request without changing it.
     pub fn get_gateway_priority_order(&self) -> &[String] {
         &self.gateways
     }
@@ -683,6 +1291,9 @@ mod tests {
     use super::*;
     use crate::models::OdyseeRequest;
 
+    /// Serializes tests that touch the global in-flight request map
+    static INFLIGHT_TEST_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
     /// Creates a test OdyseeRequest for testing
     fn create_test_request() -> OdyseeRequest {
         OdyseeRequest {
@@ -905,12 +1516,13 @@ mod tests {
     /// Test for gateway health logging functionality
     #[test]
     fn test_gateway_health_logging() {
-        let mut client = GatewayClient::new();
+        let client = GatewayClient::new();
         let response_time = Duration::from_millis(250);
 
         // Test success logging
         client.log_gateway_success(0, response_time);
-        let health = &client.health_stats[0];
+        let health_stats = client.get_health_stats();
+        let health = &health_stats[0];
         assert_eq!(health.status, "healthy");
         assert!(health.last_success.is_some());
         assert_eq!(health.response_time_ms, Some(250));
@@ -919,7 +1531,8 @@ mod tests {
         // Test failure logging
         let error = KiyyaError::gateway_error("Connection timeout");
         client.log_gateway_failure(1, &error, response_time);
-        let health = &client.health_stats[1];
+        let health_stats = client.get_health_stats();
+        let health = &health_stats[1];
         assert_eq!(health.status, "down");
         assert!(health.last_error.is_some());
         assert_eq!(health.response_time_ms, Some(250));
@@ -1743,4 +2356,418 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test]
+    async fn test_rate_limiter_paces_requests_under_configured_rate() {
+        let rate = 50;
+        let mut limiter = RateLimiter::new(rate);
+        let start = Instant::now();
+
+        let mut sent_at = Vec::new();
+        for _ in 0..100 {
+            limiter.acquire().await.unwrap();
+            sent_at.push(start.elapsed());
+        }
+
+        // The first second's burst goes out at once; after that each request
+        // waits for its own token
+        for (i, elapsed) in sent_at.iter().enumerate() {
+            let earliest = (i as f64 + 1.0 - rate as f64).max(0.0) / rate as f64;
+            assert!(
+                elapsed.as_secs_f64() >= earliest - 0.005,
+                "request {} sent after {:?}, expected at least {:.3}s",
+                i,
+                elapsed,
+                earliest
+            );
+        }
+        assert!(sent_at[99] >= Duration::from_millis(990));
+    }
+
+    #[test]
+    fn test_rate_limiter_rejects_when_queue_is_full() {
+        let mut limiter = RateLimiter::new(1);
+
+        // One token of burst, then each request queues a second behind the last
+        for expected_wait in 0..=5u64 {
+            let wait = limiter.reserve().unwrap();
+            assert!(wait.as_secs_f64() <= expected_wait as f64 + 0.01);
+        }
+        assert!(matches!(
+            limiter.reserve(),
+            Err(KiyyaError::RateLimitExceeded { .. })
+        ));
+
+        limiter.set_max_per_second(0);
+        assert_eq!(limiter.max_per_second(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_every_attempt_spends_a_rate_limit_token() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let mut client = GatewayClient::with_gateways(vec![server.uri(), server.uri()]);
+        client.max_retries_per_gateway = 0;
+        client.set_max_requests_per_second(1);
+
+        // The failover attempt waits for a token of its own, taken from the
+        // limiter the clone shares with the original
+        let start = Instant::now();
+        assert!(client
+            .clone()
+            .fetch_with_failover(create_test_request())
+            .await
+            .is_err());
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+        assert!(start.elapsed() >= Duration::from_millis(950));
+        assert!(client.rate_limiter().reserve().unwrap() >= Duration::from_millis(900));
+    }
+
+    #[tokio::test]
+    async fn test_identical_inflight_requests_are_coalesced() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let _lock = INFLIGHT_TEST_LOCK.lock().await;
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "result": { "items": [] },
+                        "id": 1
+                    }))
+                    .set_delay(Duration::from_millis(200)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let gateway = Mutex::new(GatewayClient::with_gateways(vec![server.uri()]));
+        let request = OdyseeRequest {
+            method: "claim_search".to_string(),
+            params: serde_json::json!({ "channel": "@coalesce-test", "page": 1 }),
+        };
+
+        let results = futures::future::join_all(
+            (0..10).map(|_| fetch_coalesced(&gateway, request.clone())),
+        )
+        .await;
+        for result in results {
+            assert!(result.unwrap().success);
+        }
+
+        assert!(inflight_requests().get(&request_key(&request)).is_none());
+        assert!(get_inflight_requests()
+            .iter()
+            .all(|info| info.key != request_key(&request)));
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_clear_inflight_requests_unblocks_wedged_key() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let _lock = INFLIGHT_TEST_LOCK.lock().await;
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "result": { "items": [] },
+                "id": 1
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let gateway = Mutex::new(GatewayClient::with_gateways(vec![server.uri()]));
+        let request = OdyseeRequest {
+            method: "claim_search".to_string(),
+            params: serde_json::json!({ "channel": "@wedged-test", "page": 1 }),
+        };
+        let key = request_key(&request);
+
+        // An entry whose owner is gone but never cleaned up
+        let (sender, _) = broadcast::channel(1);
+        inflight_requests().insert(
+            key.clone(),
+            InflightRequest {
+                id: NEXT_INFLIGHT_ID.fetch_add(1, Ordering::Relaxed),
+                method: request.method.clone(),
+                started_at: Instant::now(),
+                sender,
+            },
+        );
+
+        let waiter = tokio::spawn(async move { fetch_coalesced(&gateway, request).await });
+        for _ in 0..100 {
+            let waiting = get_inflight_requests()
+                .iter()
+                .any(|info| info.key == key && info.waiters == 1);
+            if waiting {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!waiter.is_finished(), "request should be stuck behind the entry");
+
+        assert!(clear_inflight_requests() >= 1);
+        let response = tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .expect("cleared request should go to the network")
+            .unwrap()
+            .unwrap();
+        assert!(response.success);
+        assert!(get_inflight_requests().iter().all(|info| info.key != key));
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_all_gateways_failed_reports_each_gateway() {
+        use crate::error::GatewayFailureKind;
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let slow = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&slow)
+            .await;
+        let unavailable = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&unavailable)
+            .await;
+        let api_error = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "error": { "code": -32500, "message": "claim_search is disabled" },
+                "id": 1
+            })))
+            .mount(&api_error)
+            .await;
+        // Nothing listens on a port we just released
+        let unreachable = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}", listener.local_addr().unwrap())
+        };
+
+        let mut client = GatewayClient::with_gateways(vec![
+            slow.uri(),
+            unavailable.uri(),
+            api_error.uri(),
+            unreachable.clone(),
+        ]);
+        client.client = Client::builder()
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        client.max_retries_per_gateway = 0;
+
+        let request = OdyseeRequest {
+            method: "claim_search".to_string(),
+            params: serde_json::json!({ "page": 1 }),
+        };
+        let error = client.fetch_with_failover(request).await.unwrap_err();
+
+        let failures = match &error {
+            KiyyaError::AllGatewaysFailed { attempts, failures } => {
+                assert_eq!(*attempts, 4);
+                failures.clone()
+            }
+            other => panic!("Expected AllGatewaysFailed, got {:?}", other),
+        };
+        let kinds: Vec<(GatewayFailureKind, Option<u16>)> =
+            failures.iter().map(|f| (f.kind, f.status)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (GatewayFailureKind::Timeout, None),
+                (GatewayFailureKind::HttpStatus, Some(503)),
+                (GatewayFailureKind::ApiError, None),
+                (GatewayFailureKind::Connection, None),
+            ]
+        );
+        assert_eq!(failures[0].gateway, slow.uri());
+        assert_eq!(failures[3].gateway, unreachable);
+        assert!(failures[2].message.contains("claim_search is disabled"));
+        assert!(error.to_string().contains(concat!(
+            "tried 4 gateways: 1 timed out, 1 returned HTTP 503, ",
+            "1 returned an API error, 1 could not be reached"
+        )));
+    }
+
+    #[test]
+    fn test_resolve_gateways_filters_invalid_entries() {
+        let setting = serde_json::json!([
+            "https://proxy.example.com/api/v1/proxy/",
+            "http://insecure.example.com/api/v1/proxy",
+            "not a url",
+            "https://",
+            "https://proxy.example.com/api/v1/proxy",
+            " https://eu.proxy.example.com/api/v1/proxy "
+        ])
+        .to_string();
+
+        assert_eq!(
+            resolve_gateways(Some(&setting)),
+            vec![
+                "https://proxy.example.com/api/v1/proxy".to_string(),
+                "https://eu.proxy.example.com/api/v1/proxy".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_gateways_falls_back_to_defaults() {
+        let defaults: Vec<String> = DEFAULT_GATEWAYS.iter().map(|url| url.to_string()).collect();
+
+        assert_eq!(resolve_gateways(None), defaults);
+        assert_eq!(resolve_gateways(Some("[]")), defaults);
+        assert_eq!(resolve_gateways(Some("not json")), defaults);
+        assert_eq!(
+            resolve_gateways(Some(r#"["http://a.example.com", "ftp://b.example.com"]"#)),
+            defaults
+        );
+
+        let many: Vec<String> = (0..20)
+            .map(|i| format!("https://gw{}.example.com/api/v1/proxy", i))
+            .collect();
+        let setting = serde_json::to_string(&many).unwrap();
+        assert_eq!(resolve_gateways(Some(&setting)).len(), MAX_CUSTOM_GATEWAYS);
+    }
+
+    #[test]
+    fn test_custom_gateways_are_used() {
+        let setting = r#"["https://self-hosted.example.com/api/v1/proxy", "http://bad.example.com"]"#;
+        let mut client = GatewayClient::new();
+        client.set_gateways(resolve_gateways(Some(setting)));
+
+        assert_eq!(
+            client.get_gateway_priority_order(),
+            ["https://self-hosted.example.com/api/v1/proxy".to_string()]
+        );
+        assert_eq!(client.get_current_gateway(), "https://self-hosted.example.com/api/v1/proxy");
+        assert_eq!(client.get_health_stats().len(), 1);
+        assert_eq!(
+            client.get_health_stats()[0].url,
+            "https://self-hosted.example.com/api/v1/proxy"
+        );
+        assert_eq!(client.max_attempts, 1);
+
+        // Clearing the setting restores the built-in list
+        client.set_gateways(resolve_gateways(None));
+        assert_eq!(client.get_gateway_priority_order(), DEFAULT_GATEWAYS);
+        assert_eq!(client.max_attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn test_failing_gateway_is_deprioritized_then_skipped() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let ok = || {
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "result": { "items": [] },
+                "id": 1
+            }))
+        };
+        let broken = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&broken)
+            .await;
+        let backup = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ok())
+            .mount(&backup)
+            .await;
+
+        let mut client = GatewayClient::with_gateways(vec![broken.uri(), backup.uri()]);
+        client.max_retries_per_gateway = 0;
+
+        // The primary fails once and drops behind the backup
+        assert!(client
+            .fetch_with_failover(create_test_request())
+            .await
+            .is_ok());
+        assert_eq!(client.attempt_order(), vec![1, 0]);
+        assert!(client
+            .fetch_with_failover(create_test_request())
+            .await
+            .is_ok());
+        assert_eq!(broken.received_requests().await.unwrap().len(), 1);
+
+        // With the backup down too, the primary keeps failing until it cools down
+        backup.reset().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&backup)
+            .await;
+        assert!(client
+            .fetch_with_failover(create_test_request())
+            .await
+            .is_err());
+        assert!(client
+            .fetch_with_failover(create_test_request())
+            .await
+            .is_err());
+        assert_eq!(broken.received_requests().await.unwrap().len(), 3);
+
+        let health = client.get_gateway_health();
+        assert_eq!(health[0].consecutive_failures, GATEWAY_FAILURE_THRESHOLD);
+        assert_eq!(health[0].recent_failures, 3);
+        assert!(health[0].last_failure.is_some());
+        assert!(health[0].cooldown_until.is_some());
+        assert_eq!(health[1].recent_successes, 2);
+        assert_eq!(health[1].recent_failures, 2);
+
+        // While cooling down the primary is not contacted at all
+        backup.reset().await;
+        Mock::given(method("POST"))
+            .respond_with(ok())
+            .mount(&backup)
+            .await;
+        assert_eq!(client.attempt_order(), vec![1]);
+        assert!(client
+            .fetch_with_failover(create_test_request())
+            .await
+            .is_ok());
+        assert_eq!(broken.received_requests().await.unwrap().len(), 3);
+
+        // Once the cooldown ends it is a candidate again, behind the backup
+        client.clock.advance(GATEWAY_COOLDOWN);
+        assert_eq!(client.attempt_order(), vec![1, 0]);
+        assert!(client.get_gateway_health()[0].cooldown_until.is_none());
+    }
+
+    #[test]
+    fn test_gateways_are_tried_when_all_are_cooling_down() {
+        let mut client = GatewayClient::with_gateways(vec![
+            "https://a.example.com/api/v1/proxy".to_string(),
+            "https://b.example.com/api/v1/proxy".to_string(),
+        ]);
+        client.failure_threshold = 1;
+
+        client.record_gateway_outcome(1, false);
+        assert_eq!(client.attempt_order(), vec![0]);
+        client.record_gateway_outcome(0, false);
+        assert_eq!(client.attempt_order(), vec![1, 0]);
+
+        // A success ends the cooldown straight away
+        client.record_gateway_outcome(0, true);
+        assert_eq!(client.attempt_order(), vec![0]);
+        assert_eq!(client.get_gateway_health()[0].consecutive_failures, 0);
+    }
 }
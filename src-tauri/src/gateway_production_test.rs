@@ -101,7 +101,7 @@ mod tests {
     async fn test_production_failover_mechanism() {
         println!("\n=== Testing Production Failover Mechanism ===\n");
 
-        let mut gateway_client = GatewayClient::new();
+        let gateway_client = GatewayClient::new();
 
         // Create a valid request
         let request = OdyseeRequest {
@@ -194,7 +194,7 @@ mod tests {
     async fn test_production_failover_consistency() {
         println!("\n=== Testing Production Failover Consistency ===\n");
 
-        let mut gateway_client = GatewayClient::new();
+        let gateway_client = GatewayClient::new();
 
         // Make multiple requests to verify consistent behavior
         for i in 1..=3 {
@@ -260,7 +260,7 @@ mod tests {
     async fn test_production_failover_different_requests() {
         println!("\n=== Testing Production Failover with Different Request Types ===\n");
 
-        let mut gateway_client = GatewayClient::new();
+        let gateway_client = GatewayClient::new();
 
         // Test 1: ClaimSearch with tags
         println!("Test 1: ClaimSearch with tags");
@@ -397,7 +397,7 @@ mod tests {
     async fn test_production_gateway_logging() {
         println!("\n=== Testing Production Gateway Logging ===\n");
 
-        let mut gateway_client = GatewayClient::new();
+        let gateway_client = GatewayClient::new();
 
         // Make a request that will generate logs
         let request = OdyseeRequest {
@@ -45,6 +45,7 @@ mod hero_single_item_tests {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         };
 
         // Store the single hero item in cache
@@ -129,6 +130,7 @@ mod hero_single_item_tests {
                         etag: None,
                         content_hash: None,
                         raw_json: None,
+                        stale: false,
                     }
                 })
                 .collect();
@@ -231,6 +233,7 @@ mod hero_single_item_tests {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         };
 
         db.store_content_items(vec![item.clone()])
@@ -258,4 +261,73 @@ mod hero_single_item_tests {
             "Text search should return results independently of threshold"
         );
     }
+
+    /// CRITICAL TEST: A single cached hero_trailer is enough to skip the network
+    /// with the default `min_cache_results`, and raising the setting forces a refresh
+    #[tokio::test]
+    async fn test_min_cache_results_single_hero_item() {
+        use crate::commands::{cache_satisfies_query, min_cache_results};
+
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let db_path = temp_dir.path().join("test_hero_min_results.db");
+        let db = Database::new_with_path(&db_path)
+            .await
+            .expect("Failed to create database");
+
+        let hero_item = ContentItem {
+            claim_id: "hero-min-results".to_string(),
+            title: "Lone Hero Trailer".to_string(),
+            description: None,
+            tags: vec!["hero_trailer".to_string()],
+            thumbnail_url: None,
+            duration: Some(120),
+            release_time: 1234567890,
+            video_urls: HashMap::new(),
+            compatibility: CompatibilityInfo {
+                compatible: true,
+                fallback_available: false,
+                reason: None,
+            },
+            etag: None,
+            content_hash: None,
+            raw_json: None,
+            stale: false,
+        };
+        db.store_content_items(vec![hero_item])
+            .await
+            .expect("Failed to store hero item");
+
+        let cached_items = db
+            .get_cached_content(CacheQuery {
+                tags: Some(vec!["hero_trailer".to_string()]),
+                text_search: None,
+                limit: Some(20),
+                offset: None,
+                order_by: Some("releaseTime DESC".to_string()),
+            })
+            .await
+            .expect("Failed to get cached content");
+        assert_eq!(cached_items.len(), 1);
+
+        // Unset, the threshold defaults to 1 and the single item is served from cache
+        let threshold = min_cache_results(&db).await.unwrap();
+        assert_eq!(threshold, 1);
+        assert!(cache_satisfies_query(cached_items.len(), false, threshold));
+
+        // A user who wants fuller pages can raise the threshold
+        db.set_setting("min_cache_results", "6").await.unwrap();
+        let threshold = min_cache_results(&db).await.unwrap();
+        assert_eq!(threshold, 6);
+        assert!(!cache_satisfies_query(cached_items.len(), false, threshold));
+
+        // Lowering it again serves the cache
+        db.set_setting("min_cache_results", "1").await.unwrap();
+        let threshold = min_cache_results(&db).await.unwrap();
+        assert_eq!(threshold, 1);
+        assert!(cache_satisfies_query(cached_items.len(), false, threshold));
+
+        // Empty cache and text searches always go to the network
+        assert!(!cache_satisfies_query(0, false, threshold));
+        assert!(!cache_satisfies_query(cached_items.len(), true, threshold));
+    }
 }
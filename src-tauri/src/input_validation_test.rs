@@ -28,17 +28,18 @@ mod tests {
 
     #[test]
     fn test_quality_validation_prevents_invalid_values() {
-        // Valid qualities should pass (only "master" in new CDN-first architecture)
+        // Valid qualities should pass: master and the heights claims may list
         assert!(validation::validate_quality("master").is_ok());
+        assert!(validation::validate_quality("720p").is_ok());
+        assert!(validation::validate_quality("1080p").is_ok());
+        assert!(validation::validate_quality("480p").is_ok());
 
         // Case insensitive
         assert!(validation::validate_quality("MASTER").is_ok());
         assert!(validation::validate_quality("Master").is_ok());
 
-        // Invalid qualities should fail (including old quality-specific values)
-        assert!(validation::validate_quality("720p").is_err());
-        assert!(validation::validate_quality("1080p").is_err());
-        assert!(validation::validate_quality("480p").is_err());
+        // Invalid qualities should fail
+        assert!(validation::validate_quality("4k").is_err());
         assert!(validation::validate_quality("invalid").is_err());
         assert!(validation::validate_quality("9999p").is_err());
         assert!(validation::validate_quality("").is_err());
@@ -51,9 +52,9 @@ mod tests {
     fn test_url_validation_prevents_malicious_urls() {
         // Valid download URLs should pass
         assert!(validation::validate_download_url("https://example.com/video.mp4").is_ok());
-        assert!(validation::validate_download_url("http://example.com/video.mp4").is_ok());
 
         // Invalid protocols should fail
+        assert!(validation::validate_download_url("http://example.com/video.mp4").is_err());
         assert!(validation::validate_download_url("ftp://example.com/video.mp4").is_err());
         assert!(validation::validate_download_url("file:///etc/passwd").is_err());
         assert!(validation::validate_download_url("javascript:alert(1)").is_err());
@@ -191,9 +192,10 @@ mod tests {
         assert!(validation::validate_setting_value("cache_ttl_minutes", "2000").is_err());
         assert!(validation::validate_setting_value("cache_ttl_minutes", "invalid").is_err());
 
-        // Quality values (only "master" in new CDN-first architecture)
+        // Quality values: master and the ranked download heights
         assert!(validation::validate_setting_value("last_used_quality", "master").is_ok());
-        assert!(validation::validate_setting_value("last_used_quality", "720p").is_err());
+        assert!(validation::validate_setting_value("last_used_quality", "720p").is_ok());
+        assert!(validation::validate_setting_value("last_used_quality", "4k").is_err());
         assert!(validation::validate_setting_value("last_used_quality", "invalid").is_err());
     }
 
@@ -321,6 +321,7 @@ mod tests {
                 etag: None,
                 content_hash: None,
                 raw_json: None,
+                stale: false,
             }];
 
             db.store_content_items(test_content)
@@ -602,6 +603,7 @@ mod tests {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         };
 
         // Store the hero item in cache
@@ -739,6 +741,7 @@ mod tests {
             etag: None,
             content_hash: None,
             raw_json: None, // No raw_json means no direct URL fields were present
+            stale: false,
         };
 
         // Store the hero item in cache
@@ -889,6 +892,7 @@ mod tests {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         };
         valid_items.push(episode_1);
 
@@ -921,6 +925,7 @@ mod tests {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         };
         valid_items.push(episode_2);
 
@@ -953,6 +958,7 @@ mod tests {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         };
         valid_items.push(episode_3);
 
@@ -1214,6 +1220,7 @@ mod tests {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         };
 
         db.store_content_items(vec![valid_movie])
@@ -1299,6 +1306,7 @@ mod tests {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         };
 
         db.store_content_items(vec![series_item])
@@ -1424,6 +1432,7 @@ mod tests {
                 etag: None,
                 content_hash: None,
                 raw_json: None,
+                stale: false,
             };
 
             series_items.push(episode);
@@ -1468,6 +1477,7 @@ mod tests {
                 etag: None,
                 content_hash: None,
                 raw_json: None,
+                stale: false,
             };
 
             movie_items.push(movie);
@@ -1713,6 +1723,7 @@ mod tests {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         };
 
         db.store_content_items(vec![new_hero])
@@ -1847,6 +1858,7 @@ mod tests {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         });
 
         // 2. Movie content with base tag
@@ -1878,6 +1890,7 @@ mod tests {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         });
 
         // 3. Series content with base tag
@@ -1909,6 +1922,7 @@ mod tests {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         });
 
         // 4. Sitcom content
@@ -1940,6 +1954,7 @@ mod tests {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         });
 
         // 5. Kids content with filter tag
@@ -1971,6 +1986,7 @@ mod tests {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         });
 
         // Store all test items
@@ -0,0 +1,153 @@
+//! # Library Change Events
+//!
+//! Broadcasts a single `library-changed` event to every window whenever the
+//! backend mutates favorites, progress, offline content or the content cache,
+//! so windows and background tasks stay consistent without polling.
+//!
+//! ## Event Contract
+//!
+//! ```json
+//! { "kind": "favorite", "action": "added", "claim_id": "abc123" }
+//! ```
+//!
+//! - **kind**: `"favorite" | "progress" | "offline" | "cache"`
+//! - **action**: `"added" | "updated" | "removed" | "cleared"`
+//! - **claim_id**: The affected claim, or `null` when many items changed at
+//!   once (bulk cleanup, import, clearing). Consumers should refetch the
+//!   whole collection for `kind` when it is `null`.
+//!
+//! Events are emitted by the `Database` methods after their write commits,
+//! regardless of which command or task called them. Before `init` is called
+//! (and in tests) emitting is a no-op.
+
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tracing::{debug, warn};
+
+/// Name of the event emitted to the frontend
+pub const LIBRARY_CHANGED_EVENT: &str = "library-changed";
+
+static APP_HANDLE: OnceCell<AppHandle> = OnceCell::new();
+
+/// Which part of the library changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LibraryChangeKind {
+    Favorite,
+    Progress,
+    Offline,
+    Cache,
+}
+
+/// What happened to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LibraryChangeAction {
+    Added,
+    Updated,
+    Removed,
+    Cleared,
+}
+
+/// Payload of the `library-changed` event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LibraryChangedEvent {
+    pub kind: LibraryChangeKind,
+    pub action: LibraryChangeAction,
+    pub claim_id: Option<String>,
+}
+
+/// Registers the app handle used to broadcast events; later calls are ignored
+pub fn init(app_handle: AppHandle) {
+    if APP_HANDLE.set(app_handle).is_err() {
+        debug!("Library events already initialized");
+    }
+}
+
+/// Broadcasts a change to all windows
+pub fn emit(kind: LibraryChangeKind, action: LibraryChangeAction, claim_id: Option<&str>) {
+    let event = LibraryChangedEvent {
+        kind,
+        action,
+        claim_id: claim_id.map(str::to_string),
+    };
+
+    #[cfg(test)]
+    tests::record(&event);
+
+    if let Some(app_handle) = APP_HANDLE.get() {
+        if let Err(e) = app_handle.emit_all(LIBRARY_CHANGED_EVENT, &event) {
+            warn!("Failed to emit {}: {}", LIBRARY_CHANGED_EVENT, e);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use once_cell::sync::Lazy;
+    use std::sync::Mutex;
+
+    static RECORDED: Lazy<Mutex<Vec<LibraryChangedEvent>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+    pub(crate) fn record(event: &LibraryChangedEvent) {
+        RECORDED
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(event.clone());
+    }
+
+    /// Events emitted so far for `claim_id`, oldest first
+    ///
+    /// Tests run in parallel, so each test should use its own claim ids.
+    pub(crate) fn recorded_for(claim_id: &str) -> Vec<LibraryChangedEvent> {
+        RECORDED
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .filter(|event| event.claim_id.as_deref() == Some(claim_id))
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn test_payload_shape() {
+        let event = LibraryChangedEvent {
+            kind: LibraryChangeKind::Offline,
+            action: LibraryChangeAction::Removed,
+            claim_id: Some("abc123".to_string()),
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({ "kind": "offline", "action": "removed", "claim_id": "abc123" })
+        );
+
+        let bulk = LibraryChangedEvent {
+            kind: LibraryChangeKind::Cache,
+            action: LibraryChangeAction::Cleared,
+            claim_id: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&bulk).unwrap(),
+            serde_json::json!({ "kind": "cache", "action": "cleared", "claim_id": null })
+        );
+    }
+
+    #[test]
+    fn test_emit_without_app_handle_is_recorded_only() {
+        emit(
+            LibraryChangeKind::Favorite,
+            LibraryChangeAction::Added,
+            Some("library-events-no-handle"),
+        );
+        assert_eq!(
+            recorded_for("library-events-no-handle"),
+            vec![LibraryChangedEvent {
+                kind: LibraryChangeKind::Favorite,
+                action: LibraryChangeAction::Added,
+                claim_id: Some("library-events-no-handle".to_string()),
+            }]
+        );
+    }
+}
@@ -236,7 +236,7 @@ pub fn init_logging() -> Result<(), Box<dyn std::error::Error>> {
 ///
 /// Uses the application's data directory to store logs
 /// Falls back to a temp directory if app data dir is not available
-fn get_log_directory() -> Result<PathBuf, Box<dyn std::error::Error>> {
+pub(crate) fn get_log_directory() -> Result<PathBuf, Box<dyn std::error::Error>> {
     // Use path_security module to get validated logs directory
     match path_security::validate_subdir_path("logs", "") {
         Ok(log_dir) => Ok(log_dir),
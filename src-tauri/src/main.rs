@@ -1,16 +1,21 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod command_metrics;
 mod commands;
 mod crash_reporting;
 mod database;
 mod diagnostics;
+mod disk_io;
 mod download;
 mod encryption;
 mod error;
 mod error_logging;
+mod external_opener;
 mod gateway;
+mod library_events;
 mod logging;
+mod media_probe;
 mod migrations;
 mod models;
 mod path_security;
@@ -145,6 +150,12 @@ mod migration_clean_run_test;
 #[cfg(test)]
 mod hero_single_item_test;
 
+#[cfg(test)]
+mod tag_matching_property_test;
+
+#[cfg(test)]
+mod download_url_property_test;
+
 use std::sync::Arc;
 use tauri::{Manager, State};
 use tokio::sync::Mutex;
@@ -152,7 +163,7 @@ use tokio::sync::Mutex;
 use crate::database::Database;
 use crate::download::DownloadManager;
 use crate::gateway::GatewayClient;
-use crate::models::VersionManifest;
+use crate::models::{CacheEvictionPolicy, RawJsonFormat, ThumbnailUrlPolicy, VersionManifest};
 use crate::server::LocalServer;
 
 // Application state
@@ -162,99 +173,134 @@ pub struct AppState {
     pub gateway: Arc<Mutex<GatewayClient>>,
     pub download_manager: Arc<Mutex<DownloadManager>>,
     pub local_server: Arc<Mutex<LocalServer>>,
+    /// Client for short requests such as reachability probes, held outside
+    /// the download manager lock so they never wait on a download
+    pub http_client: reqwest::Client,
 }
 
 #[tokio::main]
 async fn main() {
-    println!("=== MAIN FUNCTION STARTED ===");
-
     // Initialize logging system with file rotation
-    println!("=== INITIALIZING LOGGING ===");
     if let Err(e) = crate::logging::init_logging() {
         eprintln!("Failed to initialize logging: {}", e);
         // Continue without logging rather than crash
     }
-    println!("=== LOGGING INITIALIZED ===");
 
     // Initialize crash reporting (optional)
-    println!("=== INITIALIZING CRASH REPORTING ===");
     if let Ok(app_data_path) = crate::path_security::get_app_data_dir() {
         crate::crash_reporting::init_crash_reporting(&app_data_path);
     } else {
         eprintln!("Failed to get app data directory for crash reporting");
     }
-    println!("=== CRASH REPORTING INITIALIZED ===");
 
     // CRITICAL: Emergency disable check runs before all other startup logic
-    println!("=== STARTING EMERGENCY DISABLE CHECK ===");
     tracing::info!("🔍 Starting emergency disable check...");
 
-    // TEMPORARY: Skip emergency disable check to isolate the hang
-    tracing::info!("⚠️ TEMPORARY: Skipping emergency disable check for debugging");
-    println!("=== SKIPPING EMERGENCY DISABLE CHECK (DEBUG) ===");
-
-    /*
     if let Err(e) = check_emergency_disable().await {
         tracing::error!("Emergency disable check failed: {}", e);
         // If we can't check emergency disable, proceed with caution
         // This ensures the app doesn't fail to start due to network issues
     }
-    */
 
     tracing::info!("✅ Emergency disable check complete");
-    println!("=== EMERGENCY DISABLE CHECK COMPLETE ===");
 
     // Initialize application state
-    println!("=== INITIALIZING APP STATE ===");
     tracing::info!("🔍 About to initialize app state...");
     let app_state = initialize_app_state()
         .await
         .expect("Failed to initialize application state");
     tracing::info!("✅ App state initialized");
-    println!("=== APP STATE INITIALIZED ===");
 
-    println!("=== BUILDING TAURI APP ===");
     tauri::Builder::default()
         .manage(app_state)
         .invoke_handler(tauri::generate_handler![
             commands::test_connection,
             commands::build_cdn_playback_url_test,
+            commands::get_cdn_gateway_info,
             commands::fetch_channel_claims,
+            commands::fetch_channel_claims_v2,
+            commands::warm_cache,
             commands::fetch_playlists,
+            commands::build_series_playlists,
             commands::resolve_claim,
+            commands::get_content_item,
+            commands::is_downloadable,
+            commands::is_downloading,
+            commands::get_download_status,
+            commands::resolve_best_quality,
+            commands::set_content_quality_pref,
+            commands::get_content_quality_pref,
             commands::download_movie_quality,
             commands::stream_offline,
+            commands::list_offline,
+            commands::get_offline_total_duration,
+            commands::restart_local_server,
             commands::delete_offline,
+            commands::prebuffer,
+            commands::read_content_range,
             commands::save_progress,
+            commands::save_progress_batch,
             commands::get_progress,
+            commands::get_continue_watching,
+            commands::get_playlist_neighbors,
             commands::get_app_config,
             commands::open_external,
             commands::get_diagnostics,
+            commands::get_schema_summary,
+            commands::rerun_migration,
+            commands::validate_claim_ids,
             commands::collect_debug_package,
+            commands::get_recent_logs,
+            commands::benchmark_encryption,
+            commands::get_disk_io_stats,
             commands::get_recent_crashes,
             commands::clear_crash_log,
             commands::save_favorite,
             commands::remove_favorite,
+            commands::toggle_favorite,
             commands::get_favorites,
             commands::is_favorite,
+            commands::get_changes_since,
             commands::update_settings,
+            commands::reset_settings_to_defaults,
+            commands::reset_setting,
             commands::invalidate_cache_item,
             commands::invalidate_cache_by_tags,
+            commands::invalidate_channel_cache,
+            commands::get_cache_claim_ids,
+            commands::get_cache_hashes_all,
+            commands::diff_against_remote,
             commands::clear_all_cache,
+            commands::verify_cache_consistency,
             commands::cleanup_expired_cache,
             commands::get_cache_stats,
             commands::get_memory_stats,
             commands::optimize_database_memory,
+            commands::get_storage_recommendations,
+            commands::clear_old_raw_json,
+            commands::remove_orphaned_files,
+            commands::change_encryption_passphrase,
+            commands::get_parse_failures,
+            commands::get_region_blocked_claims,
+            commands::seed_test_data,
+            commands::get_query_timings,
+            commands::get_view_history,
+            commands::clear_view_history,
+            commands::clear_personal_data,
+            commands::export_user_data_json,
+            commands::import_user_data_json,
+            commands::get_command_metrics,
+            commands::get_security_events,
+            commands::get_inflight_requests,
+            commands::clear_inflight_requests,
+            commands::search_content_streaming,
+            commands::search_content_with_snippets,
         ])
-        .setup(|_app| {
-            println!("=== TAURI SETUP HOOK STARTED ===");
+        .setup(|app| {
             tracing::info!("🔍 Tauri setup hook started");
 
-            // TEMPORARY: Skip migrations to isolate the hang
-            tracing::info!("⚠️ TEMPORARY: Skipping migrations for debugging");
-            println!("=== SKIPPING MIGRATIONS (DEBUG) ===");
+            library_events::init(app.handle());
 
-            /*
             // CRITICAL: Single Migration Execution Point
             //
             // Database migrations are executed ONLY here in the setup hook, ensuring they
@@ -272,22 +318,46 @@ async fn main() {
             // this single, well-defined point in the startup sequence.
             //
             // See: .kiro/specs/fix-database-initialization-stack-overflow/ for full details
+            //
+            // `Database::run_startup_migrations` only runs the first time it is
+            // called, so this spawn cannot repeat the migration run even if setup
+            // is entered again.
             let app_handle = app.handle();
             tauri::async_runtime::spawn(async move {
                 if let Err(e) = run_startup_migrations(&app_handle).await {
                     tracing::error!("Failed to run database migrations: {}", e);
                 }
             });
-            */
 
-            println!("=== TAURI SETUP HOOK COMPLETE ===");
+            let app_handle = app.handle();
+            tauri::async_runtime::spawn(async move {
+                flush_cache_periodically(&app_handle).await;
+            });
+
             tracing::info!("✅ Tauri setup hook complete");
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            // Buffered cache hit/miss counts would otherwise be lost on exit.
+            // This callback runs inside the runtime, so nothing here may block
+            // on a future; the flush is a plain synchronous write.
+            if let tauri::RunEvent::Exit = event {
+                let state: State<AppState> = app_handle.state();
+                let flushed = match state.db.try_lock() {
+                    Ok(db) => db.flush_cache_stats_blocking(),
+                    Err(_) => {
+                        tracing::warn!("Database busy on exit, cache stats not flushed");
+                        return;
+                    }
+                };
+                if let Err(e) = flushed {
+                    tracing::warn!("Failed to flush cache stats on exit: {}", e);
+                }
+            }
+        });
 
-    println!("=== TAURI APP RUNNING ===");
 }
 
 async fn initialize_app_state() -> Result<AppState, Box<dyn std::error::Error>> {
@@ -296,16 +366,87 @@ async fn initialize_app_state() -> Result<AppState, Box<dyn std::error::Error>>
     // Initialize database
     tracing::info!("🔍 Initializing database...");
     let db = Database::new().await?;
+    if let Ok(Some(max_items)) = db.get_setting("max_cache_items").await {
+        if let Ok(max_items) = max_items.parse::<u32>() {
+            db.set_max_cache_items(max_items).await?;
+        }
+    }
+    if let Ok(Some(max_bytes)) = db.get_setting("max_cache_bytes").await {
+        if let Ok(max_bytes) = max_bytes.parse::<u64>() {
+            db.set_max_cache_bytes(max_bytes).await?;
+        }
+    }
+    if let Ok(Some(policy)) = db.get_setting("cache_eviction_policy").await {
+        if let Some(policy) = CacheEvictionPolicy::parse(&policy) {
+            db.set_cache_eviction_policy(policy);
+        }
+    }
+    if let Ok(Some(overrides)) = db.get_setting("tag_ttl_overrides").await {
+        match validation::parse_tag_ttl_overrides(&overrides) {
+            Ok(overrides) => db.set_tag_ttl_overrides(overrides),
+            Err(e) => tracing::warn!("Ignoring invalid tag_ttl_overrides setting: {}", e),
+        }
+    }
+    if let Ok(Some(enabled)) = db.get_setting("content_pipeline_tracing").await {
+        commands::set_content_pipeline_tracing(enabled == "true");
+    }
+    if let Ok(Some(format)) = db.get_setting("raw_json_format").await {
+        if let Some(format) = RawJsonFormat::parse(&format) {
+            commands::set_raw_json_format(format);
+        }
+    }
+    if let Ok(Some(policy)) = db.get_setting("thumbnail_url_policy").await {
+        if let Some(policy) = ThumbnailUrlPolicy::parse(&policy) {
+            commands::set_thumbnail_url_policy(policy);
+        }
+    }
+    if let Ok(Some(chars)) = db.get_setting("max_title_length").await {
+        if let Ok(chars) = chars.parse::<u32>() {
+            commands::set_max_title_length(chars);
+        }
+    }
+    if let Ok(Some(chars)) = db.get_setting("max_description_length").await {
+        if let Ok(chars) = chars.parse::<u32>() {
+            commands::set_max_description_length(chars);
+        }
+    }
+    // Pool warming can be turned off, e.g. on slow disks where opening every
+    // connection up front delays the first screen instead of smoothing it
+    let warm_connection_pool = match db.get_setting("warm_connection_pool").await {
+        Ok(Some(enabled)) => enabled == "true",
+        _ => true,
+    };
+    if warm_connection_pool {
+        let pooled = db.warm_pool().await;
+        tracing::info!("🔍 Connection pool warmed with {} connections", pooled);
+    } else {
+        tracing::info!("🔍 Connection pool warming is disabled");
+    }
     tracing::info!("✅ Database initialized");
 
     // Initialize gateway client
     tracing::info!("🔍 Initializing gateway client...");
-    let gateway = GatewayClient::new();
+    let gateways = db.get_setting("gateways").await.ok().flatten();
+    let mut gateway = GatewayClient::with_gateways(gateway::resolve_gateways(gateways.as_deref()));
+    if let Ok(Some(rate)) = db.get_setting("gateway_max_requests_per_second").await {
+        if let Ok(rate) = rate.parse::<u32>() {
+            gateway.set_max_requests_per_second(rate);
+        }
+    }
     tracing::info!("✅ Gateway client initialized");
 
     // Initialize download manager
     tracing::info!("🔍 Initializing download manager...");
-    let download_manager = DownloadManager::new().await?;
+    let mut download_manager = DownloadManager::new().await?;
+    if let Ok(Some(dir)) = db.get_setting("download_temp_dir").await {
+        let applied = match crate::path_security::validate_user_directory(&dir) {
+            Ok(dir) => download_manager.set_temp_dir(Some(dir)).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = applied {
+            tracing::warn!("Ignoring download_temp_dir setting '{}': {}", dir, e);
+        }
+    }
     tracing::info!("✅ Download manager initialized");
 
     // Initialize local server
@@ -315,11 +456,14 @@ async fn initialize_app_state() -> Result<AppState, Box<dyn std::error::Error>>
 
     tracing::info!("🎉 App state initialization complete!");
 
+    let http_client = download_manager.http_client();
+
     Ok(AppState {
         db: Arc::new(Mutex::new(db)),
         gateway: Arc::new(Mutex::new(gateway)),
         download_manager: Arc::new(Mutex::new(download_manager)),
         local_server: Arc::new(Mutex::new(local_server)),
+        http_client,
     })
 }
 
@@ -334,8 +478,8 @@ async fn initialize_app_state() -> Result<AppState, Box<dyn std::error::Error>>
 /// 1. Application starts and calls `initialize_app_state()`
 /// 2. `Database::new()` creates connection pool and base schema (NO migrations)
 /// 3. Tauri setup hook calls this function
-/// 4. This function executes all pending migrations via `db.run_migrations()`
-/// 5. Application is ready for use
+/// 4. This function executes all pending migrations via `db.run_startup_migrations()`
+/// 5. Application is ready for use and the `app-ready` event is emitted
 ///
 /// # Bug Fix Reference
 ///
@@ -344,20 +488,49 @@ async fn initialize_app_state() -> Result<AppState, Box<dyn std::error::Error>>
 /// the application to crash on startup.
 ///
 /// See: .kiro/specs/fix-database-initialization-stack-overflow/ for full details
-#[allow(dead_code)]
 async fn run_startup_migrations(
     app_handle: &tauri::AppHandle,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let state: State<AppState> = app_handle.state();
     let db = state.db.lock().await;
-    db.run_migrations().await?;
+    if db.run_startup_migrations().await? {
+        tracing::info!("✅ Startup migrations complete");
+    }
+    drop(db);
+
+    let _ = app_handle.emit_all("app-ready", ());
+
+    // Favorites keep fresh metadata even after their cache entries expire
+    if let Err(e) = commands::prewarm_favorites(&state).await {
+        tracing::warn!("Failed to prewarm favorites: {}", e);
+    }
     Ok(())
 }
 
+/// Writes buffered cache hit and miss counts, and retries cache writes that
+/// failed, every `CACHE_STATS_FLUSH_INTERVAL_SECS` for as long as the app runs
+async fn flush_cache_periodically(app_handle: &tauri::AppHandle) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+        database::CACHE_STATS_FLUSH_INTERVAL_SECS,
+    ));
+    // The first tick completes immediately and there is nothing to flush yet
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let state: State<AppState> = app_handle.state();
+        let db = state.db.lock().await;
+        if let Err(e) = db.flush_cache_stats().await {
+            tracing::warn!("Failed to flush cache stats: {}", e);
+        }
+        if let Err(e) = db.retry_pending_stores().await {
+            tracing::warn!("Failed to retry pending cache writes: {}", e);
+        }
+    }
+}
+
 /// Checks for emergency disable flag from update manifest
 /// This function runs before all other startup logic to ensure
 /// the application can be remotely disabled if necessary
-#[allow(dead_code)]
 async fn check_emergency_disable() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("🔍 check_emergency_disable: Reading environment variable...");
 
@@ -434,7 +607,6 @@ async fn check_emergency_disable() -> Result<(), Box<dyn std::error::Error>> {
 
 /// Shows emergency disable message and provides exit option
 /// This is a blocking operation that prevents normal application startup
-#[allow(dead_code)]
 fn show_emergency_disable_message() {
     // In a real implementation, this would show a native dialog
     // For now, we log the message and exit
@@ -0,0 +1,376 @@
+//! # MP4 Media Probe
+//!
+//! Reads duration, resolution and codecs from the `moov` box of a downloaded
+//! MP4 so offline items show what was actually downloaded rather than the
+//! claim's advertised values.
+//!
+//! Only the container header is parsed; no samples are decoded and no
+//! external tools are needed. The `moov` box may sit before or after the
+//! media data, so top-level boxes are skipped by seeking rather than read.
+
+use crate::error::{KiyyaError, Result};
+use crate::models::MediaProbe;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Largest `moov` box that is read into memory
+const MAX_MOOV_BYTES: u64 = 16 * 1024 * 1024;
+
+type BoxType = [u8; 4];
+
+/// Probes an MP4 file's container header
+pub fn probe_file(path: &Path) -> Result<MediaProbe> {
+    let mut file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    let moov = read_top_level_box(&mut file, file_size, b"moov")?;
+
+    let mut probe = parse_moov(&moov)?;
+    probe.bitrate_bps = probe
+        .duration_seconds
+        .filter(|seconds| *seconds > 0.0)
+        .map(|seconds| (file_size as f64 * 8.0 / seconds) as u64);
+    Ok(probe)
+}
+
+fn parsing_error(message: impl Into<String>) -> KiyyaError {
+    KiyyaError::ContentParsing {
+        message: message.into(),
+    }
+}
+
+/// Finds a top-level box by seeking over the others and returns its payload
+fn read_top_level_box(file: &mut File, file_size: u64, wanted: &BoxType) -> Result<Vec<u8>> {
+    let mut offset = 0u64;
+
+    while file_size - offset >= 8 {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut header = [0u8; 8];
+        file.read_exact(&mut header)?;
+
+        let size32 = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let box_type: BoxType = [header[4], header[5], header[6], header[7]];
+        let (box_size, header_len) = match size32 {
+            0 => (file_size - offset, 8),
+            1 => {
+                let mut large = [0u8; 8];
+                file.read_exact(&mut large)?;
+                (u64::from_be_bytes(large), 16)
+            }
+            size => (u64::from(size), 8),
+        };
+        // The size comes from the file, so it must neither wrap the offset
+        // nor leave it where it was
+        let box_end = match offset.checked_add(box_size) {
+            Some(end) if box_size >= header_len && end <= file_size => end,
+            _ => {
+                return Err(parsing_error(format!(
+                    "Invalid MP4 box size {} at offset {}",
+                    box_size, offset
+                )))
+            }
+        };
+
+        if &box_type == wanted {
+            let payload_len = box_size - header_len;
+            if payload_len > MAX_MOOV_BYTES {
+                return Err(parsing_error(format!(
+                    "MP4 {} box is too large to probe ({} bytes)",
+                    String::from_utf8_lossy(wanted),
+                    payload_len
+                )));
+            }
+            let mut payload = vec![0u8; payload_len as usize];
+            file.read_exact(&mut payload)?;
+            return Ok(payload);
+        }
+
+        offset = box_end;
+    }
+
+    Err(parsing_error(format!(
+        "No {} box found in MP4 file",
+        String::from_utf8_lossy(wanted)
+    )))
+}
+
+/// Splits a box payload into its child boxes
+fn child_boxes(data: &[u8]) -> Result<Vec<(BoxType, &[u8])>> {
+    let mut children = Vec::new();
+    let mut offset = 0usize;
+
+    while offset + 8 <= data.len() {
+        let size32 = read_u32(data, offset).unwrap_or(0);
+        let box_type: BoxType = [
+            data[offset + 4],
+            data[offset + 5],
+            data[offset + 6],
+            data[offset + 7],
+        ];
+        let (box_size, header_len) = match size32 {
+            0 => (data.len() - offset, 8),
+            1 => {
+                let large = read_u64(data, offset + 8)
+                    .ok_or_else(|| parsing_error("Truncated MP4 box header"))?;
+                (usize::try_from(large).unwrap_or(usize::MAX), 16)
+            }
+            size => (size as usize, 8),
+        };
+        if box_size < header_len || box_size > data.len() - offset {
+            return Err(parsing_error(format!(
+                "Invalid MP4 child box size {} at offset {}",
+                box_size, offset
+            )));
+        }
+
+        children.push((box_type, &data[offset + header_len..offset + box_size]));
+        offset += box_size;
+    }
+
+    Ok(children)
+}
+
+fn find_child<'a>(data: &'a [u8], wanted: &BoxType) -> Result<Option<&'a [u8]>> {
+    Ok(child_boxes(data)?
+        .into_iter()
+        .find(|(box_type, _)| box_type == wanted)
+        .map(|(_, body)| body))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset + 8).map(|b| {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(b);
+        u64::from_be_bytes(bytes)
+    })
+}
+
+/// Track details pulled from a `trak` box
+#[derive(Debug, Default)]
+struct TrackInfo {
+    handler: Option<BoxType>,
+    codec: Option<BoxType>,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+fn parse_moov(moov: &[u8]) -> Result<MediaProbe> {
+    let mut probe = MediaProbe::default();
+
+    for (box_type, body) in child_boxes(moov)? {
+        match &box_type {
+            b"mvhd" => probe.duration_seconds = parse_mvhd(body),
+            b"trak" => {
+                let track = parse_trak(body)?;
+                match track.handler.as_ref() {
+                    Some(b"vide") if probe.video_codec.is_none() => {
+                        probe.video_codec = track.codec.map(codec_name);
+                        probe.width = track.width;
+                        probe.height = track.height;
+                    }
+                    Some(b"soun") if probe.audio_codec.is_none() => {
+                        probe.audio_codec = track.codec.map(codec_name);
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(probe)
+}
+
+/// Duration in seconds from a movie header (version 0 or 1)
+fn parse_mvhd(body: &[u8]) -> Option<f64> {
+    let (timescale, duration) = match body.first()? {
+        1 => (read_u32(body, 20)?, read_u64(body, 24)?),
+        _ => (read_u32(body, 12)?, u64::from(read_u32(body, 16)?)),
+    };
+    if timescale == 0 || duration == u64::MAX || duration == u64::from(u32::MAX) {
+        return None;
+    }
+    Some(duration as f64 / f64::from(timescale))
+}
+
+fn parse_trak(trak: &[u8]) -> Result<TrackInfo> {
+    let mut track = TrackInfo::default();
+
+    if let Some(tkhd) = find_child(trak, b"tkhd")? {
+        // Width and height are 16.16 fixed point at the end of the header
+        let dims_offset = if tkhd.first() == Some(&1) { 88 } else { 76 };
+        track.width = read_u32(tkhd, dims_offset)
+            .map(|w| w >> 16)
+            .filter(|w| *w > 0);
+        track.height = read_u32(tkhd, dims_offset + 4)
+            .map(|h| h >> 16)
+            .filter(|h| *h > 0);
+    }
+
+    if let Some(mdia) = find_child(trak, b"mdia")? {
+        if let Some(hdlr) = find_child(mdia, b"hdlr")? {
+            track.handler = hdlr.get(8..12).map(|t| [t[0], t[1], t[2], t[3]]);
+        }
+
+        let stsd = match find_child(mdia, b"minf")? {
+            Some(minf) => match find_child(minf, b"stbl")? {
+                Some(stbl) => find_child(stbl, b"stsd")?,
+                None => None,
+            },
+            None => None,
+        };
+        if let Some(stsd) = stsd {
+            // Full box header and entry count, then the first sample entry
+            track.codec = stsd.get(12..16).map(|t| [t[0], t[1], t[2], t[3]]);
+            if track.width.is_none() {
+                track.width = read_u16(stsd, 40).map(u32::from).filter(|w| *w > 0);
+                track.height = read_u16(stsd, 42).map(u32::from).filter(|h| *h > 0);
+            }
+        }
+    }
+
+    Ok(track)
+}
+
+/// Maps a sample entry type to a readable codec name
+fn codec_name(fourcc: BoxType) -> String {
+    match &fourcc {
+        b"avc1" | b"avc3" => "h264".to_string(),
+        b"hvc1" | b"hev1" => "hevc".to_string(),
+        b"av01" => "av1".to_string(),
+        b"vp09" => "vp9".to_string(),
+        b"mp4a" => "aac".to_string(),
+        b"Opus" => "opus".to_string(),
+        b"ac-3" => "ac3".to_string(),
+        b"ec-3" => "eac3".to_string(),
+        other => String::from_utf8_lossy(other).trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn mp4_box(box_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(box_type);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn mvhd(timescale: u32, duration: u32) -> Vec<u8> {
+        let mut payload = vec![0u8; 100];
+        payload[12..16].copy_from_slice(&timescale.to_be_bytes());
+        payload[16..20].copy_from_slice(&duration.to_be_bytes());
+        mp4_box(b"mvhd", &payload)
+    }
+
+    fn trak(handler: &[u8; 4], codec: &[u8; 4], width: u32, height: u32) -> Vec<u8> {
+        let mut tkhd = vec![0u8; 84];
+        tkhd[76..80].copy_from_slice(&(width << 16).to_be_bytes());
+        tkhd[80..84].copy_from_slice(&(height << 16).to_be_bytes());
+
+        let mut hdlr = vec![0u8; 24];
+        hdlr[8..12].copy_from_slice(handler);
+
+        let mut stsd = vec![0u8; 8];
+        stsd[7] = 1; // entry count
+        stsd.extend(mp4_box(codec, &[0u8; 78]));
+
+        let stbl = mp4_box(b"stbl", &mp4_box(b"stsd", &stsd));
+        let minf = mp4_box(b"minf", &stbl);
+        let mdia = mp4_box(b"mdia", &[mp4_box(b"hdlr", &hdlr), minf].concat());
+        mp4_box(b"trak", &[mp4_box(b"tkhd", &tkhd), mdia].concat())
+    }
+
+    /// A minimal MP4: 90s of 1280x720 H.264 with AAC audio
+    ///
+    /// `moov_last` puts the header after the media data, as non-faststart
+    /// encoders do.
+    pub(crate) fn sample_mp4(moov_last: bool) -> Vec<u8> {
+        let ftyp = mp4_box(b"ftyp", b"isom\0\0\x02\0isomavc1");
+        let mdat = mp4_box(b"mdat", &vec![0u8; 4096]);
+        let moov = mp4_box(
+            b"moov",
+            &[
+                mvhd(1000, 90_000),
+                trak(b"vide", b"avc1", 1280, 720),
+                trak(b"soun", b"mp4a", 0, 0),
+            ]
+            .concat(),
+        );
+
+        if moov_last {
+            [ftyp, mdat, moov].concat()
+        } else {
+            [ftyp, moov, mdat].concat()
+        }
+    }
+
+    #[test]
+    fn test_probe_reads_moov_before_and_after_media() {
+        let temp_dir = TempDir::new().unwrap();
+
+        for moov_last in [false, true] {
+            let path = temp_dir.path().join(format!("sample-{}.mp4", moov_last));
+            let data = sample_mp4(moov_last);
+            std::fs::write(&path, &data).unwrap();
+
+            let probe = probe_file(&path).unwrap();
+            assert_eq!(probe.duration_seconds, Some(90.0));
+            assert_eq!(probe.width, Some(1280));
+            assert_eq!(probe.height, Some(720));
+            assert_eq!(probe.video_codec.as_deref(), Some("h264"));
+            assert_eq!(probe.audio_codec.as_deref(), Some("aac"));
+            assert_eq!(probe.bitrate_bps, Some(data.len() as u64 * 8 / 90));
+        }
+    }
+
+    #[test]
+    fn test_probe_rejects_non_mp4_files() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let text = temp_dir.path().join("notes.mp4");
+        std::fs::write(&text, b"definitely not an mp4 file").unwrap();
+        assert!(probe_file(&text).is_err());
+
+        let no_moov = temp_dir.path().join("no-moov.mp4");
+        std::fs::write(&no_moov, mp4_box(b"ftyp", b"isom")).unwrap();
+        assert!(probe_file(&no_moov).is_err());
+    }
+
+    #[test]
+    fn test_probe_rejects_overflowing_largesize() {
+        let temp_dir = TempDir::new().unwrap();
+        let ftyp = mp4_box(b"ftyp", b"isom");
+
+        // Sizes that wrap the offset back to the start of the file or just
+        // before the box itself
+        let wrapping_sizes = [u64::MAX - ftyp.len() as u64 + 1, u64::MAX];
+        for (i, largesize) in wrapping_sizes.into_iter().enumerate() {
+            let mut data = ftyp.clone();
+            data.extend_from_slice(&1u32.to_be_bytes());
+            data.extend_from_slice(b"free");
+            data.extend_from_slice(&largesize.to_be_bytes());
+            data.extend_from_slice(&sample_mp4(false));
+
+            let path = temp_dir.path().join(format!("overflow-{}.mp4", i));
+            std::fs::write(&path, &data).unwrap();
+            assert!(matches!(
+                probe_file(&path),
+                Err(KiyyaError::ContentParsing { .. })
+            ));
+        }
+    }
+}
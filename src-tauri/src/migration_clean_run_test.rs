@@ -593,4 +593,155 @@ mod migration_clean_run_tests {
 
         println!("✓ All tables have proper schema");
     }
+
+    #[tokio::test]
+    async fn test_schema_summary_reports_checksum_mismatch() {
+        let (db, _temp_dir, db_path) = create_test_db().await;
+        db.run_migrations()
+            .await
+            .expect("Migrations should run successfully");
+
+        let summary = db
+            .get_schema_summary()
+            .await
+            .expect("Failed to get schema summary");
+        let migration_count = get_migration_count(&db_path).expect("Failed to get migration count");
+        assert_eq!(summary.migrations_applied, migration_count);
+        assert!(summary.current_version > 0);
+        assert!(summary.latest_applied_at.is_some());
+        assert!(!summary.has_unvalidated, "Fresh migrations should validate");
+
+        let conn = Connection::open(&db_path).expect("Failed to open database");
+        conn.execute(
+            "UPDATE migrations SET checksum = 'tampered' WHERE version = 1",
+            [],
+        )
+        .expect("Failed to tamper with checksum");
+
+        let summary = db
+            .get_schema_summary()
+            .await
+            .expect("Failed to get schema summary");
+        assert!(summary.has_unvalidated);
+        assert_eq!(summary.unvalidated_versions, vec![1]);
+
+        // A NULL checksum predates checksum recording and is accepted as
+        // legacy, the same as validate_migrations treats it
+        conn.execute(
+            "UPDATE migrations SET checksum = NULL WHERE version = 1",
+            [],
+        )
+        .expect("Failed to clear checksum");
+
+        let summary = db
+            .get_schema_summary()
+            .await
+            .expect("Failed to get schema summary");
+        assert!(!summary.has_unvalidated);
+        assert!(summary.unvalidated_versions.is_empty());
+        db.validate_migrations()
+            .await
+            .expect("Legacy checksums should validate");
+    }
+
+    #[tokio::test]
+    async fn test_startup_migrations_run_once_and_reach_ready() {
+        let (db, _temp_dir, db_path) = create_test_db().await;
+
+        // Two startup paths racing for the migration run
+        let (first, second) =
+            tokio::join!(db.run_startup_migrations(), db.run_startup_migrations());
+        let ran: Vec<bool> = vec![
+            first.expect("Startup migrations should succeed"),
+            second.expect("Startup migrations should succeed"),
+        ];
+        assert_eq!(
+            ran.iter().filter(|ran| **ran).count(),
+            1,
+            "Exactly one caller should run the migrations"
+        );
+
+        let count_after_startup =
+            get_migration_count(&db_path).expect("Failed to get migration count");
+
+        // A later call is a no-op
+        assert!(!db
+            .run_startup_migrations()
+            .await
+            .expect("Repeat startup call should succeed"));
+        assert_eq!(
+            get_migration_count(&db_path).expect("Failed to get migration count"),
+            count_after_startup
+        );
+
+        // Schema is at the latest version and the database serves requests
+        let conn = Connection::open(&db_path).expect("Failed to open database");
+        let latest = crate::migrations::get_migrations()
+            .iter()
+            .map(|(version, _)| *version)
+            .max()
+            .expect("At least one migration should be defined");
+        assert_eq!(
+            MigrationRunner::new()
+                .get_current_version(&conn)
+                .expect("Failed to get current version"),
+            latest
+        );
+        let favorites = db.get_favorites().await.expect("Database should be ready");
+        assert!(favorites.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_rerun_restores_pre_rerun_state() {
+        let (mut db, _temp_dir, db_path) = create_test_db().await;
+        db.run_migrations().await.expect("Migrations should run");
+
+        db.save_favorite(crate::models::FavoriteItem {
+            claim_id: "rerun-marker".to_string(),
+            title: "Rerun Marker".to_string(),
+            thumbnail_url: None,
+            inserted_at: 1,
+        })
+        .await
+        .expect("Failed to save favorite");
+
+        // Migration 3 indexes offline_meta, so rerunning it now fails
+        {
+            let conn = Connection::open(&db_path).expect("Failed to open database");
+            conn.execute("DROP TABLE offline_meta", [])
+                .expect("Failed to drop offline_meta");
+        }
+        let count_before = get_migration_count(&db_path).expect("Failed to get migration count");
+
+        assert!(db.rerun_migration(3).await.is_err());
+
+        let conn = Connection::open(&db_path).expect("Failed to open database");
+        assert!(
+            MigrationRunner::new()
+                .is_migration_applied(&conn, 3)
+                .expect("Failed to check migration 3"),
+            "Migration 3 record should be restored"
+        );
+        assert_eq!(
+            get_migration_count(&db_path).expect("Failed to get migration count"),
+            count_before
+        );
+        assert!(db
+            .is_favorite("rerun-marker")
+            .await
+            .expect("Failed to check favorite"));
+        assert!(!table_exists(&db_path, "offline_meta").expect("Failed to check offline_meta"));
+    }
+
+    #[tokio::test]
+    async fn test_rerun_rejects_unknown_version() {
+        let (mut db, _temp_dir, db_path) = create_test_db().await;
+        db.run_migrations().await.expect("Migrations should run");
+
+        assert!(db.rerun_migration(9999).await.is_err());
+        assert!(
+            !db_path.with_extension("db-rerun-backup").exists(),
+            "No backup should be taken for an unknown version"
+        );
+    }
 }
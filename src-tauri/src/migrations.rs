@@ -6,6 +6,7 @@
 #![allow(dead_code)]
 
 use crate::error::{ErrorContext, KiyyaError, Result};
+use crate::models::SchemaSummary;
 use chrono::Utc;
 use rusqlite::{params, Connection, Transaction};
 use tracing::{debug, error, info, warn};
@@ -199,6 +200,30 @@ impl MigrationRunner {
         }
     }
 
+    /// Deletes the record for `version` and executes that migration again
+    ///
+    /// The delete is not part of the migration's transaction, so a failed
+    /// rerun leaves the migration unrecorded. `Database::rerun_migration`
+    /// wraps this with a backup for that reason.
+    pub fn rerun_migration(&self, conn: &Connection, version: u32) -> Result<()> {
+        let migration = self
+            .migrations
+            .iter()
+            .find(|m| m.version == version)
+            .ok_or_else(|| KiyyaError::InvalidInput {
+                message: format!("Unknown migration version {}", version),
+            })?;
+
+        self.ensure_migrations_table(conn)?;
+        conn.execute(
+            "DELETE FROM migrations WHERE version = ?1",
+            params![version],
+        )
+        .with_context_fn(|| format!("Failed to delete record of migration {}", version))?;
+
+        self.execute_migration(conn, migration)
+    }
+
     /// Executes the SQL for a migration
     fn execute_migration_sql(&self, tx: &Transaction, migration: &Migration) -> Result<()> {
         // Split SQL into individual statements and execute them
@@ -240,6 +265,27 @@ impl MigrationRunner {
         format!("{:x}", hasher.finish())
     }
 
+    /// Compares an applied migration's stored checksum with this build
+    ///
+    /// A NULL checksum comes from a database migrated before checksums were
+    /// recorded and is accepted as legacy. `validate_migrations` and
+    /// `get_schema_summary` both go through here so they agree.
+    fn check_applied_checksum(&self, version: u32, stored: Option<&str>) -> AppliedChecksum {
+        let migration = match self.migrations.iter().find(|m| m.version == version) {
+            Some(migration) => migration,
+            None => return AppliedChecksum::NotInBuild,
+        };
+        let current = self.calculate_checksum(migration.sql);
+        match stored {
+            None => AppliedChecksum::Legacy,
+            Some(stored) if stored == current => AppliedChecksum::Matches,
+            Some(stored) => AppliedChecksum::Mismatch {
+                stored: stored.to_string(),
+                current,
+            },
+        }
+    }
+
     /// Validates that all applied migrations match their checksums
     pub fn validate_migrations(&self, conn: &Connection) -> Result<()> {
         let mut stmt = conn
@@ -260,29 +306,25 @@ impl MigrationRunner {
             let (version, description, stored_checksum) =
                 applied.with_context("Failed to parse applied migration")?;
 
-            if let Some(migration) = self.migrations.iter().find(|m| m.version == version) {
-                let current_checksum = self.calculate_checksum(migration.sql);
-
-                if let Some(stored) = stored_checksum {
-                    if stored != current_checksum {
-                        warn!(
-                            "Migration {} checksum mismatch. Stored: {}, Current: {}",
-                            version, stored, current_checksum
-                        );
-                    }
-                }
+            match self.check_applied_checksum(version, stored_checksum.as_deref()) {
+                AppliedChecksum::Mismatch { stored, current } => warn!(
+                    "Migration {} checksum mismatch. Stored: {}, Current: {}",
+                    version, stored, current
+                ),
+                AppliedChecksum::NotInBuild => warn!(
+                    "Applied migration {} not found in current migration set",
+                    version
+                ),
+                AppliedChecksum::Matches | AppliedChecksum::Legacy => {}
+            }
 
+            if let Some(migration) = self.migrations.iter().find(|m| m.version == version) {
                 if migration.description != description {
                     warn!(
                         "Migration {} description changed. Stored: '{}', Current: '{}'",
                         version, description, migration.description
                     );
                 }
-            } else {
-                warn!(
-                    "Applied migration {} not found in current migration set",
-                    version
-                );
             }
         }
 
@@ -323,6 +365,32 @@ impl MigrationRunner {
             .with_context("Failed to parse migration history rows")
     }
 
+    /// Summarizes the migration history: current version, count, when the
+    /// latest ran, and which applied migrations fail checksum validation
+    pub fn get_schema_summary(&self, conn: &Connection) -> Result<SchemaSummary> {
+        let history = self.get_migration_history(conn)?;
+
+        let unvalidated_versions: Vec<u32> = history
+            .iter()
+            .filter(|applied| {
+                matches!(
+                    self.check_applied_checksum(applied.version, applied.checksum.as_deref()),
+                    AppliedChecksum::Mismatch { .. } | AppliedChecksum::NotInBuild
+                )
+            })
+            .map(|applied| applied.version)
+            .collect();
+        let latest = history.iter().max_by_key(|applied| applied.version);
+
+        Ok(SchemaSummary {
+            current_version: latest.map(|applied| applied.version).unwrap_or(0),
+            migrations_applied: history.len() as u32,
+            latest_applied_at: latest.map(|applied| applied.applied_at),
+            has_unvalidated: !unvalidated_versions.is_empty(),
+            unvalidated_versions,
+        })
+    }
+
     /// Performs a dry-run of pending migrations without executing them
     /// 
     /// This validates SQL syntax and returns a plan of what would be executed.
@@ -496,6 +564,20 @@ pub enum ValidationResult {
     Invalid { error: String },
 }
 
+/// How an applied migration's stored checksum compares with this build
+#[derive(Debug, Clone, PartialEq)]
+enum AppliedChecksum {
+    Matches,
+    /// No checksum was recorded, as for databases migrated before checksums
+    Legacy,
+    Mismatch {
+        stored: String,
+        current: String,
+    },
+    /// The migration is not part of this build
+    NotInBuild,
+}
+
 /// Returns all available migrations in order
 pub fn get_all_migrations() -> Vec<Migration> {
     vec![
@@ -759,6 +841,112 @@ pub fn get_all_migrations() -> Vec<Migration> {
                 CREATE INDEX IF NOT EXISTS idx_localcache_release_time ON local_cache(releaseTime DESC)
             "#,
         },
+        Migration {
+            version: 15,
+            description: "Add normalized content tag index".to_string(),
+            sql: r#"
+                -- Sync triggers are created by Database::initialize()
+                CREATE TABLE IF NOT EXISTS content_tags (
+                    claimId TEXT NOT NULL,
+                    tag TEXT NOT NULL,
+                    PRIMARY KEY (claimId, tag)
+                );
+
+                CREATE INDEX IF NOT EXISTS idx_content_tags_tag ON content_tags(tag);
+
+                INSERT OR IGNORE INTO content_tags (claimId, tag)
+                SELECT local_cache.claimId, json_each.value
+                FROM local_cache, json_each(local_cache.tags)
+                WHERE json_valid(local_cache.tags)
+            "#,
+        },
+        Migration {
+            version: 16,
+            description: "Parse failure tracking".to_string(),
+            sql: r#"
+                CREATE TABLE IF NOT EXISTS parse_failures (
+                    claimId TEXT PRIMARY KEY,
+                    reason TEXT NOT NULL,
+                    failureCount INTEGER NOT NULL DEFAULT 1,
+                    firstSeen INTEGER NOT NULL,
+                    lastSeen INTEGER NOT NULL
+                );
+
+                CREATE INDEX IF NOT EXISTS idx_parse_failures_lastSeen ON parse_failures(lastSeen DESC)
+            "#,
+        },
+        Migration {
+            version: 17,
+            description: "View history".to_string(),
+            sql: r#"
+                CREATE TABLE IF NOT EXISTS view_history (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    claimId TEXT NOT NULL,
+                    viewedAt INTEGER NOT NULL
+                );
+
+                CREATE INDEX IF NOT EXISTS idx_view_history_claim_viewedAt ON view_history(claimId, viewedAt DESC);
+                CREATE INDEX IF NOT EXISTS idx_view_history_viewedAt ON view_history(viewedAt DESC)
+            "#,
+        },
+        Migration {
+            version: 18,
+            description: "Add offline media probe columns".to_string(),
+            sql: r#"
+                -- probe* columns on offline_meta are added by initialize()
+                SELECT 1
+            "#,
+        },
+        Migration {
+            version: 19,
+            description: "Link cached items to their channel".to_string(),
+            sql: r#"
+                -- local_cache.channelId and its index are added by initialize()
+                SELECT 1
+            "#,
+        },
+        Migration {
+            version: 20,
+            description: "Region block tracking".to_string(),
+            sql: r#"
+                CREATE TABLE IF NOT EXISTS region_blocks (
+                    claimId TEXT PRIMARY KEY,
+                    status INTEGER NOT NULL,
+                    blockCount INTEGER NOT NULL DEFAULT 1,
+                    firstSeen INTEGER NOT NULL,
+                    lastSeen INTEGER NOT NULL
+                );
+
+                CREATE INDEX IF NOT EXISTS idx_region_blocks_lastSeen ON region_blocks(lastSeen DESC)
+            "#,
+        },
+        Migration {
+            version: 21,
+            description: "Sync tombstones for favorites and progress".to_string(),
+            sql: r#"
+                -- favorites.deletedAt and progress.deletedAt are added by initialize()
+                SELECT 1
+            "#,
+        },
+        Migration {
+            version: 22,
+            description: "Per-item quality preferences".to_string(),
+            sql: r#"
+                CREATE TABLE IF NOT EXISTS content_prefs (
+                    claimId TEXT PRIMARY KEY,
+                    preferredQuality TEXT NOT NULL,
+                    updatedAt INTEGER NOT NULL
+                )
+            "#,
+        },
+        Migration {
+            version: 23,
+            description: "Sync change sequence for favorites and progress".to_string(),
+            sql: r#"
+                -- changeSeq, changedAt and the sync_sequence triggers are added by initialize()
+                SELECT 1
+            "#,
+        },
     ]
 }
 
@@ -23,6 +23,9 @@ pub struct ContentItem {
     /// Raw JSON response from API for debugging purposes
     #[serde(skip_serializing_if = "Option::is_none")]
     pub raw_json: Option<String>,
+    /// True when served from expired cache because the gateway was unreachable
+    #[serde(default)]
+    pub stale: bool,
 }
 
 impl ContentItem {
@@ -59,6 +62,7 @@ impl ContentItem {
             etag: None,
             content_hash: None,
             raw_json: None,
+            stale: false,
         })
     }
 
@@ -150,6 +154,51 @@ impl ContentItem {
     }
 }
 
+/// Where the items of a channel fetch came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentSource {
+    Cache,
+    Network,
+}
+
+/// Channel fetch result with enough freshness data for a "last updated" label
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelClaimsResult {
+    pub items: Vec<ContentItem>,
+    pub source: ContentSource,
+    /// When the oldest returned item was fetched from the network
+    pub fetched_at: i64,
+    /// True when any item is past the cache TTL (served after a gateway failure)
+    pub stale: bool,
+    pub page_info: PageInfo,
+}
+
+/// Pagination state of a channel fetch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageInfo {
+    pub page: u32,
+    pub page_size: u32,
+    /// Only known when the gateway reports it
+    pub total_items: Option<u32>,
+    /// Only known when the gateway reports it
+    pub total_pages: Option<u32>,
+    pub has_more: bool,
+}
+
+impl PageInfo {
+    /// Page info without totals, assuming more pages follow a full one
+    pub fn from_page_fill(page: u32, page_size: u32, returned: usize) -> Self {
+        Self {
+            page,
+            page_size,
+            total_items: None,
+            total_pages: None,
+            has_more: page_size > 0 && returned >= page_size as usize,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VideoUrl {
     pub url: String,
@@ -288,9 +337,30 @@ impl Playlist {
     pub fn is_season(&self) -> bool {
         self.season_number.is_some()
     }
+
+    /// Items before and after `claim_id` in position order
+    ///
+    /// Returns `None` if `claim_id` is not in this playlist.
+    pub fn neighbors(&self, claim_id: &str) -> Option<PlaylistNeighbors> {
+        let mut items: Vec<&PlaylistItem> = self.items.iter().collect();
+        items.sort_by_key(|item| item.position);
+
+        let index = items.iter().position(|item| item.claim_id == claim_id)?;
+        Some(PlaylistNeighbors {
+            prev: index.checked_sub(1).map(|prev| items[prev].clone()),
+            next: items.get(index + 1).map(|next| (*next).clone()),
+        })
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The items around one entry of a playlist, for next/previous controls
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlaylistNeighbors {
+    pub prev: Option<PlaylistItem>,
+    pub next: Option<PlaylistItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PlaylistItem {
     pub claim_id: String,
     pub position: u32,
@@ -332,6 +402,28 @@ pub struct ProgressData {
     pub updated_at: i64,
 }
 
+/// Share of a video that must be watched before it counts as finished
+pub const DEFAULT_FINISHED_THRESHOLD_PERCENT: u32 = 95;
+
+/// Whether playback at `position_seconds` has finished a video
+///
+/// Videos with an unknown (or zero) duration are never finished. Positions are
+/// saved in whole seconds, so reaching the final second always counts; this
+/// keeps very short clips from being stuck just under the threshold.
+pub fn is_finished(
+    position_seconds: u32,
+    duration_seconds: Option<u32>,
+    threshold_percent: u32,
+) -> bool {
+    let duration = match duration_seconds {
+        Some(duration) if duration > 0 => duration,
+        _ => return false,
+    };
+
+    u64::from(position_seconds) * 100 >= u64::from(duration) * u64::from(threshold_percent)
+        || duration.saturating_sub(position_seconds) <= 1
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FavoriteItem {
     pub claim_id: String,
@@ -348,19 +440,134 @@ pub struct OfflineMetadata {
     pub file_size: u64,
     pub encrypted: bool,
     pub added_at: i64,
+    /// What the downloaded file actually contains, when it could be probed
+    #[serde(default)]
+    pub probe: Option<MediaProbe>,
+    /// Title from the content cache; only filled in by `list_offline`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// Column `list_offline` sorts by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OfflineSortKey {
+    Date,
+    Size,
+    Title,
+}
+
+impl OfflineSortKey {
+    /// Column name accepted by `sanitization::sanitize_order_by`
+    pub fn column(self) -> &'static str {
+        match self {
+            OfflineSortKey::Date => "addedAt",
+            OfflineSortKey::Size => "fileSize",
+            OfflineSortKey::Title => "titleLower",
+        }
+    }
+}
+
+/// Ordering for `list_offline`; defaults to newest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OfflineSort {
+    pub by: OfflineSortKey,
+    #[serde(default)]
+    pub ascending: bool,
+}
+
+impl Default for OfflineSort {
+    fn default() -> Self {
+        Self {
+            by: OfflineSortKey::Date,
+            ascending: false,
+        }
+    }
+}
+
+/// Filters for `list_offline`; the default matches everything
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OfflineFilter {
+    #[serde(default)]
+    pub encrypted_only: bool,
+    #[serde(default)]
+    pub quality: Option<String>,
+}
+
+/// Container-level details read from a downloaded file's MP4 header
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MediaProbe {
+    pub duration_seconds: Option<f64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    /// Average over the whole file, including audio and container overhead
+    pub bitrate_bps: Option<u64>,
+}
+
+/// Quality picked for a download by `resolve_best_quality`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QualitySelection {
+    pub claim_id: String,
+    pub quality: String,
+    pub url: String,
+    /// False when the preferred quality was unavailable and a fallback was picked
+    pub preferred_available: bool,
+}
+
+/// Total watchable runtime of the offline library, counting each claim once
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OfflineDuration {
+    pub total_seconds: u64,
+    /// Claims whose runtime is included in the total
+    pub counted_claims: u32,
+    /// Claims left out because their runtime is unknown
+    pub uncounted_claims: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub theme: String,
     pub last_used_quality: String,
+    pub preferred_download_quality: String,
     pub encrypt_downloads: bool,
     pub auto_upgrade_quality: bool,
     pub cache_ttl_minutes: u32,
+    pub tag_ttl_overrides: HashMap<String, u32>,
     pub max_cache_items: u32,
+    pub max_cache_bytes: u64,
+    pub allow_stale_on_error: bool,
+    pub min_cache_results: u32,
+    pub prebuffer_seconds: u32,
+    pub proxy_thumbnails: bool,
+    pub cache_eviction_policy: CacheEvictionPolicy,
+    pub finished_threshold_percent: u32,
+    pub content_pipeline_tracing: bool,
+    pub prewarm_favorites: bool,
+    pub warm_connection_pool: bool,
+    pub raw_json_format: RawJsonFormat,
+    pub thumbnail_url_policy: ThumbnailUrlPolicy,
+    pub max_title_length: u32,
+    pub max_description_length: u32,
+    pub gateway_max_requests_per_second: u32,
+    pub allow_plaintext_fallback: bool,
+    pub download_temp_dir: String,
     pub vault_path: String,
     pub version: String,
     pub gateways: Vec<String>,
+    /// Live disk usage shown next to the vault path; not a setting
+    #[serde(default)]
+    pub storage: StorageInfo,
+}
+
+/// Disk usage reported with the app config; `None` means it could not be
+/// measured, e.g. because the vault volume is unavailable
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageInfo {
+    pub vault_usage_bytes: Option<u64>,
+    pub free_disk_bytes: Option<u64>,
+    pub database_size_bytes: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -370,10 +577,123 @@ pub struct DiagnosticsData {
     pub free_disk_bytes: u64,
     pub local_server_status: ServerStatus,
     pub last_manifest_fetch: Option<i64>,
+    pub cdn_gateway: CdnGatewayInfo,
     pub cache_stats: CacheStats,
+    #[serde(default)]
+    pub cache_age_histogram: Vec<(AgeBucket, u32)>,
     pub download_stats: DownloadStats,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error_stats: Option<crate::error_logging::ErrorStats>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_consistency: Option<ConsistencyReport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_summary: Option<SchemaSummary>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<RuntimeStats>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offline_duration: Option<OfflineDuration>,
+}
+
+/// Snapshot of the Tokio runtime's scheduler, for diagnosing hangs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuntimeStats {
+    pub workers: usize,
+    /// Tasks spawned and not yet finished (approximate)
+    pub alive_tasks: usize,
+    /// Tasks waiting in the shared queue for a free worker
+    pub global_queue_depth: usize,
+    /// Only measured in builds with `--cfg tokio_unstable`
+    #[serde(default)]
+    pub blocking_pool: Option<BlockingPoolStats>,
+}
+
+/// State of the `spawn_blocking` thread pool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockingPoolStats {
+    pub threads: usize,
+    pub idle_threads: usize,
+    /// Blocking tasks waiting for a thread
+    pub queue_depth: usize,
+}
+
+/// Where the active CDN gateway was configured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GatewaySource {
+    /// Set through the ODYSEE_CDN_GATEWAY environment variable
+    Env,
+    /// Built-in default (env var unset or rejected)
+    Default,
+}
+
+/// Where a `prebuffer` request was served from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrebufferSource {
+    /// Read from the offline file into the local server's memory
+    Offline,
+    /// Warmed up with a ranged request to the CDN
+    Online,
+    /// `prebuffer_seconds` is 0
+    Disabled,
+}
+
+/// Whether a claim's content can actually be downloaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadAvailability {
+    /// A stream whose CDN URL answered a HEAD request
+    Available,
+    /// Not a stream claim, or a stream without a valid sd_hash
+    NotStream,
+    /// A valid stream whose CDN URL could not be reached
+    Unreachable,
+    /// A valid stream the CDN refuses in the user's region (HTTP 403 or 451)
+    RegionBlocked,
+}
+
+impl DownloadAvailability {
+    pub fn is_downloadable(self) -> bool {
+        self == Self::Available
+    }
+}
+
+/// Where a download of one claim and quality stands in this session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadState {
+    /// No download was started since the app launched
+    NotStarted,
+    /// Requested and waiting for the download manager
+    Queued,
+    /// Transferring
+    Active,
+    Completed,
+    Failed,
+}
+
+impl DownloadState {
+    /// Whether the UI should show the item as downloading
+    pub fn is_downloading(self) -> bool {
+        matches!(self, Self::Queued | Self::Active)
+    }
+}
+
+/// Outcome of a `prebuffer` request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrebufferResult {
+    pub claim_id: String,
+    pub quality: String,
+    pub source: PrebufferSource,
+    pub bytes: u64,
+}
+
+/// The resolved CDN gateway used for playback URLs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CdnGatewayInfo {
+    pub gateway: String,
+    pub source: GatewaySource,
+    pub is_default: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -383,6 +703,28 @@ pub struct GatewayHealth {
     pub last_success: Option<i64>,
     pub last_error: Option<String>,
     pub response_time_ms: Option<u64>,
+    /// Successful requests among the last `GATEWAY_HEALTH_WINDOW`
+    #[serde(default)]
+    pub recent_successes: u32,
+    /// Failed requests among the last `GATEWAY_HEALTH_WINDOW`
+    #[serde(default)]
+    pub recent_failures: u32,
+    #[serde(default)]
+    pub consecutive_failures: u32,
+    pub last_failure: Option<i64>,
+    /// Unix time until which the gateway is skipped
+    pub cooldown_until: Option<i64>,
+}
+
+/// A gateway request that identical requests are currently waiting on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InflightRequestInfo {
+    /// Method and params the request is coalesced by
+    pub key: String,
+    pub method: String,
+    pub age_ms: u64,
+    /// Callers waiting for this request's result
+    pub waiters: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -400,6 +742,265 @@ pub struct CacheStats {
     pub last_cleanup: Option<i64>,
 }
 
+/// A claim that could not be parsed, deduplicated by claim id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParseFailure {
+    pub claim_id: String,
+    pub reason: String,
+    pub failure_count: u32,
+    pub first_seen: i64,
+    pub last_seen: i64,
+}
+
+/// Most recent view of a claim, with cached metadata when available
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ViewHistoryEntry {
+    pub claim_id: String,
+    pub viewed_at: i64,
+    pub title: Option<String>,
+    pub thumbnail_url: Option<String>,
+}
+
+/// Outcome of `store_content_items`; skipped items do not abort the batch
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StoreReport {
+    pub stored: u32,
+    pub skipped: Vec<SkippedItem>,
+}
+
+/// An item left out of a batch, with the reason it could not be stored
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkippedItem {
+    pub claim_id: String,
+    pub reason: String,
+}
+
+/// Rows and files removed by `clear_personal_data`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PersonalDataReport {
+    pub favorites_removed: u32,
+    pub progress_removed: u32,
+    pub view_history_removed: u32,
+    pub settings_removed: u32,
+    pub content_prefs_removed: u32,
+    pub offline_removed: u32,
+    pub offline_failed: u32,
+}
+
+/// A favorite changed since a sync cursor, or its tombstone if removed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FavoriteChange {
+    #[serde(flatten)]
+    pub favorite: FavoriteItem,
+    pub deleted_at: Option<i64>,
+    /// Local time of the change, whatever `inserted_at` the frontend sent
+    pub changed_at: i64,
+}
+
+/// A progress row changed since a sync cursor, or its tombstone if removed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgressChange {
+    #[serde(flatten)]
+    pub progress: ProgressData,
+    pub deleted_at: Option<i64>,
+    /// Local time of the change
+    pub changed_at: i64,
+}
+
+/// Favorites and progress changed after `since`, for a sync engine
+///
+/// `since` and `until` are positions in the local change sequence, not
+/// times. `until` is the last change included and should be passed as
+/// `since` on the next call; `0` returns every row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncChanges {
+    pub since: i64,
+    pub until: i64,
+    pub favorites: Vec<FavoriteChange>,
+    pub progress: Vec<ProgressChange>,
+}
+
+/// How a remote listing differs from the cache, by claim id
+///
+/// `new`, `changed` and `unchanged` follow the order of the incoming items.
+/// `removed` lists cached claims missing from the incoming set, sorted, as
+/// candidates for invalidation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffResult {
+    pub new: Vec<String>,
+    /// Cached with a different content hash, or with none recorded
+    pub changed: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// A channel and tag filter to prefetch into the cache with `warm_cache`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmCacheTarget {
+    pub channel_id: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Outcome of warming one `WarmCacheTarget`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelWarmResult {
+    pub channel_id: String,
+    pub tags: Vec<String>,
+    pub fetched: u32,
+    /// Items that were new or changed and written to the cache
+    pub stored: u32,
+    /// Items already cached with the same content
+    pub skipped: u32,
+    /// Set when this channel could not be fetched or stored
+    pub error: Option<String>,
+}
+
+/// Per-channel results of a `warm_cache` call, in request order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmCacheSummary {
+    pub channels: Vec<ChannelWarmResult>,
+}
+
+/// Version of the format written by `export_user_data_json`
+pub const USER_DATA_EXPORT_VERSION: u32 = 1;
+
+/// Portable, human-readable export of a user's data
+///
+/// Version 1 layout (all timestamps are Unix seconds):
+///
+/// ```json
+/// {
+///   "schema_version": 1,
+///   "exported_at": 1700000000,
+///   "favorites": [{ "claim_id": "...", "title": "...", "thumbnail_url": null, "inserted_at": 0 }],
+///   "progress": [{ "claim_id": "...", "position_seconds": 0, "quality": "720p", "updated_at": 0 }],
+///   "view_history": [{ "claim_id": "...", "viewed_at": 0 }],
+///   "content_prefs": [{ "claim_id": "...", "preferred_quality": "720p", "updated_at": 0 }],
+///   "playlists": [{
+///     "id": "...", "title": "...", "claim_id": "...", "season_number": 1, "series_key": "...",
+///     "updated_at": 0,
+///     "items": [{ "claim_id": "...", "position": 0, "episode_number": 1, "season_number": 1 }]
+///   }]
+/// }
+/// ```
+///
+/// `view_history` holds the latest view of each claim.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserDataExport {
+    pub schema_version: u32,
+    pub exported_at: i64,
+    #[serde(default)]
+    pub favorites: Vec<FavoriteItem>,
+    #[serde(default)]
+    pub progress: Vec<ProgressData>,
+    #[serde(default)]
+    pub view_history: Vec<ExportedView>,
+    #[serde(default)]
+    pub playlists: Vec<ExportedPlaylist>,
+    #[serde(default)]
+    pub content_prefs: Vec<ExportedContentPref>,
+}
+
+/// A single claim view in a user data export
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedView {
+    pub claim_id: String,
+    pub viewed_at: i64,
+}
+
+/// A per-item quality preference in a user data export
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedContentPref {
+    pub claim_id: String,
+    pub preferred_quality: String,
+    pub updated_at: i64,
+}
+
+/// A playlist in a user data export, with the time it was last stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedPlaylist {
+    #[serde(flatten)]
+    pub playlist: Playlist,
+    pub updated_at: i64,
+}
+
+/// Outcome of `import_user_data_json`
+///
+/// Records are skipped when the local copy is at least as recent, and
+/// conflicted when they cannot be merged safely; each conflict is listed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserDataImportReport {
+    pub favorites_imported: u32,
+    pub progress_imported: u32,
+    pub views_imported: u32,
+    pub playlists_imported: u32,
+    pub content_prefs_imported: u32,
+    pub skipped: u32,
+    pub conflicted: u32,
+    pub conflicts: Vec<ImportConflict>,
+}
+
+/// Why an imported record was not merged as-is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportConflictKind {
+    /// The same id appears more than once in a section; the newest copy is kept
+    DuplicateClaimId,
+    /// Two progress records share a timestamp but disagree on the position
+    ProgressTimestamp,
+    /// A playlist item references a claim that is neither cached nor imported
+    OrphanedPlaylistItem,
+}
+
+/// A record from an import file that was dropped by the validation pass
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportConflict {
+    pub kind: ImportConflictKind,
+    /// `favorites`, `progress`, `view_history` or `playlists`
+    pub section: String,
+    /// Claim id of the record, or the playlist id for duplicate playlists
+    pub id: String,
+    pub detail: String,
+}
+
+/// Duration of one standard query run by `get_query_timings`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryTiming {
+    pub name: String,
+    pub duration_ms: f64,
+    pub result_count: u32,
+}
+
+/// Age buckets for cached items, measured from `updatedAt`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgeBucket {
+    UnderOneHour,
+    OneToSixHours,
+    SixToTwentyFourHours,
+    OverTwentyFourHours,
+}
+
+impl AgeBucket {
+    /// All buckets in ascending age order
+    pub const ALL: [AgeBucket; 4] = [
+        AgeBucket::UnderOneHour,
+        AgeBucket::OneToSixHours,
+        AgeBucket::SixToTwentyFourHours,
+        AgeBucket::OverTwentyFourHours,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AgeBucket::UnderOneHour => "<1h",
+            AgeBucket::OneToSixHours => "1-6h",
+            AgeBucket::SixToTwentyFourHours => "6-24h",
+            AgeBucket::OverTwentyFourHours => ">24h",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryStats {
     pub cache_items: u32,
@@ -410,6 +1011,248 @@ pub struct MemoryStats {
     pub database_file_size: u64,
 }
 
+/// Order in which cache rows are evicted once `max_cache_items` is exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheEvictionPolicy {
+    /// Least recently accessed first
+    #[default]
+    Lru,
+    /// Least frequently accessed first
+    Lfu,
+    /// Oldest stored first
+    Fifo,
+}
+
+impl CacheEvictionPolicy {
+    /// Parses a `cache_eviction_policy` setting value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "lru" => Some(Self::Lru),
+            "lfu" => Some(Self::Lfu),
+            "fifo" => Some(Self::Fifo),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lru => "lru",
+            Self::Lfu => "lfu",
+            Self::Fifo => "fifo",
+        }
+    }
+
+    /// `ORDER BY` clause that lists eviction candidates first
+    ///
+    /// Only these fixed strings ever reach the eviction query.
+    pub fn order_by(self) -> &'static str {
+        match self {
+            Self::Lru => "lastAccessed ASC, accessCount ASC",
+            Self::Lfu => "accessCount ASC, lastAccessed ASC",
+            Self::Fifo => "updatedAt ASC",
+        }
+    }
+}
+
+/// How `raw_json` is written when a claim is cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RawJsonFormat {
+    /// Single-line JSON, the smallest on disk
+    #[default]
+    Compact,
+    /// Indented JSON that is easier to read when inspecting the database
+    Pretty,
+}
+
+impl RawJsonFormat {
+    /// Parses a `raw_json_format` setting value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "compact" => Some(Self::Compact),
+            "pretty" => Some(Self::Pretty),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Compact => "compact",
+            Self::Pretty => "pretty",
+        }
+    }
+}
+
+/// Which thumbnail URLs are kept when a claim is parsed
+///
+/// Protocol-relative `//host/img.jpg` URLs are always read as `https:`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailUrlPolicy {
+    /// `http://` URLs are rewritten to `https://`
+    #[default]
+    Upgrade,
+    /// Only `https://` URLs are kept
+    Strict,
+    /// `http://` URLs are kept as they are
+    Lenient,
+}
+
+impl ThumbnailUrlPolicy {
+    /// Parses a `thumbnail_url_policy` setting value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "upgrade" => Some(Self::Upgrade),
+            "strict" => Some(Self::Strict),
+            "lenient" => Some(Self::Lenient),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Upgrade => "upgrade",
+            Self::Strict => "strict",
+            Self::Lenient => "lenient",
+        }
+    }
+}
+
+/// A claim the CDN refused in the user's region, deduplicated by claim id
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegionBlock {
+    pub claim_id: String,
+    /// HTTP status of the most recent refusal, 403 or 451
+    pub status: u16,
+    pub block_count: u32,
+    pub first_seen: i64,
+    pub last_seen: i64,
+}
+
+/// Result of checking one id in `validate_claim_ids`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClaimIdValidation {
+    pub id: String,
+    pub valid: bool,
+    pub error: Option<String>,
+}
+
+/// Short health summary of the applied schema migrations
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaSummary {
+    pub current_version: u32,
+    pub migrations_applied: u32,
+    /// When the highest applied migration ran, as a Unix timestamp
+    pub latest_applied_at: Option<i64>,
+    /// True when any applied migration has a checksum that no longer matches
+    /// its SQL or is missing from this build. A NULL checksum is legacy and
+    /// counts as validated, as in `validate_migrations`.
+    pub has_unvalidated: bool,
+    pub unvalidated_versions: Vec<u32>,
+}
+
+/// Result of comparing `local_cache` with its FTS5 search index
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConsistencyReport {
+    /// False when search uses LIKE queries and there is no index to check
+    pub fts5_available: bool,
+    pub cache_rows: u32,
+    pub fts_rows: u32,
+    /// Cached claims the search index has no entry for
+    pub missing_from_fts: Vec<String>,
+    /// Index entries whose cache row no longer exists
+    pub orphaned_fts_rows: u32,
+    pub consistent: bool,
+    /// Whether the index was rebuilt after this check found drift
+    #[serde(default)]
+    pub rebuilt: bool,
+}
+
+/// Reclaimable space inside the database, used by storage recommendations
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatabaseStorageUsage {
+    pub database_bytes: u64,
+    pub wal_bytes: u64,
+    /// Unused pages that only a VACUUM returns to the filesystem
+    pub freelist_bytes: u64,
+    pub expired_items: u32,
+    pub expired_bytes: u64,
+    pub old_raw_json_items: u32,
+    pub old_raw_json_bytes: u64,
+}
+
+/// A single cleanup step suggested by `get_storage_recommendations`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageRecommendation {
+    pub description: String,
+    /// Tauri command that performs the cleanup
+    pub command: String,
+    /// Arguments to invoke `command` with
+    pub args: serde_json::Value,
+    pub estimated_bytes: u64,
+}
+
+/// Storage totals plus the cleanup steps worth running, in suggested order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageRecommendations {
+    pub vault_bytes: u64,
+    pub database_bytes: u64,
+    pub wal_bytes: u64,
+    pub recommendations: Vec<StorageRecommendation>,
+}
+
+/// A logged security event, as returned by `get_security_events`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityEventRecord {
+    pub timestamp: String,
+    pub severity: String,
+    pub event_type: String,
+    pub details: String,
+}
+
+/// Bytes, operation count and latency for one IO path
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IoPathStats {
+    pub bytes: u64,
+    pub operations: u64,
+    pub average_latency_ms: f64,
+    /// Moving average weighted towards the latest operations
+    pub recent_latency_ms: f64,
+}
+
+/// Disk IO on the download and playback paths, as returned by `get_disk_io_stats`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskIoStats {
+    pub download_writes: IoPathStats,
+    pub stream_reads: IoPathStats,
+    pub stream_decryption: IoPathStats,
+}
+
+/// Encrypt and decrypt throughput measured by `benchmark_encryption`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionBenchmark {
+    pub size_mb: u32,
+    pub encrypt_mb_per_sec: f64,
+    pub decrypt_mb_per_sec: f64,
+    pub measured_at: i64,
+}
+
+/// Timing summary for a single Tauri command over its recent calls
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandMetrics {
+    pub command: String,
+    pub call_count: u64,
+    pub success_count: u64,
+    pub failure_count: u64,
+    /// Number of recent durations the percentiles are computed from
+    pub sample_count: u32,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DownloadStats {
     pub total_downloads: u32,
@@ -586,6 +1429,26 @@ impl UpdateState {
 
 // Additional models for better type safety and functionality
 
+/// Outcome of a streaming search, sent with the final `search-complete` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchSummary {
+    pub query: String,
+    pub total_count: u32,
+    /// True if FTS5 was used, false for the LIKE fallback
+    pub used_fts5: bool,
+}
+
+/// A search result with the matched text marked for display
+///
+/// Snippets are HTML-escaped apart from the `<mark>` tags around matches and
+/// are `None` when the query did not match that field.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub item: ContentItem,
+    pub title_snippet: Option<String>,
+    pub description_snippet: Option<String>,
+}
+
 /// Represents a cache query with filters and pagination
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheQuery {
@@ -812,6 +1675,37 @@ pub mod quality {
             _ => 0,
         }
     }
+
+    /// Fixed-height qualities from best to worst; `master` is the adaptive stream
+    pub const RANKED_QUALITIES: &[&str] = &["1080p", "720p", "480p", "360p", "240p"];
+
+    /// Checks if a quality may be preferred for downloads
+    pub fn is_known_quality(quality: &str) -> bool {
+        quality == "master" || RANKED_QUALITIES.contains(&quality)
+    }
+
+    /// Returns the qualities to try for a preferred quality, best match first
+    ///
+    /// A preferred height is followed by the lower heights, closest first, so a
+    /// fallback never uses more data than asked for. `master` comes next since
+    /// the adaptive stream can serve the preferred height, and the higher
+    /// heights come last. Preferring `master` (or an unknown quality) tries it
+    /// first and then every height from the best down.
+    pub fn fallback_order(preferred: &str) -> Vec<&'static str> {
+        match RANKED_QUALITIES.iter().position(|q| *q == preferred) {
+            Some(index) => {
+                let mut order = RANKED_QUALITIES[index..].to_vec();
+                order.push("master");
+                order.extend(RANKED_QUALITIES[..index].iter().rev());
+                order
+            }
+            None => {
+                let mut order = vec!["master"];
+                order.extend(RANKED_QUALITIES);
+                order
+            }
+        }
+    }
 }
 
 // Series parsing utilities
@@ -990,6 +1884,40 @@ mod tests {
         assert!(!video_url.is_hls());
     }
 
+    #[test]
+    fn test_playlist_neighbors() {
+        let mut playlist = Playlist::new(
+            "neighbors-playlist".to_string(),
+            "Season 1".to_string(),
+            "series-claim".to_string(),
+        )
+        .unwrap();
+        // Stored out of order; neighbors follow position
+        for (claim_id, position) in [("episode-3", 3), ("episode-1", 1), ("episode-2", 2)] {
+            playlist.items.push(PlaylistItem {
+                claim_id: claim_id.to_string(),
+                position,
+                episode_number: Some(position),
+                season_number: Some(1),
+            });
+        }
+        let claim = |item: Option<PlaylistItem>| item.map(|item| item.claim_id);
+
+        let first = playlist.neighbors("episode-1").unwrap();
+        assert_eq!(claim(first.prev), None);
+        assert_eq!(claim(first.next).as_deref(), Some("episode-2"));
+
+        let middle = playlist.neighbors("episode-2").unwrap();
+        assert_eq!(claim(middle.prev).as_deref(), Some("episode-1"));
+        assert_eq!(claim(middle.next).as_deref(), Some("episode-3"));
+
+        let last = playlist.neighbors("episode-3").unwrap();
+        assert_eq!(claim(last.prev).as_deref(), Some("episode-2"));
+        assert_eq!(claim(last.next), None);
+
+        assert!(playlist.neighbors("episode-9").is_none());
+    }
+
     #[test]
     fn test_range_request_parsing() {
         let range = RangeRequest::from_header("bytes=0-1023").unwrap();
@@ -10,6 +10,10 @@
 //! - Symbolic links must be resolved and validated
 //! - Absolute paths outside app data must be rejected
 //!
+//! The one exception is a directory the user picks in settings, such as the
+//! download temp directory, which may live on another drive. Those go through
+//! `validate_user_directory`, which rejects traversal and system directories.
+//!
 //! ## Usage
 //!
 //! ```rust
@@ -216,6 +220,77 @@ pub fn validate_subdir_path<P: AsRef<Path>, Q: AsRef<Path>>(
     validate_path(path)
 }
 
+/// Top-level system directories a user-chosen directory may not be in
+#[cfg(not(target_os = "windows"))]
+const SYSTEM_DIRECTORIES: &[&str] = &[
+    "/bin", "/boot", "/dev", "/etc", "/lib", "/lib64", "/proc", "/sbin", "/sys", "/usr", "/System",
+];
+#[cfg(target_os = "windows")]
+const SYSTEM_DIRECTORIES: &[&str] = &[
+    "c:\\windows",
+    "c:\\program files",
+    "c:\\program files (x86)",
+    "c:\\programdata",
+];
+
+/// Validates a directory the user chose in settings, which may be outside app data
+///
+/// Relative paths are resolved inside app data with `validate_path`. Absolute
+/// paths must not contain `..`, must not be a filesystem root or inside a
+/// system directory, and must not name an existing file. The directory does
+/// not need to exist yet.
+pub fn validate_user_directory<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
+    let path = path.as_ref();
+    if !path.is_absolute() {
+        return validate_path(path);
+    }
+
+    if path
+        .components()
+        .any(|component| matches!(component, std::path::Component::ParentDir))
+    {
+        return Err(KiyyaError::SecurityViolation {
+            message: format!(
+                "Path '{}' contains parent directory references",
+                path.display()
+            ),
+        });
+    }
+
+    let resolved = resolve_path_components(path)?;
+    if resolved.parent().is_none() {
+        return Err(KiyyaError::SecurityViolation {
+            message: format!("Path '{}' is a filesystem root", path.display()),
+        });
+    }
+
+    let normalized = normalize_path(&resolved);
+    #[cfg(target_os = "windows")]
+    let in_system_directory = {
+        let lowered = normalized.to_string_lossy().to_lowercase();
+        SYSTEM_DIRECTORIES
+            .iter()
+            .any(|dir| Path::new(&lowered).starts_with(dir))
+    };
+    #[cfg(not(target_os = "windows"))]
+    let in_system_directory = SYSTEM_DIRECTORIES
+        .iter()
+        .any(|dir| normalized.starts_with(dir));
+    if in_system_directory {
+        return Err(KiyyaError::SecurityViolation {
+            message: format!("Path '{}' is inside a system directory", path.display()),
+        });
+    }
+
+    if resolved.exists() && !resolved.is_dir() {
+        return Err(KiyyaError::InvalidInput {
+            message: format!("Path '{}' is not a directory", path.display()),
+        });
+    }
+
+    Ok(resolved)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -323,4 +398,26 @@ mod tests {
         assert!(path.to_string_lossy().contains("movies"));
         assert!(path.to_string_lossy().contains("action"));
     }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_validate_user_directory() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let chosen = temp_dir.path().join("downloads-in-progress");
+        assert_eq!(validate_user_directory(&chosen).unwrap(), chosen);
+
+        // Relative paths stay inside app data
+        let relative = validate_user_directory("vault/partial").unwrap();
+        assert!(relative.starts_with(get_app_data_dir().unwrap()));
+
+        assert!(validate_user_directory("/").is_err());
+        assert!(validate_user_directory("/etc/kiyya").is_err());
+        assert!(validate_user_directory("/usr/local/tmp").is_err());
+        assert!(validate_user_directory(temp_dir.path().join("a/../../escape")).is_err());
+        assert!(validate_user_directory("../outside").is_err());
+
+        let file = temp_dir.path().join("file.txt");
+        std::fs::write(&file, b"not a directory").unwrap();
+        assert!(validate_user_directory(&file).is_err());
+    }
 }
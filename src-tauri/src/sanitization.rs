@@ -21,6 +21,7 @@ pub fn sanitize_order_by(order_by: &str) -> Result<String> {
         "accessCount",
         "insertedAt",
         "addedAt",
+        "fileSize",
         "positionSeconds",
     ];
 
@@ -65,6 +65,7 @@ mod tests {
                 etag: None,
                 content_hash: None,
                 raw_json: None,
+                stale: false,
             },
             ContentItem {
                 claim_id: "claim2".to_string(),
@@ -83,6 +84,7 @@ mod tests {
                 etag: None,
                 content_hash: None,
                 raw_json: None,
+                stale: false,
             },
             ContentItem {
                 claim_id: "claim3".to_string(),
@@ -101,6 +103,7 @@ mod tests {
                 etag: None,
                 content_hash: None,
                 raw_json: None,
+                stale: false,
             },
         ];
 
@@ -176,6 +179,39 @@ mod tests {
         assert_eq!(results.len(), 0, "Empty query should return no results");
     }
 
+    #[tokio::test]
+    async fn test_search_content_chunked_streams_all_results() {
+        let db = setup_test_db().await;
+
+        let chunks = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let chunks_clone = chunks.clone();
+        let summary = db
+            .search_content_chunked("Breaking Bad", 1, move |items| {
+                chunks_clone.lock().unwrap().push(items.len());
+                Ok(())
+            })
+            .await
+            .expect("Streaming search failed");
+
+        let chunks = chunks.lock().unwrap().clone();
+        assert_eq!(chunks, vec![1, 1], "Each match should arrive in its own chunk");
+        assert_eq!(summary.total_count, 2);
+        assert_eq!(summary.used_fts5, db.fts5_available);
+        assert_eq!(summary.query, "Breaking Bad");
+    }
+
+    #[tokio::test]
+    async fn test_search_content_chunked_empty_query() {
+        let db = setup_test_db().await;
+
+        let summary = db
+            .search_content_chunked("  ", 10, |_| panic!("No chunks expected for empty query"))
+            .await
+            .expect("Streaming search failed");
+
+        assert_eq!(summary.total_count, 0);
+    }
+
     #[tokio::test]
     async fn test_search_content_no_results() {
         let db = setup_test_db().await;
@@ -14,6 +14,9 @@
 //! - Network security violations (attempts to access unauthorized domains)
 //! - Encryption key operations (key generation, access, deletion)
 //! - Authentication/authorization failures
+//! - Repeated validation failures in a short window (possible misbehaving caller)
+//!
+//! The most recent events are also kept in memory for `get_security_events`.
 //!
 //! ## Log Format
 //!
@@ -41,11 +44,31 @@
 //! });
 //! ```
 
+use crate::models::SecurityEventRecord;
 use crate::path_security;
+use once_cell::sync::Lazy;
+use std::collections::VecDeque;
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tracing::{error, info, warn};
 
+/// Number of recent events kept in memory for diagnostics
+const MAX_RECENT_EVENTS: usize = 500;
+
+/// Validation failures within `VALIDATION_FAILURE_WINDOW` that are reported as suspicious
+pub const VALIDATION_FAILURE_THRESHOLD: usize = 10;
+
+/// Sliding window over which validation failures are counted
+pub const VALIDATION_FAILURE_WINDOW: Duration = Duration::from_secs(60);
+
+static RECENT_EVENTS: Lazy<Mutex<VecDeque<SecurityEventRecord>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(MAX_RECENT_EVENTS)));
+
+static VALIDATION_FAILURES: Lazy<Mutex<ValidationFailureMonitor>> =
+    Lazy::new(|| Mutex::new(ValidationFailureMonitor::new()));
+
 /// Security event types that can be logged
 #[derive(Debug, Clone)]
 pub enum SecurityEvent {
@@ -296,6 +319,20 @@ pub fn log_security_event(event: SecurityEvent) {
         }
     }
 
+    let mut recent = RECENT_EVENTS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if recent.len() == MAX_RECENT_EVENTS {
+        recent.pop_front();
+    }
+    recent.push_back(SecurityEventRecord {
+        timestamp: timestamp.clone(),
+        severity: severity_str.to_string(),
+        event_type: event_type.to_string(),
+        details: details.clone(),
+    });
+    drop(recent);
+
     // Also log to standard logging system based on severity
     match severity {
         SecuritySeverity::Info => {
@@ -310,6 +347,91 @@ pub fn log_security_event(event: SecurityEvent) {
     }
 }
 
+/// Returns up to `limit` of the most recent security events, newest first
+pub fn recent_security_events(limit: usize) -> Vec<SecurityEventRecord> {
+    RECENT_EVENTS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .iter()
+        .rev()
+        .take(limit)
+        .cloned()
+        .collect()
+}
+
+/// Counts validation failures in a sliding window and decides when a burst of
+/// them should be reported
+#[derive(Debug)]
+pub struct ValidationFailureMonitor {
+    failures: VecDeque<Instant>,
+    last_alert: Option<Instant>,
+}
+
+impl ValidationFailureMonitor {
+    pub fn new() -> Self {
+        Self {
+            failures: VecDeque::new(),
+            last_alert: None,
+        }
+    }
+
+    /// Records a failure at `now` and returns the number of failures in the
+    /// window when it crosses the threshold
+    ///
+    /// At most one alert is raised per window so a flood of bad input produces
+    /// one event rather than one per failure.
+    pub fn record(&mut self, now: Instant) -> Option<usize> {
+        while let Some(oldest) = self.failures.front() {
+            if now.duration_since(*oldest) > VALIDATION_FAILURE_WINDOW {
+                self.failures.pop_front();
+            } else {
+                break;
+            }
+        }
+        self.failures.push_back(now);
+
+        if self.failures.len() < VALIDATION_FAILURE_THRESHOLD {
+            return None;
+        }
+        if let Some(last_alert) = self.last_alert {
+            if now.duration_since(last_alert) < VALIDATION_FAILURE_WINDOW {
+                return None;
+            }
+        }
+
+        self.last_alert = Some(now);
+        Some(self.failures.len())
+    }
+}
+
+impl Default for ValidationFailureMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Records a command rejected for invalid input, logging a suspicious-activity
+/// event when too many arrive within `VALIDATION_FAILURE_WINDOW`
+pub fn record_validation_failure(command: &str, reason: &str) {
+    let count = VALIDATION_FAILURES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .record(Instant::now());
+
+    if let Some(count) = count {
+        log_security_event(SecurityEvent::SuspiciousActivity {
+            activity_type: "repeated_validation_failures".to_string(),
+            details: format!(
+                "{} validation failures within {}s, latest: {}",
+                count,
+                VALIDATION_FAILURE_WINDOW.as_secs(),
+                reason
+            ),
+            source: command.to_string(),
+        });
+    }
+}
+
 /// Logs multiple security events in batch
 ///
 /// This is useful when multiple related security events need to be logged together.
@@ -401,4 +523,46 @@ mod tests {
         ];
         log_security_events(events);
     }
+
+    #[test]
+    fn test_repeated_validation_failures_alert_once_per_window() {
+        let mut monitor = ValidationFailureMonitor::new();
+        let start = Instant::now();
+
+        for i in 0..VALIDATION_FAILURE_THRESHOLD - 1 {
+            assert_eq!(monitor.record(start + Duration::from_secs(i as u64)), None);
+        }
+        let burst_end = start + Duration::from_secs(VALIDATION_FAILURE_THRESHOLD as u64);
+        assert_eq!(monitor.record(burst_end), Some(VALIDATION_FAILURE_THRESHOLD));
+
+        // Further failures in the same window don't raise another alert
+        assert_eq!(monitor.record(burst_end + Duration::from_secs(1)), None);
+
+        // Failures spread out over time never reach the threshold
+        let later = burst_end + VALIDATION_FAILURE_WINDOW * 3;
+        for i in 0..VALIDATION_FAILURE_THRESHOLD * 2 {
+            assert_eq!(monitor.record(later + Duration::from_secs(i as u64 * 10)), None);
+        }
+    }
+
+    #[test]
+    fn test_recent_security_events_newest_first() {
+        for attempt in ["first", "second"] {
+            log_security_event(SecurityEvent::InputValidationFailure {
+                input_type: "claim_id".to_string(),
+                reason: format!("recent-events-test {}", attempt),
+                source: "test".to_string(),
+            });
+        }
+
+        let ours: Vec<SecurityEventRecord> = recent_security_events(MAX_RECENT_EVENTS)
+            .into_iter()
+            .filter(|event| event.details.contains("recent-events-test"))
+            .collect();
+        assert_eq!(ours.len(), 2);
+        assert!(ours[0].details.ends_with("second"));
+        assert_eq!(ours[0].event_type, "INPUT_VALIDATION_FAILURE");
+        assert_eq!(ours[0].severity, "WARNING");
+        assert!(recent_security_events(1).len() <= 1);
+    }
 }
@@ -430,8 +430,8 @@ mod tests {
 
     #[test]
     fn test_security_boundary_quality_values_restricted() {
-        // In the new CDN-first architecture, we only use "master" quality for HLS adaptive streaming
-        let valid_qualities = vec!["master"];
+        // "master" plus the fixed heights used to rank download qualities
+        let valid_qualities = vec!["master", "240p", "360p", "480p", "720p", "1080p"];
 
         for quality in valid_qualities {
             assert!(
@@ -447,12 +447,8 @@ mod tests {
             "ultra",
             "max",
             "'; DROP TABLE--",
-            // Old quality-specific values are no longer valid
-            "240p",
-            "360p",
-            "480p",
-            "720p",
-            "1080p",
+            // Heights outside the ranked list
+            "144p",
             "1440p",
             "2160p",
             "4k",
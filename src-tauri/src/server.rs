@@ -1,19 +1,254 @@
-use crate::encryption::EncryptionManager;
+use crate::disk_io;
+use crate::encryption::{EncryptionManager, RangeDecryptor};
 use crate::error::{KiyyaError, Result};
 use crate::models::ServerStatus;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tokio::fs::File;
 use tokio::io::{AsyncReadExt, AsyncSeekExt, SeekFrom};
 use tokio::sync::{Mutex, RwLock};
-use tracing::{error, info, warn};
-use warp::{http::StatusCode, Filter, Rejection, Reply};
+use tracing::{debug, error, info, warn};
+use warp::http::{HeaderMap, StatusCode};
+use warp::hyper::Body;
+use warp::{Filter, Rejection, Reply};
+
+/// Upper bound on the bytes held in memory for one prebuffered stream
+pub const MAX_PREBUFFER_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Upper bound on the bytes held across all prebuffered streams
+pub const MAX_PREBUFFER_TOTAL_BYTES: u64 = 96 * 1024 * 1024;
+
+/// Number of streams that may hold a prebuffer at once
+const MAX_PREBUFFERED_STREAMS: usize = 8;
+
+/// Prebuffers the player has not read from for this long are dropped
+const PREBUFFER_IDLE_TTL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// Leading bytes of registered streams, read ahead of the player's first request
+type PrebufferMap = Arc<RwLock<PrebufferCache>>;
+
+/// Prebuffered streams, bounded in count and total bytes
+///
+/// Idle buffers are dropped on every access, and the least recently used
+/// buffers make room when a new one would exceed the limits.
+struct PrebufferCache {
+    entries: HashMap<String, PrebufferEntry>,
+    total_bytes: u64,
+    max_streams: usize,
+    max_total_bytes: u64,
+    idle_ttl: std::time::Duration,
+}
+
+struct PrebufferEntry {
+    data: Arc<Vec<u8>>,
+    last_used: Instant,
+}
+
+impl Default for PrebufferCache {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            total_bytes: 0,
+            max_streams: MAX_PREBUFFERED_STREAMS,
+            max_total_bytes: MAX_PREBUFFER_TOTAL_BYTES,
+            idle_ttl: PREBUFFER_IDLE_TTL,
+        }
+    }
+}
+
+impl PrebufferCache {
+    /// Returns the buffer for `uuid` and marks it as used
+    fn get(&mut self, uuid: &str, now: Instant) -> Option<Arc<Vec<u8>>> {
+        self.evict_idle(now);
+        let entry = self.entries.get_mut(uuid)?;
+        entry.last_used = now;
+        Some(entry.data.clone())
+    }
+
+    fn insert(&mut self, uuid: String, data: Arc<Vec<u8>>, now: Instant) {
+        self.remove(&uuid);
+        self.evict_idle(now);
+
+        let size = data.len() as u64;
+        while !self.entries.is_empty()
+            && (self.entries.len() >= self.max_streams
+                || self.total_bytes + size > self.max_total_bytes)
+        {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(uuid, _)| uuid.clone());
+            if let Some(oldest) = oldest {
+                debug!("Evicting prebuffer for {} to make room", oldest);
+                self.remove(&oldest);
+            }
+        }
+
+        self.total_bytes += size;
+        self.entries.insert(
+            uuid,
+            PrebufferEntry {
+                data,
+                last_used: now,
+            },
+        );
+    }
+
+    fn remove(&mut self, uuid: &str) {
+        if let Some(entry) = self.entries.remove(uuid) {
+            self.total_bytes -= entry.data.len() as u64;
+        }
+    }
+
+    fn evict_idle(&mut self, now: Instant) {
+        let idle_ttl = self.idle_ttl;
+        let idle: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| now.saturating_duration_since(entry.last_used) >= idle_ttl)
+            .map(|(uuid, _)| uuid.clone())
+            .collect();
+        for uuid in idle {
+            debug!("Dropping idle prebuffer for {}", uuid);
+            self.remove(&uuid);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Largest image the thumbnail proxy will fetch
+pub const MAX_THUMBNAIL_BYTES: u64 = 5 * 1024 * 1024;
+
+/// How long the liveness check waits for `/health` before declaring the server hung
+const HEALTH_PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Number of proxied thumbnails kept in memory
+const MAX_CACHED_THUMBNAILS: usize = 256;
+
+/// Number of registered thumbnail URLs remembered by the proxy
+const MAX_THUMBNAIL_SOURCES: usize = 4096;
+
+/// Image gateways tried, in order, before the thumbnail's own URL
+///
+/// Each entry is a prefix the original URL is appended to. They serve the
+/// same images from hosts outside the thumbnail CDN, which is what networks
+/// that block the CDN still let through.
+pub const DEFAULT_THUMBNAIL_GATEWAYS: [&str; 2] = [
+    "https://image-processor.vanwanet.com/optimize/s:0:0/quality:85/plain/",
+    "https://thumbnails.odycdn.com/optimize/s:0:0/quality:85/plain/",
+];
+
+/// Thumbnails served through `/thumbnails/{id}`
+///
+/// Only URLs registered through `register_thumbnail` can be fetched, so the
+/// endpoint cannot be used as an open proxy.
+struct ThumbnailProxy {
+    /// Proxy id to upstream URL
+    sources: LruMap<String>,
+    /// Proxy id to fetched image
+    cache: LruMap<CachedThumbnail>,
+    /// Prefixes tried before the direct URL, see `DEFAULT_THUMBNAIL_GATEWAYS`
+    gateways: Vec<String>,
+}
+
+impl Default for ThumbnailProxy {
+    fn default() -> Self {
+        Self {
+            sources: LruMap::new(MAX_THUMBNAIL_SOURCES),
+            cache: LruMap::new(MAX_CACHED_THUMBNAILS),
+            gateways: DEFAULT_THUMBNAIL_GATEWAYS
+                .iter()
+                .map(|prefix| prefix.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl ThumbnailProxy {
+    /// Upstream URLs for `source`, gateways first and the direct URL last
+    fn fetch_urls(&self, source: &str) -> Vec<String> {
+        self.gateways
+            .iter()
+            .map(|prefix| format!("{}{}", prefix, source))
+            .chain(std::iter::once(source.to_string()))
+            .collect()
+    }
+}
+
+/// Map that drops its least recently used key once it exceeds `capacity`
+struct LruMap<V> {
+    capacity: usize,
+    /// Key to value and the tick it was last used at
+    entries: HashMap<String, (V, u64)>,
+    /// Last-use tick to key, oldest first
+    recency: BTreeMap<u64, String>,
+    next_tick: u64,
+}
+
+#[derive(Debug, Clone)]
+struct CachedThumbnail {
+    content_type: String,
+    data: Arc<Vec<u8>>,
+}
+
+impl<V: Clone> LruMap<V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            next_tick: 0,
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.next_tick += 1;
+        self.next_tick
+    }
+
+    /// Returns the value for `key` and marks it as recently used
+    fn get(&mut self, key: &str) -> Option<V> {
+        let tick = self.tick();
+        let (value, last_used) = self.entries.get_mut(key)?;
+        self.recency.remove(last_used);
+        *last_used = tick;
+        self.recency.insert(tick, key.to_string());
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: String, value: V) {
+        let tick = self.tick();
+        if let Some((_, last_used)) = self.entries.insert(key.clone(), (value, tick)) {
+            self.recency.remove(&last_used);
+        }
+        self.recency.insert(tick, key);
+
+        while self.entries.len() > self.capacity {
+            let oldest = match self.recency.keys().next() {
+                Some(&tick) => tick,
+                None => break,
+            };
+            if let Some(key) = self.recency.remove(&oldest) {
+                self.entries.remove(&key);
+            }
+        }
+    }
+}
 
 pub struct LocalServer {
     port: Option<u16>,
     active_streams: Arc<RwLock<HashMap<String, StreamInfo>>>,
+    prebuffered: PrebufferMap,
+    thumbnails: Arc<RwLock<ThumbnailProxy>>,
+    http_client: reqwest::Client,
     encryption_manager: Arc<Mutex<EncryptionManager>>,
     server_handle: Option<tokio::task::JoinHandle<()>>,
 }
@@ -24,15 +259,27 @@ struct StreamInfo {
     encrypted: bool,
     content_type: String,
     file_size: u64,
+    /// Validator derived from the file's mtime and size at registration
+    etag: String,
+    last_modified: Option<SystemTime>,
 }
 
 impl LocalServer {
     pub async fn new() -> Result<Self> {
         let encryption_manager = EncryptionManager::new()?;
+        // Redirects are not followed: a registered thumbnail URL was validated,
+        // the host it redirects to was not and could be on the local network.
+        let http_client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(15))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
 
         Ok(Self {
             port: None,
             active_streams: Arc::new(RwLock::new(HashMap::new())),
+            prebuffered: Arc::new(RwLock::new(PrebufferCache::default())),
+            thumbnails: Arc::new(RwLock::new(ThumbnailProxy::default())),
+            http_client,
             encryption_manager: Arc::new(Mutex::new(encryption_manager)),
             server_handle: None,
         })
@@ -54,16 +301,33 @@ impl LocalServer {
         info!("Starting local HTTP server on port {}", port);
 
         let active_streams = self.active_streams.clone();
+        let prebuffered = self.prebuffered.clone();
         let encryption_manager = self.encryption_manager.clone();
 
         // Create warp routes
         let movies_route = warp::path!("movies" / String)
-            .and(warp::header::optional::<String>("range"))
-            .and_then(move |uuid: String, range: Option<String>| {
+            .and(warp::get().or(warp::head()).unify())
+            .and(warp::header::headers_cloned())
+            .and_then(move |uuid: String, headers: HeaderMap| {
                 let active_streams = active_streams.clone();
+                let prebuffered = prebuffered.clone();
                 let encryption_manager = encryption_manager.clone();
 
-                async move { serve_content(uuid, range, active_streams, encryption_manager).await }
+                async move {
+                    serve_content(uuid, headers, active_streams, prebuffered, encryption_manager)
+                        .await
+                }
+            });
+
+        // Thumbnail proxy for networks that block the image CDN
+        let thumbnails = self.thumbnails.clone();
+        let http_client = self.http_client.clone();
+        let thumbnails_route = warp::path!("thumbnails" / String)
+            .and(warp::get())
+            .and_then(move |id: String| {
+                let thumbnails = thumbnails.clone();
+                let http_client = http_client.clone();
+                async move { serve_thumbnail(id, thumbnails, http_client).await }
             });
 
         // Health check endpoint
@@ -80,7 +344,12 @@ impl LocalServer {
             async move { status_check(active_streams).await }
         });
 
-        let routes = movies_route.or(health_route).or(status_route).with(
+        let routes = movies_route
+            .or(thumbnails_route)
+            .or(health_route)
+            .or(status_route)
+            .recover(handle_rejection)
+            .with(
             warp::cors()
                 .allow_any_origin()
                 .allow_headers(vec!["range"])
@@ -108,7 +377,18 @@ impl LocalServer {
         file_path: PathBuf,
         encrypted: bool,
     ) -> Result<()> {
-        let file_size = tokio::fs::metadata(&file_path).await?.len();
+        let metadata = tokio::fs::metadata(&file_path).await?;
+        // Players see the plaintext, so encrypted files report its length
+        let file_size = if encrypted {
+            self.encryption_manager
+                .lock()
+                .await
+                .plaintext_size(&file_path)
+                .await?
+        } else {
+            metadata.len()
+        };
+        let last_modified = metadata.modified().ok();
         let content_type = mime_guess::from_path(&file_path)
             .first_or_octet_stream()
             .to_string();
@@ -118,6 +398,8 @@ impl LocalServer {
             encrypted,
             content_type,
             file_size,
+            etag: stream_etag(last_modified, file_size),
+            last_modified,
         };
 
         let mut streams = self.active_streams.write().await;
@@ -133,10 +415,103 @@ impl LocalServer {
     pub async fn unregister_content(&self, uuid: &str) -> Result<()> {
         let mut streams = self.active_streams.write().await;
         streams.remove(uuid);
+        self.prebuffered.write().await.remove(uuid);
         info!("Unregistered content: {}", uuid);
         Ok(())
     }
 
+    /// Reads the first `max_bytes` of a registered stream into memory
+    ///
+    /// Requests that fall inside the buffer are then served without touching
+    /// the file. Encrypted content is decrypted once here. Calling this again
+    /// for an already buffered stream does not re-read it. Returns the number
+    /// of bytes buffered, capped at `MAX_PREBUFFER_BYTES` and the file size.
+    /// Older buffers are evicted once `MAX_PREBUFFER_TOTAL_BYTES` would be
+    /// exceeded.
+    pub async fn prebuffer(&self, uuid: &str, max_bytes: u64) -> Result<u64> {
+        let stream_info = self
+            .active_streams
+            .read()
+            .await
+            .get(uuid)
+            .cloned()
+            .ok_or_else(|| KiyyaError::ContentNotFound {
+                claim_id: uuid.to_string(),
+            })?;
+
+        let target = max_bytes
+            .min(MAX_PREBUFFER_BYTES)
+            .min(stream_info.file_size);
+        if target == 0 {
+            return Ok(0);
+        }
+
+        let existing = self.prebuffered.write().await.get(uuid, Instant::now());
+        if let Some(existing) = existing {
+            if existing.len() as u64 >= target {
+                debug!("Stream {} already prebuffered ({} bytes)", uuid, existing.len());
+                return Ok(existing.len() as u64);
+            }
+        }
+
+        let data = if stream_info.encrypted {
+            let encryption_manager = self.encryption_manager.lock().await;
+            encryption_manager
+                .decrypt_range(&stream_info.file_path, 0, target - 1)
+                .await?
+        } else {
+            read_file_range(&stream_info.file_path, 0, target - 1).await?
+        };
+
+        let buffered = data.len() as u64;
+        self.prebuffered
+            .write()
+            .await
+            .insert(uuid.to_string(), Arc::new(data), Instant::now());
+
+        info!("Prebuffered {} bytes for {}", buffered, uuid);
+        Ok(buffered)
+    }
+
+    /// Makes `url` available through the thumbnail proxy and returns the local URL
+    ///
+    /// The caller validates `url` first. The server must be running.
+    pub async fn register_thumbnail(&self, url: &str) -> Result<String> {
+        let port = self.port.ok_or_else(|| KiyyaError::Internal {
+            message: "Local server is not running".to_string(),
+        })?;
+
+        let id = thumbnail_id(url);
+        self.thumbnails
+            .write()
+            .await
+            .sources
+            .insert(id.clone(), url.to_string());
+
+        Ok(format!("http://127.0.0.1:{}/thumbnails/{}", port, id))
+    }
+
+    /// Authenticates an encrypted file with the stream decryption key
+    ///
+    /// Fails with `IntegrityCheckFailed` when the file was modified or cut short.
+    /// A file written before the format header is rewritten in the current
+    /// format on its first check.
+    pub async fn check_integrity(&self, file_path: &Path) -> Result<()> {
+        self.encryption_manager
+            .lock()
+            .await
+            .check_integrity(file_path)
+            .await
+    }
+
+    /// Reloads the stream decryption key from the OS keystore
+    pub async fn reload_encryption_key(&self) -> Result<bool> {
+        self.encryption_manager
+            .lock()
+            .await
+            .load_encryption_from_keystore()
+    }
+
     pub async fn get_status(&self) -> ServerStatus {
         let active_streams = self.active_streams.read().await;
 
@@ -156,12 +531,56 @@ impl LocalServer {
         self.port = None;
         Ok(())
     }
+
+    /// Returns true if the running server answers its own `/health` endpoint
+    pub async fn is_responsive(&self) -> bool {
+        let port = match self.port {
+            Some(port) => port,
+            None => return false,
+        };
+        if matches!(&self.server_handle, Some(handle) if handle.is_finished()) {
+            return false;
+        }
+
+        match self
+            .http_client
+            .get(format!("http://127.0.0.1:{}/health", port))
+            .timeout(HEALTH_PROBE_TIMEOUT)
+            .send()
+            .await
+        {
+            Ok(response) => response.status().is_success(),
+            Err(e) => {
+                debug!("Local server health probe failed: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Tears down the server and starts a fresh one on a new port
+    ///
+    /// Registered streams, prebuffers and thumbnails are shared with the new
+    /// server, so only the port in previously returned URLs changes.
+    pub async fn restart(&mut self) -> Result<u16> {
+        self.stop().await?;
+        self.start().await
+    }
+
+    /// Starts the server, or restarts it if it is running but unresponsive
+    pub async fn ensure_running(&mut self) -> Result<u16> {
+        if self.port.is_some() && !self.is_responsive().await {
+            warn!("Local HTTP server is unresponsive, restarting it");
+            return self.restart().await;
+        }
+        self.start().await
+    }
 }
 
 async fn serve_content(
     uuid: String,
-    range_header: Option<String>,
+    headers: HeaderMap,
     active_streams: Arc<RwLock<HashMap<String, StreamInfo>>>,
+    prebuffered: PrebufferMap,
     encryption_manager: Arc<Mutex<EncryptionManager>>,
 ) -> std::result::Result<impl Reply, Rejection> {
     let streams = active_streams.read().await;
@@ -177,9 +596,26 @@ async fn serve_content(
     };
     drop(streams);
 
+    // Conditional requests are answered before touching the file
+    if is_not_modified(&headers, &stream_info.etag, stream_info.last_modified) {
+        debug!("Serving 304 for {}", uuid);
+        let mut response = warp::http::Response::builder().status(StatusCode::NOT_MODIFIED);
+        for (name, value) in validator_headers(&stream_info) {
+            response = response.header(name, value);
+        }
+        return Ok(response
+            .body(Vec::new())
+            .map(Reply::into_response)
+            .unwrap_or_else(|_| StatusCode::NOT_MODIFIED.into_response()));
+    }
+
+    // A Range whose If-Range validator is stale gets the whole file instead
+    let range_header = header_str(&headers, "range")
+        .filter(|_| if_range_satisfied(&headers, &stream_info.etag, stream_info.last_modified));
+
     // Parse range header
     let (start, end) = if let Some(range) = range_header {
-        match parse_range_header(&range, stream_info.file_size) {
+        match parse_range_header(range, stream_info.file_size) {
             Ok(range) => range,
             Err(e) => {
                 warn!("Invalid range header: {} - {}", range, e);
@@ -194,15 +630,26 @@ async fn serve_content(
         (0, stream_info.file_size - 1)
     };
 
-    // Read content
-    let content = if stream_info.encrypted {
-        // Decrypt range
-        let encryption_manager = encryption_manager.lock().await;
-        match encryption_manager
-            .decrypt_range(&stream_info.file_path, start, end)
+    // Read content, preferring bytes already prebuffered in memory
+    let buffered = prebuffered
+        .write()
+        .await
+        .get(&uuid, Instant::now())
+        .filter(|buffer| end < buffer.len() as u64)
+        .cloned();
+    let (body, content_length) = if let Some(buffer) = buffered {
+        let content = buffer[start as usize..=end as usize].to_vec();
+        let content_length = content.len() as u64;
+        (Body::from(content), content_length)
+    } else if stream_info.encrypted {
+        // Decrypt chunk by chunk while the body is sent
+        let opened = encryption_manager
+            .lock()
             .await
-        {
-            Ok(data) => data,
+            .open_range(&stream_info.file_path, start, end)
+            .await;
+        match opened {
+            Ok(decryptor) => (decrypted_body(decryptor), end - start + 1),
             Err(e) => {
                 error!("Failed to decrypt content: {}", e);
                 return Ok(warp::reply::with_status(
@@ -215,7 +662,10 @@ async fn serve_content(
     } else {
         // Read plain file range
         match read_file_range(&stream_info.file_path, start, end).await {
-            Ok(data) => data,
+            Ok(data) => {
+                let content_length = data.len() as u64;
+                (Body::from(data), content_length)
+            }
             Err(e) => {
                 error!("Failed to read file: {}", e);
                 return Ok(warp::reply::with_status(
@@ -228,14 +678,15 @@ async fn serve_content(
     };
 
     // Build response
-    let content_length = content.len();
     let is_partial = start > 0 || end < stream_info.file_size - 1;
 
     let mut response = warp::http::Response::builder()
         .header("Content-Type", &stream_info.content_type)
         .header("Accept-Ranges", "bytes")
-        .header("Content-Length", content_length.to_string())
-        .header("Cache-Control", "no-cache");
+        .header("Content-Length", content_length.to_string());
+    for (name, value) in validator_headers(&stream_info) {
+        response = response.header(name, value);
+    }
 
     if is_partial {
         response = response.status(StatusCode::PARTIAL_CONTENT).header(
@@ -246,7 +697,7 @@ async fn serve_content(
         response = response.status(StatusCode::OK);
     }
 
-    match response.body(content) {
+    match response.body(body) {
         Ok(resp) => Ok(resp.into_response()),
         Err(e) => {
             error!("Failed to build response: {}", e);
@@ -261,6 +712,243 @@ async fn serve_content(
     }
 }
 
+/// Strong validator for a file, from its modification time and size
+fn stream_etag(last_modified: Option<SystemTime>, file_size: u64) -> String {
+    let modified_nanos = last_modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|since_epoch| since_epoch.as_nanos())
+        .unwrap_or(0);
+    format!("\"{:x}-{:x}\"", modified_nanos, file_size)
+}
+
+/// `ETag`, `Last-Modified` and `Cache-Control` sent with every stream response
+///
+/// `no-cache` lets clients keep a copy but makes them revalidate it, which the
+/// validators turn into a cheap 304.
+fn validator_headers(stream_info: &StreamInfo) -> Vec<(&'static str, String)> {
+    let mut headers = vec![
+        ("ETag", stream_info.etag.clone()),
+        ("Cache-Control", "private, no-cache".to_string()),
+    ];
+    if let Some(modified) = stream_info.last_modified {
+        headers.push(("Last-Modified", http_date(modified)));
+    }
+    headers
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn http_date(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+/// Whole seconds since the epoch, the precision of HTTP dates
+fn http_date_secs(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
+fn parse_http_date_secs(value: &str) -> Option<u64> {
+    chrono::DateTime::parse_from_rfc2822(value)
+        .ok()
+        .and_then(|date| u64::try_from(date.timestamp()).ok())
+}
+
+/// Whether a request can be answered with 304 Not Modified
+///
+/// `If-None-Match` is compared weakly and takes precedence over
+/// `If-Modified-Since` (RFC 9110 section 13.2.2).
+fn is_not_modified(headers: &HeaderMap, etag: &str, last_modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = header_str(headers, "if-none-match") {
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+    }
+
+    let since = header_str(headers, "if-modified-since").and_then(parse_http_date_secs);
+    match (since, last_modified.and_then(http_date_secs)) {
+        (Some(since), Some(modified)) => modified <= since,
+        _ => false,
+    }
+}
+
+/// Whether a `Range` request's `If-Range` precondition still holds
+///
+/// An entity tag must match strongly and a date exactly; otherwise the client's
+/// partial copy is stale and the full file should be sent.
+fn if_range_satisfied(headers: &HeaderMap, etag: &str, last_modified: Option<SystemTime>) -> bool {
+    let if_range = match header_str(headers, "if-range") {
+        Some(value) => value.trim(),
+        None => return true,
+    };
+
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        return if_range == etag;
+    }
+    match (
+        parse_http_date_secs(if_range),
+        last_modified.and_then(http_date_secs),
+    ) {
+        (Some(date), Some(modified)) => date == modified,
+        _ => false,
+    }
+}
+
+/// Stable proxy id for a thumbnail URL
+fn thumbnail_id(url: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    let digest = Sha256::digest(url.as_bytes());
+    digest[..16].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Serves a registered thumbnail, fetching and caching it on first use
+///
+/// A cold thumbnail is fetched through the image gateways in order and then
+/// from its own URL; the first image any of them returns is cached.
+async fn serve_thumbnail(
+    id: String,
+    thumbnails: Arc<RwLock<ThumbnailProxy>>,
+    http_client: reqwest::Client,
+) -> std::result::Result<warp::reply::Response, Rejection> {
+    let (cached, fetch_urls) = {
+        let mut proxy = thumbnails.write().await;
+        let cached = proxy.cache.get(&id);
+        let source = proxy.sources.get(&id);
+        (cached, source.map(|url| proxy.fetch_urls(&url)))
+    };
+
+    let thumbnail = match (cached, fetch_urls) {
+        (Some(thumbnail), _) => thumbnail,
+        (None, Some(urls)) => match fetch_thumbnail_chain(&http_client, &urls).await {
+            Ok(thumbnail) => {
+                thumbnails.write().await.cache.insert(id, thumbnail.clone());
+                thumbnail
+            }
+            Err(e) => {
+                warn!("Thumbnail proxy fetch failed on every route: {}", e);
+                return Ok(
+                    warp::reply::with_status("Bad Gateway", StatusCode::BAD_GATEWAY)
+                        .into_response(),
+                );
+            }
+        },
+        (None, None) => {
+            return Ok(
+                warp::reply::with_status("Thumbnail not found", StatusCode::NOT_FOUND)
+                    .into_response(),
+            );
+        }
+    };
+
+    match warp::http::Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", &thumbnail.content_type)
+        .header("Content-Length", thumbnail.data.len().to_string())
+        .header("Cache-Control", "max-age=86400")
+        .body(thumbnail.data.to_vec())
+    {
+        Ok(resp) => Ok(resp.into_response()),
+        Err(e) => {
+            error!("Failed to build thumbnail response: {}", e);
+            Ok(warp::reply::with_status(
+                "Internal Server Error",
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+            .into_response())
+        }
+    }
+}
+
+/// Tries each URL in turn and returns the first image fetched; errors with the last failure
+async fn fetch_thumbnail_chain(
+    client: &reqwest::Client,
+    urls: &[String],
+) -> Result<CachedThumbnail> {
+    let mut last_error = KiyyaError::Internal {
+        message: "No thumbnail route to try".to_string(),
+    };
+    for url in urls {
+        match fetch_thumbnail(client, url).await {
+            Ok(thumbnail) => return Ok(thumbnail),
+            Err(e) => {
+                debug!("Thumbnail route {} failed: {}", url, e);
+                last_error = e;
+            }
+        }
+    }
+    Err(last_error)
+}
+
+/// Downloads an image, rejecting non-images and anything over `MAX_THUMBNAIL_BYTES`
+async fn fetch_thumbnail(client: &reqwest::Client, url: &str) -> Result<CachedThumbnail> {
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    if response.status().is_redirection() {
+        return Err(KiyyaError::ContentParsing {
+            message: format!("Thumbnail route redirected with {}", response.status()),
+        });
+    }
+
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    if !content_type.starts_with("image/") {
+        return Err(KiyyaError::ContentParsing {
+            message: format!("Thumbnail has non-image content type '{}'", content_type),
+        });
+    }
+
+    let too_large = || KiyyaError::ContentParsing {
+        message: format!("Thumbnail exceeds {} bytes", MAX_THUMBNAIL_BYTES),
+    };
+    if response.content_length().unwrap_or(0) > MAX_THUMBNAIL_BYTES {
+        return Err(too_large());
+    }
+
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if (data.len() + chunk.len()) as u64 > MAX_THUMBNAIL_BYTES {
+            return Err(too_large());
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    Ok(CachedThumbnail {
+        content_type,
+        data: Arc::new(data),
+    })
+}
+
+/// Maps requests no route accepted to a JSON error with a proper status code
+///
+/// Unknown paths get 404 and known paths with the wrong method get 405, so
+/// clients see a clear answer instead of warp's bare default rejection.
+async fn handle_rejection(
+    rejection: Rejection,
+) -> std::result::Result<warp::reply::Response, Infallible> {
+    let (status, message) = if rejection.is_not_found() {
+        (StatusCode::NOT_FOUND, "Not found")
+    } else if rejection.find::<warp::reject::MethodNotAllowed>().is_some() {
+        (StatusCode::METHOD_NOT_ALLOWED, "Method not allowed")
+    } else {
+        warn!("Unhandled local server rejection: {:?}", rejection);
+        (StatusCode::BAD_REQUEST, "Bad request")
+    };
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({ "error": message })),
+        status,
+    )
+    .into_response())
+}
+
 /// Health check endpoint - returns 200 OK if server is running
 async fn health_check(
     _active_streams: Arc<RwLock<HashMap<String, StreamInfo>>>,
@@ -363,7 +1051,28 @@ fn parse_range_header(range: &str, file_size: u64) -> Result<(u64, u64)> {
     Ok((start, end))
 }
 
+/// Response body that decrypts one chunk ahead of what the client has read
+///
+/// A chunk that fails authentication ends the body early, so the client sees
+/// a truncated response rather than corrupt bytes.
+fn decrypted_body(decryptor: RangeDecryptor) -> Body {
+    let pieces = futures_util::stream::unfold(Some(decryptor), |state| async move {
+        let mut decryptor = state?;
+        match decryptor.next_piece().await {
+            Ok(Some(piece)) => Some((Ok(piece), Some(decryptor))),
+            Ok(None) => None,
+            Err(e) => {
+                error!("Failed to decrypt stream chunk: {}", e);
+                let error = std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string());
+                Some((Err(error), None))
+            }
+        }
+    });
+    Body::wrap_stream(pieces)
+}
+
 async fn read_file_range(file_path: &PathBuf, start: u64, end: u64) -> Result<Vec<u8>> {
+    let started = Instant::now();
     let mut file = File::open(file_path).await?;
     file.seek(SeekFrom::Start(start)).await?;
 
@@ -372,6 +1081,7 @@ async fn read_file_range(file_path: &PathBuf, start: u64, end: u64) -> Result<Ve
 
     let bytes_read = file.read(&mut buffer).await?;
     buffer.truncate(bytes_read);
+    disk_io::STREAM_READS.record(bytes_read, started.elapsed());
 
     Ok(buffer)
 }
@@ -1190,6 +1900,214 @@ mod tests {
         server.stop().await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_unknown_path_returns_404() {
+        let mut server = LocalServer::new().await.unwrap();
+        let port = server.start().await.unwrap();
+
+        let client = reqwest::Client::new();
+        for path in ["/nope", "/movies", "/movies/a/b", "/health/extra"] {
+            let response = client
+                .get(format!("http://127.0.0.1:{}{}", port, path))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 404, "GET {}", path);
+            let json: serde_json::Value = response.json().await.unwrap();
+            assert_eq!(json["error"], "Not found");
+        }
+
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_non_get_on_stream_path_returns_405() {
+        let mut server = LocalServer::new().await.unwrap();
+        let port = server.start().await.unwrap();
+
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("movie.mp4");
+        write(&file_path, b"not really a movie").await.unwrap();
+        server
+            .register_content("method-test", file_path, false)
+            .await
+            .unwrap();
+
+        let client = reqwest::Client::new();
+        let url = format!("http://127.0.0.1:{}/movies/method-test", port);
+
+        let response = client.post(&url).body("data").send().await.unwrap();
+        assert_eq!(response.status(), 405);
+        let json: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(json["error"], "Method not allowed");
+
+        let response = client.delete(&url).send().await.unwrap();
+        assert_eq!(response.status(), 405);
+
+        // GET and HEAD still serve the stream
+        assert_eq!(client.get(&url).send().await.unwrap().status(), 200);
+        assert_eq!(client.head(&url).send().await.unwrap().status(), 200);
+
+        server.stop().await.unwrap();
+    }
+
+    /// Starts a server with one registered plain file and returns its URL
+    async fn serve_test_file(uuid: &str, content: &[u8]) -> (LocalServer, TempDir, String) {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("movie.mp4");
+        write(&file_path, content).await.unwrap();
+
+        let mut server = LocalServer::new().await.unwrap();
+        let port = server.start().await.unwrap();
+        server
+            .register_content(uuid, file_path, false)
+            .await
+            .unwrap();
+
+        let url = format!("http://127.0.0.1:{}/movies/{}", port, uuid);
+        (server, temp_dir, url)
+    }
+
+    #[tokio::test]
+    async fn test_conditional_requests_return_304() {
+        let content: Vec<u8> = (0..4096).map(|i| (i % 251) as u8).collect();
+        let (mut server, _temp_dir, url) = serve_test_file("conditional-test", &content).await;
+        let client = reqwest::Client::new();
+
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        let etag = response.headers()["etag"].to_str().unwrap().to_string();
+        let last_modified = response.headers()["last-modified"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+        assert_eq!(response.headers()["cache-control"], "private, no-cache");
+        assert_eq!(response.bytes().await.unwrap().as_ref(), content.as_slice());
+
+        // Matching validators skip the body
+        let response = client
+            .get(&url)
+            .header("If-None-Match", &etag)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 304);
+        assert_eq!(response.headers()["etag"].to_str().unwrap(), etag);
+        assert!(response.bytes().await.unwrap().is_empty());
+
+        let response = client
+            .get(&url)
+            .header("If-None-Match", format!("\"other\", W/{}", etag))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 304);
+
+        let response = client
+            .get(&url)
+            .header("If-Modified-Since", &last_modified)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 304);
+
+        // Stale validators get the full content
+        let response = client
+            .get(&url)
+            .header("If-None-Match", "\"stale\"")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().len(), content.len());
+
+        let response = client
+            .get(&url)
+            .header("If-Modified-Since", "Mon, 01 Jan 2001 00:00:00 GMT")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        // If-None-Match wins over a matching If-Modified-Since
+        let response = client
+            .get(&url)
+            .header("If-None-Match", "\"stale\"")
+            .header("If-Modified-Since", &last_modified)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_range_requests_with_etag() {
+        let content: Vec<u8> = (0..4096).map(|i| (i % 251) as u8).collect();
+        let (mut server, _temp_dir, url) = serve_test_file("range-etag-test", &content).await;
+        let client = reqwest::Client::new();
+
+        let full = client.get(&url).send().await.unwrap();
+        let etag = full.headers()["etag"].to_str().unwrap().to_string();
+
+        // Partial responses carry the same validator as the full file
+        let response = client
+            .get(&url)
+            .header("Range", "bytes=0-99")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 206);
+        assert_eq!(response.headers()["etag"].to_str().unwrap(), etag);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), &content[..100]);
+
+        // If-Range with the current ETag keeps the range
+        let response = client
+            .get(&url)
+            .header("Range", "bytes=100-199")
+            .header("If-Range", &etag)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 206);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), &content[100..200]);
+
+        // A stale If-Range means the client's copy changed: send everything
+        let response = client
+            .get(&url)
+            .header("Range", "bytes=100-199")
+            .header("If-Range", "\"stale\"")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), content.as_slice());
+
+        // Weak validators never satisfy If-Range
+        let response = client
+            .get(&url)
+            .header("Range", "bytes=100-199")
+            .header("If-Range", format!("W/{}", etag))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        // A matching If-None-Match answers 304 even for a range
+        let response = client
+            .get(&url)
+            .header("Range", "bytes=0-99")
+            .header("If-None-Match", &etag)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 304);
+
+        server.stop().await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_status_endpoint_no_streams() {
         let mut server = LocalServer::new().await.unwrap();
@@ -1306,4 +2224,361 @@ mod tests {
 
         server.stop().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_prebuffered_range_is_served_from_memory() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test_video.mp4");
+        let content: Vec<u8> = (0..4000).map(|i| (i % 256) as u8).collect();
+        write(&file_path, &content).await.unwrap();
+
+        let mut server = LocalServer::new().await.unwrap();
+        let port = server.start().await.unwrap();
+        server
+            .register_content("test-video", file_path.clone(), false)
+            .await
+            .unwrap();
+
+        assert_eq!(server.prebuffer("test-video", 1000).await.unwrap(), 1000);
+        // A second call reuses the buffer instead of reading the file again
+        assert_eq!(server.prebuffer("test-video", 500).await.unwrap(), 1000);
+        assert!(server.prebuffer("missing", 1000).await.is_err());
+
+        // Overwrite the file so only the buffer can produce the original bytes
+        write(&file_path, vec![0u8; 4000]).await.unwrap();
+
+        let client = reqwest::Client::new();
+        let url = format!("http://127.0.0.1:{}/movies/test-video", port);
+        let body = client
+            .get(&url)
+            .header("Range", "bytes=100-199")
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(&body[..], &content[100..200]);
+
+        // Ranges past the buffer still come from disk
+        let body = client
+            .get(&url)
+            .header("Range", "bytes=2000-2099")
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(&body[..], &[0u8; 100][..]);
+
+        server.unregister_content("test-video").await.unwrap();
+        assert!(server.prebuffered.read().await.is_empty());
+        server.stop().await.unwrap();
+    }
+
+    #[test]
+    fn test_prebuffer_cache_evicts_least_recently_used_and_idle() {
+        let mut cache = PrebufferCache {
+            max_streams: 3,
+            max_total_bytes: 100,
+            ..PrebufferCache::default()
+        };
+        let start = Instant::now();
+        let at = |secs| start + std::time::Duration::from_secs(secs);
+
+        cache.insert("a".to_string(), Arc::new(vec![0; 40]), at(0));
+        cache.insert("b".to_string(), Arc::new(vec![0; 40]), at(1));
+        assert!(cache.get("a", at(2)).is_some());
+
+        // Over the byte cap, so the least recently used "b" goes
+        cache.insert("c".to_string(), Arc::new(vec![0; 40]), at(3));
+        assert!(cache.get("b", at(4)).is_none());
+        assert_eq!(cache.total_bytes, 80);
+
+        // Over the stream cap, so "a" goes
+        cache.insert("d".to_string(), Arc::new(vec![0; 10]), at(5));
+        cache.insert("e".to_string(), Arc::new(vec![0; 10]), at(6));
+        assert!(cache.get("a", at(7)).is_none());
+        assert_eq!(cache.entries.len(), 3);
+
+        // Buffers nobody reads from expire
+        let idle = at(6) + PREBUFFER_IDLE_TTL;
+        assert!(cache.get("e", idle).is_none());
+        assert!(cache.is_empty());
+        assert_eq!(cache.total_bytes, 0);
+    }
+
+    async fn spawn_upstream(
+        body: Vec<u8>,
+        content_type: &'static str,
+    ) -> (String, tokio::task::JoinHandle<()>) {
+        let route = warp::path!("thumb.png").map(move || {
+            warp::http::Response::builder()
+                .header("Content-Type", content_type)
+                .body(body.clone())
+                .unwrap()
+        });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        (format!("http://{}/thumb.png", addr), tokio::spawn(server))
+    }
+
+    /// Image gateway answering every path under `/plain/` with `body`
+    async fn spawn_image_gateway(
+        body: Vec<u8>,
+        content_type: &'static str,
+    ) -> (String, tokio::task::JoinHandle<()>) {
+        let route = warp::path("plain")
+            .and(warp::path::tail())
+            .map(move |_: warp::path::Tail| {
+                warp::http::Response::builder()
+                    .header("Content-Type", content_type)
+                    .body(body.clone())
+                    .unwrap()
+            });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        (format!("http://{}/plain/", addr), tokio::spawn(server))
+    }
+
+    /// URL on a port nothing listens on, standing in for a blocked CDN
+    async fn blocked_url() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        format!("http://{}/thumb.png", addr)
+    }
+
+    async fn server_with_gateways(gateways: Vec<String>) -> LocalServer {
+        let mut server = LocalServer::new().await.unwrap();
+        server.thumbnails.write().await.gateways = gateways;
+        server.start().await.unwrap();
+        server
+    }
+
+    #[tokio::test]
+    async fn test_cold_thumbnail_fetched_through_gateway_when_direct_is_blocked() {
+        let image = vec![0x89, b'P', b'N', b'G', 5, 6, 7, 8];
+        let (down_gateway, down) = spawn_image_gateway(Vec::new(), "image/png").await;
+        down.abort();
+        let _ = down.await;
+        let (gateway, gateway_server) = spawn_image_gateway(image.clone(), "image/png").await;
+
+        let mut server = server_with_gateways(vec![down_gateway, gateway]).await;
+        let direct_url = blocked_url().await;
+        let client = reqwest::Client::new();
+        assert!(client.get(&direct_url).send().await.is_err());
+
+        // Nothing is cached: the first request has to go through the gateway
+        let proxied_url = server.register_thumbnail(&direct_url).await.unwrap();
+        let response = client.get(&proxied_url).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "image/png");
+        assert_eq!(response.bytes().await.unwrap().to_vec(), image);
+
+        gateway_server.abort();
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_thumbnail_proxy_does_not_follow_redirects() {
+        let image = vec![0x89, b'P', b'N', b'G', 9];
+        let (target_url, target) = spawn_upstream(image, "image/png").await;
+        // Every gateway request is redirected to a loopback image
+        let redirect = warp::any().map(move || {
+            warp::http::Response::builder()
+                .status(StatusCode::FOUND)
+                .header("Location", &target_url)
+                .body(Vec::new())
+                .unwrap()
+        });
+        let (addr, redirector) = warp::serve(redirect).bind_ephemeral(([127, 0, 0, 1], 0));
+        let redirector = tokio::spawn(redirector);
+
+        let mut server = server_with_gateways(vec![format!("http://{}/plain/", addr)]).await;
+        let proxied_url = server
+            .register_thumbnail(&blocked_url().await)
+            .await
+            .unwrap();
+        let response = reqwest::get(&proxied_url).await.unwrap();
+        assert_eq!(response.status(), 502);
+
+        redirector.abort();
+        target.abort();
+        server.stop().await.unwrap();
+    }
+
+    #[test]
+    fn test_thumbnail_maps_evict_least_recently_used() {
+        let mut map = LruMap::new(2);
+        map.insert("a".to_string(), 1);
+        map.insert("b".to_string(), 2);
+        assert_eq!(map.get("a"), Some(1));
+
+        // "b" is the least recently used once "a" was read
+        map.insert("c".to_string(), 3);
+        assert_eq!(map.entries.len(), 2);
+        assert_eq!(map.get("b"), None);
+        assert_eq!(map.get("a"), Some(1));
+        assert_eq!(map.get("c"), Some(3));
+
+        // Re-inserting a key refreshes it instead of growing the map
+        map.insert("a".to_string(), 10);
+        map.insert("d".to_string(), 4);
+        assert_eq!(map.entries.len(), 2);
+        assert_eq!(map.get("c"), None);
+        assert_eq!(map.get("a"), Some(10));
+
+        let proxy = ThumbnailProxy::default();
+        assert_eq!(proxy.sources.capacity, MAX_THUMBNAIL_SOURCES);
+        assert_eq!(proxy.cache.capacity, MAX_CACHED_THUMBNAILS);
+    }
+
+    #[tokio::test]
+    async fn test_thumbnail_served_through_proxy_when_direct_is_blocked() {
+        let image = vec![0x89, b'P', b'N', b'G', 1, 2, 3, 4];
+        let (upstream_url, upstream) = spawn_upstream(image.clone(), "image/png").await;
+
+        let mut server = server_with_gateways(Vec::new()).await;
+        let proxied_url = server.register_thumbnail(&upstream_url).await.unwrap();
+        assert!(proxied_url.starts_with("http://127.0.0.1:"));
+        assert!(!proxied_url.contains("thumb.png"));
+
+        let client = reqwest::Client::new();
+        let response = client.get(&proxied_url).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "image/png");
+        assert_eq!(response.bytes().await.unwrap().to_vec(), image);
+
+        // Block the direct route; the proxy keeps serving its cached copy
+        upstream.abort();
+        let _ = upstream.await;
+        assert!(client.get(&upstream_url).send().await.is_err());
+
+        let response = client.get(&proxied_url).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().to_vec(), image);
+
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_thumbnail_proxy_rejects_unregistered_and_non_images() {
+        let (upstream_url, upstream) = spawn_upstream(b"<html>".to_vec(), "text/html").await;
+
+        let mut server = server_with_gateways(Vec::new()).await;
+        let port = server.port.unwrap();
+        let client = reqwest::Client::new();
+
+        let unknown = format!("http://127.0.0.1:{}/thumbnails/{}", port, thumbnail_id("x"));
+        assert_eq!(client.get(&unknown).send().await.unwrap().status(), 404);
+
+        let proxied_url = server.register_thumbnail(&upstream_url).await.unwrap();
+        assert_eq!(client.get(&proxied_url).send().await.unwrap().status(), 502);
+
+        upstream.abort();
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dead_server_is_detected_and_restarted() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("watchdog.mp4");
+        write(&file_path, b"watchdog video").await.unwrap();
+
+        let mut server = LocalServer::new().await.unwrap();
+        let old_port = server.ensure_running().await.unwrap();
+        assert!(server.is_responsive().await);
+        server
+            .register_content("watchdog-uuid", file_path, false)
+            .await
+            .unwrap();
+
+        // Kill the accept loop without telling the server, as a panic would
+        server.server_handle.as_ref().unwrap().abort();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!server.is_responsive().await);
+
+        let new_port = server.ensure_running().await.unwrap();
+        assert_ne!(new_port, old_port);
+        assert!(server.is_responsive().await);
+
+        let response = reqwest::get(format!("http://127.0.0.1:{}/movies/watchdog-uuid", new_port))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"watchdog video");
+
+        // A healthy server is left alone
+        assert_eq!(server.ensure_running().await.unwrap(), new_port);
+
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_large_encrypted_file_is_streamed() {
+        use crate::encryption::EncryptionManager;
+
+        let temp_dir = TempDir::new().unwrap();
+        let plain_file = temp_dir.path().join("source.mp4");
+        let encrypted_file = temp_dir.path().join("large.bin");
+
+        // 8MB spans 128 encryption chunks
+        let original_content: Vec<u8> = (0..8 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        write(&plain_file, &original_content).await.unwrap();
+
+        let mut encryption_manager = EncryptionManager::new().unwrap();
+        encryption_manager
+            .enable_encryption("test_chunked_streaming")
+            .unwrap();
+        encryption_manager
+            .encrypt_file(&plain_file, &encrypted_file)
+            .await
+            .unwrap();
+
+        let mut server = LocalServer::new().await.unwrap();
+        server
+            .encryption_manager
+            .lock()
+            .await
+            .enable_encryption("test_chunked_streaming")
+            .unwrap();
+        let port = server.start().await.unwrap();
+        server
+            .register_content("chunked-video", encrypted_file.clone(), true)
+            .await
+            .unwrap();
+
+        let client = reqwest::Client::new();
+        let url = format!("http://127.0.0.1:{}/movies/chunked-video", port);
+
+        // A range in the middle, starting and ending inside chunks
+        let (start, end) = (1_000_000usize, 6_500_000usize);
+        let response = client
+            .get(&url)
+            .header("Range", format!("bytes={}-{}", start, end))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 206);
+        assert_eq!(
+            response.headers()["content-range"],
+            format!("bytes {}-{}/{}", start, end, original_content.len()).as_str()
+        );
+        assert_eq!(response.content_length(), Some((end - start + 1) as u64));
+
+        let received = response.bytes().await.unwrap();
+        assert_eq!(received.to_vec(), &original_content[start..=end]);
+
+        // The full file reports the plaintext length, not the ciphertext length
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.content_length(),
+            Some(original_content.len() as u64)
+        );
+        assert_eq!(response.bytes().await.unwrap().to_vec(), original_content);
+
+        server.stop().await.unwrap();
+        encryption_manager.disable_encryption().unwrap();
+    }
 }
@@ -0,0 +1,202 @@
+/// Property-Based Tests for Exact Tag Matching
+///
+/// For any tag filter, only items carrying exactly that tag should match.
+/// Tags that merely contain the filter as a substring (e.g. "movies" for
+/// "movie") or that wrap it in JSON special characters (e.g. `"movie"`)
+/// must never match.
+
+#[cfg(test)]
+mod tag_matching_property_tests {
+    use crate::database::tests::create_test_database_with_ttl;
+    use crate::models::{CacheQuery, CompatibilityInfo, ContentItem};
+    use chrono::Utc;
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    /// Helper to create a content item with specific claim_id and tags
+    fn create_tagged_item(claim_id: &str, tags: Vec<String>) -> ContentItem {
+        let mut item = ContentItem {
+            claim_id: claim_id.to_string(),
+            title: format!("Item {}", claim_id),
+            description: None,
+            tags,
+            thumbnail_url: None,
+            duration: None,
+            release_time: Utc::now().timestamp(),
+            video_urls: HashMap::new(),
+            compatibility: CompatibilityInfo {
+                compatible: true,
+                reason: None,
+                fallback_available: false,
+            },
+            etag: None,
+            content_hash: None,
+            raw_json: None,
+            stale: false,
+        };
+        item.update_content_hash();
+        item
+    }
+
+    fn tag_query(tag: &str) -> CacheQuery {
+        CacheQuery {
+            tags: Some(vec![tag.to_string()]),
+            text_search: None,
+            limit: Some(50),
+            offset: Some(0),
+            order_by: Some("releaseTime DESC".to_string()),
+        }
+    }
+
+    /// Strategy for generating a base tag that passes tag sanitization
+    fn base_tag_strategy() -> impl Strategy<Value = String> {
+        "[a-z][a-z_]{2,11}".prop_map(|s| s)
+    }
+
+    /// Strategy for generating tags that look like `base` but are not equal to it
+    fn adversarial_tags_strategy(base: String) -> impl Strategy<Value = Vec<String>> {
+        let variants = vec![
+            format!("{}s", base),
+            format!("x{}", base),
+            format!("{}_{}", base, base),
+            base[..base.len() - 1].to_string(),
+            format!("\"{}\"", base),
+            format!("{}\"", base),
+            format!("\"{}", base),
+            format!("{},", base),
+            format!("[\"{}\"]", base),
+            format!("{}\\", base),
+        ];
+        proptest::sample::subsequence(variants, 1..=10)
+    }
+
+    fn base_and_adversarial() -> impl Strategy<Value = (String, Vec<String>)> {
+        base_tag_strategy().prop_flat_map(|base| {
+            let adversarial = adversarial_tags_strategy(base.clone());
+            (Just(base), adversarial)
+        })
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(20))]
+
+        /// Property: Filtering by a tag only returns items tagged with exactly that tag
+        #[test]
+        fn prop_tag_filter_matches_exactly(
+            (base, adversarial) in base_and_adversarial(),
+        ) {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let (db, _temp_dir, _db_path) = create_test_database_with_ttl(1800);
+
+                let exact = create_tagged_item("exact", vec![base.clone()]);
+                let decoy = create_tagged_item("decoy", adversarial.clone());
+                db.store_content_items(vec![exact, decoy]).await.unwrap();
+
+                let results = db.get_cached_content(tag_query(&base)).await.unwrap();
+                prop_assert_eq!(results.len(), 1, "Only the exact tag should match {:?}", adversarial);
+                prop_assert_eq!(&results[0].claim_id, "exact");
+
+                Ok(())
+            })?;
+        }
+
+        /// Property: Chunked queries apply the same exact tag matching
+        #[test]
+        fn prop_chunked_query_matches_exactly(
+            (base, adversarial) in base_and_adversarial(),
+        ) {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let (db, _temp_dir, _db_path) = create_test_database_with_ttl(1800);
+
+                let exact = create_tagged_item("exact", vec![base.clone()]);
+                let decoy = create_tagged_item("decoy", adversarial.clone());
+                db.store_content_items(vec![exact, decoy]).await.unwrap();
+
+                let seen = Arc::new(Mutex::new(Vec::new()));
+                let seen_clone = seen.clone();
+                db.query_content_chunked(tag_query(&base), 10, move |chunk| {
+                    seen_clone
+                        .lock()
+                        .unwrap()
+                        .extend(chunk.into_iter().map(|item| item.claim_id));
+                    Ok(())
+                })
+                .await
+                .unwrap();
+
+                let seen = seen.lock().unwrap().clone();
+                prop_assert_eq!(seen, vec!["exact".to_string()]);
+
+                Ok(())
+            })?;
+        }
+
+        /// Property: Tag invalidation only removes items tagged with exactly that tag
+        #[test]
+        fn prop_tag_invalidation_matches_exactly(
+            (base, adversarial) in base_and_adversarial(),
+        ) {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let (db, _temp_dir, _db_path) = create_test_database_with_ttl(1800);
+
+                let exact = create_tagged_item("exact", vec![base.clone()]);
+                let decoy = create_tagged_item("decoy", adversarial.clone());
+                db.store_content_items(vec![exact, decoy]).await.unwrap();
+
+                let removed = db.invalidate_cache_by_tags(vec![base.clone()]).await.unwrap();
+                prop_assert_eq!(removed, 1);
+
+                let remaining = db.get_cached_content(CacheQuery {
+                    tags: None,
+                    ..tag_query(&base)
+                }).await.unwrap();
+                prop_assert_eq!(remaining.len(), 1);
+                prop_assert_eq!(&remaining[0].claim_id, "decoy");
+
+                Ok(())
+            })?;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_movie_does_not_match_movies() {
+        let (db, _temp_dir, _db_path) = create_test_database_with_ttl(1800);
+
+        let movie = create_tagged_item("movie-item", vec!["movie".to_string()]);
+        let movies = create_tagged_item("movies-item", vec!["movies".to_string()]);
+        db.store_content_items(vec![movie, movies]).await.unwrap();
+
+        let results = db.get_cached_content(tag_query("movie")).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].claim_id, "movie-item");
+
+        let results = db.get_cached_content(tag_query("movies")).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].claim_id, "movies-item");
+    }
+
+    #[tokio::test]
+    async fn test_single_tag_array_matches() {
+        let (db, _temp_dir, _db_path) = create_test_database_with_ttl(1800);
+
+        // A single-element tag array has neither a leading nor trailing comma
+        let item = create_tagged_item("solo", vec!["series".to_string()]);
+        db.store_content_items(vec![item]).await.unwrap();
+
+        let results = db.get_cached_content(tag_query("series")).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].claim_id, "solo");
+    }
+
+    #[tokio::test]
+    async fn test_quoted_tag_filter_is_rejected() {
+        let (db, _temp_dir, _db_path) = create_test_database_with_ttl(1800);
+
+        let result = db.get_cached_content(tag_query("movie\"")).await;
+        assert!(result.is_err(), "Tags with JSON special characters should be rejected");
+    }
+}
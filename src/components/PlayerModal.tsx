@@ -3,9 +3,10 @@ import * as PlyrNamespace from 'plyr';
 import Hls from 'hls.js';
 import { X, Settings, AlertCircle, ExternalLink } from 'lucide-react';
 import { ContentItem, Quality, QUALITY_LEVELS } from '../types';
-import { saveProgress, getProgress, streamOffline, openExternal } from '../lib/api';
+import { saveProgress, getProgress, streamOffline, openExternal, getContentQualityPref, setContentQualityPref } from '../lib/api';
 import { nextLowerQuality, qualityScore } from '../types';
 import { scheduleIdleTask } from '../lib/idle';
+import { chooseInitialQuality } from '../lib/quality';
 import { checkContentCompatibility, isHLSSupported, isMP4CodecSupported } from '../lib/codec';
 import 'plyr/dist/plyr.css';
 
@@ -46,6 +47,9 @@ export default function PlayerModal({
   
   // CDN Playback: Default to "master" quality for HLS adaptive streaming
   const [currentQuality, setCurrentQuality] = useState<string>(initialQuality || 'master');
+  // False while the item's remembered quality is loading, so the player
+  // starts in the right quality instead of switching mid-load
+  const [qualityReady, setQualityReady] = useState<boolean>(!!initialQuality);
   const [availableQualities, setAvailableQualities] = useState<string[]>([]);
   const [isBuffering, setIsBuffering] = useState(false);
   const [error, setError] = useState<string | null>(null);
@@ -132,7 +136,27 @@ export default function PlayerModal({
       const defaultQuality = qualities.includes('master') ? 'master' : 
                             qualities.includes('720p') ? '720p' : qualities[0];
       setCurrentQuality(defaultQuality);
+
+      // A quality remembered for this item is read before the player starts
+      let cancelled = false;
+      setQualityReady(false);
+      getContentQualityPref(content.claim_id)
+        .catch((err) => {
+          console.error('Failed to load quality preference:', err);
+          return null;
+        })
+        .then((contentQuality) => {
+          if (cancelled) return;
+          if (contentQuality && qualities.includes(contentQuality)) {
+            setCurrentQuality(chooseInitialQuality({ availableQualities: qualities, contentQuality }));
+          }
+          setQualityReady(true);
+        });
+      return () => {
+        cancelled = true;
+      };
     }
+    setQualityReady(true);
   }, [content, initialQuality]);
 
   // Load saved progress
@@ -155,7 +179,7 @@ export default function PlayerModal({
 
   // Initialize player
   useEffect(() => {
-    if (!isOpen || !videoRef.current) return;
+    if (!isOpen || !videoRef.current || !qualityReady) return;
 
     const initPlayer = async () => {
       try {
@@ -337,7 +361,7 @@ export default function PlayerModal({
         videoRef.current.removeEventListener('waiting', handleBuffering);
       }
     };
-  }, [isOpen, currentQuality, content, isOffline, availableQualities]); // Removed handleBuffering from dependencies
+  }, [isOpen, currentQuality, content, isOffline, availableQualities, qualityReady]); // Removed handleBuffering from dependencies
 
   // Save progress periodically
   useEffect(() => {
@@ -406,6 +430,11 @@ export default function PlayerModal({
     setShowQualityMenu(false);
     bufferingCountRef.current = 0;
     bufferingWindowRef.current = Date.now();
+
+    // A quality picked by hand is remembered for this item
+    setContentQualityPref(content.claim_id, quality).catch((err) => {
+      console.error('Failed to save quality preference:', err);
+    });
   };
 
   // Handle keyboard navigation for quality menu
@@ -9,7 +9,30 @@ import {
   CacheStats,
   MemoryStats,
   DownloadRequest,
-  StreamOfflineResponse
+  DownloadAvailability,
+  DownloadState,
+  OfflineDuration,
+  QualitySelection,
+  RegionBlock,
+  SyncChanges,
+  StreamOfflineResponse,
+  ServerStatus,
+  ConsistencyReport,
+  OfflineMetadata,
+  OfflineSort,
+  OfflineFilter,
+  PlaylistNeighbors,
+  SecurityEventRecord,
+  EncryptionBenchmark,
+  DiskIoStats,
+  SchemaSummary,
+  ClaimIdValidation,
+  ChannelClaimsResult,
+  WarmCacheTarget,
+  WarmCacheSummary,
+  SearchHit,
+  DiffResult,
+  InflightRequestInfo
 } from '../types';
 
 /**
@@ -195,6 +218,9 @@ export const fetchChannelClaims = async (params: {
   page?: number;
   force_refresh?: boolean;
   stream_types?: string[];
+  include_trace?: boolean;
+  // false fetches without writing the results to the local cache
+  persist?: boolean;
 }): Promise<ContentItem[]> => {
   return await fetchWithRetry(async () => {
     try {
@@ -218,7 +244,9 @@ export const fetchChannelClaims = async (params: {
         limit: params.limit,
         page: params.page,
         forceRefresh: params.force_refresh,
-        streamTypes: params.stream_types
+        streamTypes: params.stream_types,
+        includeTrace: params.include_trace,
+        persist: params.persist
       });
       
       const timeoutPromise = new Promise((_, reject) => 
@@ -277,6 +305,36 @@ export const fetchChannelClaims = async (params: {
   }, RETRY_CONFIGS.category);
 };
 
+// Like fetchChannelClaims, plus where the items came from and how old they are
+export const fetchChannelClaimsWithFreshness = async (params: {
+  any_tags?: string[];
+  text?: string;
+  limit?: number;
+  page?: number;
+  force_refresh?: boolean;
+  stream_types?: string[];
+  persist?: boolean;
+}): Promise<ChannelClaimsResult> => {
+  return await fetchWithRetry(async () => {
+    const result: ChannelClaimsResult = await invoke('fetch_channel_claims_v2', {
+      channelId: CHANNEL_ID,
+      anyTags: params.any_tags,
+      text: params.text,
+      limit: params.limit,
+      page: params.page,
+      forceRefresh: params.force_refresh,
+      streamTypes: params.stream_types,
+      persist: params.persist
+    });
+    return { ...result, items: validateAndFilterContent(result.items) };
+  }, RETRY_CONFIGS.category);
+};
+
+// Prefetch the first page of each channel/tag pair; failures are reported per channel
+export const warmCache = async (targets: WarmCacheTarget[]): Promise<WarmCacheSummary> => {
+  return await invoke('warm_cache', { targets });
+};
+
 export const fetchPlaylists = async (): Promise<Playlist[]> => {
   return await fetchWithRetry(async () => {
     return await invoke('fetch_playlists', {
@@ -285,9 +343,25 @@ export const fetchPlaylists = async (): Promise<Playlist[]> => {
   }, RETRY_CONFIGS.category);
 };
 
-export const resolveClaim = async (claimIdOrUri: string): Promise<ContentItem> => {
+export const buildSeriesPlaylists = async (): Promise<Playlist[]> => {
+  return await invoke('build_series_playlists', { channelId: CHANNEL_ID });
+};
+
+export const getPlaylistNeighbors = async (
+  playlistId: string,
+  claimId: string
+): Promise<PlaylistNeighbors> => {
+  return await invoke('get_playlist_neighbors', { playlistId, claimId });
+};
+
+// persist=false resolves without touching the cache or view history
+export const resolveClaim = async (
+  claimIdOrUri: string,
+  persist?: boolean,
+  forceRefresh?: boolean
+): Promise<ContentItem> => {
   return await fetchWithRetry(async () => {
-    return await invoke('resolve_claim', { claimIdOrUri });  // Tauri converts snake_case to camelCase
+    return await invoke('resolve_claim', { claimIdOrUri, persist, forceRefresh });  // Tauri converts snake_case to camelCase
   }, RETRY_CONFIGS.category);
 };
 
@@ -356,6 +430,11 @@ export const searchContent = async (text: string, limit: number = 50): Promise<C
   return await fetchChannelClaims({ text, limit });
 };
 
+// Searches the local cache; snippets mark the matched text with <mark> tags
+export const searchContentWithSnippets = async (query: string, limit?: number): Promise<SearchHit[]> => {
+  return await invoke('search_content_with_snippets', { query, limit });
+};
+
 export const fetchHeroContent = async (limit: number = 20): Promise<ContentItem[]> => {
   // Uses fetchByTag which calls fetchChannelClaims with retry logic
   // Use hero-specific retry config for critical hero content
@@ -363,6 +442,41 @@ export const fetchHeroContent = async (limit: number = 20): Promise<ContentItem[
 };
 
 // Download management
+
+// Precheck for the download button: anything but 'available' means it should be disabled
+export const isDownloadable = async (claimId: string): Promise<DownloadAvailability> => {
+  return await invoke('is_downloadable', { claimId });
+};
+
+export const isDownloading = async (claimId: string, quality: string): Promise<boolean> => {
+  return await invoke('is_downloading', { claimId, quality });
+};
+
+export const getDownloadStatus = async (claimId: string, quality: string): Promise<DownloadState> => {
+  return await invoke('get_download_status', { claimId, quality });
+};
+
+// Picks the quality to download; preferred defaults to the item's own
+// preference, then the preferred_download_quality setting
+export const resolveBestQuality = async (
+  claimId: string,
+  preferred?: string
+): Promise<QualitySelection> => {
+  return await invoke('resolve_best_quality', { claimId, preferred });
+};
+
+// Remembers the quality one item plays and downloads in; null clears it
+export const setContentQualityPref = async (
+  claimId: string,
+  preferredQuality: string | null
+): Promise<void> => {
+  return await invoke('set_content_quality_pref', { claimId, preferredQuality });
+};
+
+export const getContentQualityPref = async (claimId: string): Promise<string | null> => {
+  return await invoke('get_content_quality_pref', { claimId });
+};
+
 export const downloadMovieQuality = async (params: DownloadRequest): Promise<void> => {
   // Tauri converts snake_case to camelCase
   return await invoke('download_movie_quality', {
@@ -383,6 +497,39 @@ export const streamOffline = async (params: {
   });
 };
 
+export const listOffline = async (
+  sort?: OfflineSort,
+  filter?: OfflineFilter
+): Promise<OfflineMetadata[]> => {
+  return await invoke('list_offline', { sort, filter });
+};
+
+// For "X hours available offline"
+export const getOfflineTotalDuration = async (): Promise<OfflineDuration> => {
+  return await invoke('get_offline_total_duration');
+};
+
+// Reads bytes start..=end of downloaded content, e.g. for a poster frame (max 4 MiB)
+export const readContentRange = async (params: {
+  claim_id: string;
+  quality: string;
+  start: number;
+  end: number;
+}): Promise<Uint8Array> => {
+  const bytes: number[] = await invoke('read_content_range', {
+    claimId: params.claim_id,
+    quality: params.quality,
+    start: params.start,
+    end: params.end
+  });
+  return new Uint8Array(bytes);
+};
+
+// Restarts the local streaming server; earlier stream URLs need the new port
+export const restartLocalServer = async (): Promise<ServerStatus> => {
+  return await invoke('restart_local_server');
+};
+
 export const deleteOffline = async (params: {
   claim_id: string;
   quality: string;
@@ -394,6 +541,14 @@ export const deleteOffline = async (params: {
   });
 };
 
+// Re-encrypts encrypted offline files; resolves to the number of files changed
+export const changeEncryptionPassphrase = async (
+  oldPassphrase: string,
+  newPassphrase: string
+): Promise<number> => {
+  return await invoke('change_encryption_passphrase', { oldPassphrase, newPassphrase });
+};
+
 // Progress tracking
 export const saveProgress = async (params: {
   claim_id: string;
@@ -408,10 +563,20 @@ export const saveProgress = async (params: {
   });
 };
 
+// Imports progress synced from another device; rows older than local progress are skipped
+export const saveProgressBatch = async (items: ProgressData[]): Promise<number> => {
+  return await invoke('save_progress_batch', { items });
+};
+
 export const getProgress = async (claimId: string): Promise<ProgressData | null> => {
   return await invoke('get_progress', { claimId });  // Tauri converts snake_case to camelCase
 };
 
+// Started but unfinished videos, most recently watched first
+export const getContinueWatching = async (limit?: number): Promise<ProgressData[]> => {
+  return await invoke('get_continue_watching', { limit });
+};
+
 // Favorites management
 export const saveFavorite = async (params: {
   claim_id: string;
@@ -430,6 +595,19 @@ export const removeFavorite = async (claimId: string): Promise<void> => {
   return await invoke('remove_favorite', { claimId });  // Tauri converts snake_case to camelCase
 };
 
+// Adds or removes the favorite; resolves to whether it is a favorite afterwards
+export const toggleFavorite = async (params: {
+  claim_id: string;
+  title: string;
+  thumbnail_url?: string;
+}): Promise<boolean> => {
+  return await invoke('toggle_favorite', {
+    claimId: params.claim_id,
+    title: params.title,
+    thumbnailUrl: params.thumbnail_url
+  });
+};
+
 export const getFavorites = async (): Promise<FavoriteItem[]> => {
   return await invoke('get_favorites');
 };
@@ -438,6 +616,12 @@ export const isFavorite = async (claimId: string): Promise<boolean> => {
   return await invoke('is_favorite', { claimId });  // Tauri converts snake_case to camelCase
 };
 
+// Favorites and progress changed after the change sequence position `since`, including
+// deletion tombstones; pass the returned `until` as `since` next time
+export const getChangesSince = async (since: number): Promise<SyncChanges> => {
+  return await invoke('get_changes_since', { since });
+};
+
 // Configuration and settings
 export const getAppConfig = async (): Promise<AppConfig> => {
   return await invoke('get_app_config');
@@ -447,11 +631,69 @@ export const updateSettings = async (settings: Record<string, string>): Promise<
   return await invoke('update_settings', { settings });
 };
 
+export const resetSettingsToDefaults = async (): Promise<AppConfig> => {
+  return await invoke('reset_settings_to_defaults');
+};
+
+export const resetSetting = async (key: string): Promise<AppConfig> => {
+  return await invoke('reset_setting', { key });
+};
+
 // Diagnostics
 export const getDiagnostics = async (): Promise<DiagnosticsData> => {
   return await invoke('get_diagnostics');
 };
 
+// Checks claim ids before a bulk operation; results keep the input order
+export const validateClaimIds = async (ids: string[]): Promise<ClaimIdValidation[]> => {
+  return await invoke('validate_claim_ids', { ids });
+};
+
+// Schema version and migration health for the about screen
+export const getSchemaSummary = async (): Promise<SchemaSummary> => {
+  return await invoke('get_schema_summary');
+};
+
+// Re-runs one migration; the backend restores a backup if the rerun fails.
+// `confirmation` must be the literal token 'RERUN MIGRATION'.
+export const rerunMigration = async (version: number, confirmation: string): Promise<void> => {
+  return await invoke('rerun_migration', { version, confirmation });
+};
+
+// Disk reads/writes and decryption time on the download and playback paths
+export const getDiskIoStats = async (): Promise<DiskIoStats> => {
+  return await invoke('get_disk_io_stats');
+};
+
+// Encrypt/decrypt throughput in MB/s, to compare against content bitrate
+export const benchmarkEncryption = async (sizeMb?: number): Promise<EncryptionBenchmark> => {
+  return await invoke('benchmark_encryption', { sizeMb });
+};
+
+// Last lines of the application log with secrets redacted; capped by the backend
+export const getRecentLogs = async (lines: number): Promise<string[]> => {
+  return await invoke('get_recent_logs', { lines });
+};
+
+// Claims the CDN refused in this region, most recently blocked first
+export const getRegionBlockedClaims = async (limit?: number): Promise<RegionBlock[]> => {
+  return await invoke('get_region_blocked_claims', { limit });
+};
+
+export const getSecurityEvents = async (limit?: number): Promise<SecurityEventRecord[]> => {
+  return await invoke('get_security_events', { limit });
+};
+
+// Gateway requests other identical requests are waiting on
+export const getInflightRequests = async (): Promise<InflightRequestInfo[]> => {
+  return await invoke('get_inflight_requests');
+};
+
+// Recovery tool for a gateway request that never finishes; returns how many were cleared
+export const clearInflightRequests = async (): Promise<number> => {
+  return await invoke('clear_inflight_requests');
+};
+
 // Cache management
 export const getCacheStats = async (): Promise<CacheStats> => {
   return await invoke('get_cache_stats');
@@ -473,14 +715,46 @@ export const invalidateCacheByTags = async (tags: string[]): Promise<void> => {
   return await invoke('invalidate_cache_by_tags', { tags });  // Already camelCase
 };
 
+// Removes one channel's cached items; resolves to the number removed
+export const invalidateChannelCache = async (channelId: string = CHANNEL_ID): Promise<number> => {
+  return await invoke('invalidate_channel_cache', { channelId });
+};
+
+// Cached claim ids only, for diffing against a remote set without loading items
+export const getCacheClaimIds = async (channelId?: string, tag?: string): Promise<string[]> => {
+  return await invoke('get_cache_claim_ids', { channelId, tag });
+};
+
+export const getCacheHashesAll = async (): Promise<Record<string, string>> => {
+  return await invoke('get_cache_hashes_all');
+};
+
+// Sorts a remote listing into new/changed/unchanged claims plus cached claims it no longer contains
+export const diffAgainstRemote = async (items: ContentItem[], channelId?: string): Promise<DiffResult> => {
+  return await invoke('diff_against_remote', { items, channelId });
+};
+
 export const clearAllCache = async (): Promise<void> => {
   return await invoke('clear_all_cache');
 };
 
+// Dry runs: count what the call above would remove without deleting anything
+export const previewInvalidateCacheByTags = async (tags: string[]): Promise<number> => {
+  return await invoke('invalidate_cache_by_tags', { tags, dryRun: true });
+};
+
+export const previewClearAllCache = async (): Promise<number> => {
+  return await invoke('clear_all_cache', { dryRun: true });
+};
+
 export const cleanupExpiredCache = async (): Promise<number> => {
   return await invoke('cleanup_expired_cache');
 };
 
+export const verifyCacheConsistency = async (repair = false): Promise<ConsistencyReport> => {
+  return await invoke('verify_cache_consistency', { repair });
+};
+
 // External links
 export const openExternal = async (url: string): Promise<void> => {
   return await invoke('open_external', { url });
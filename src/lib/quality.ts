@@ -5,6 +5,8 @@
 
 export interface QualitySelectionOptions {
   availableQualities: string[];
+  /** The item's own preference (getContentQualityPref), ahead of lastUsedQuality */
+  contentQuality?: string | null;
   lastUsedQuality?: string;
   networkSpeed?: 'slow' | 'medium' | 'fast';
   autoUpgrade?: boolean;
@@ -15,12 +17,17 @@ export interface QualitySelectionOptions {
  * Implements the quality selection algorithm from the design document
  */
 export function chooseInitialQuality(options: QualitySelectionOptions): string {
-  const { availableQualities, lastUsedQuality, networkSpeed } = options;
+  const { availableQualities, contentQuality, lastUsedQuality, networkSpeed } = options;
   
   if (!availableQualities.length) {
     return '480p'; // Fallback
   }
 
+  // A quality remembered for this item wins over the global one
+  if (contentQuality && availableQualities.includes(contentQuality)) {
+    return contentQuality;
+  }
+
   // If user has a preferred quality and it's available, use it
   if (lastUsedQuality && availableQualities.includes(lastUsedQuality)) {
     return lastUsedQuality;
@@ -5,7 +5,7 @@ import { ContentItem, Episode } from '../types';
 import { useSeriesGrouped } from '../hooks/useContent';
 import { useDownloadManager } from '../hooks/useDownloadManager';
 import { useOffline } from '../hooks/useOffline';
-import { saveFavorite, removeFavorite, getFavorites, resolveClaim } from '../lib/api';
+import { saveFavorite, removeFavorite, getFavorites, resolveClaim, resolveBestQuality } from '../lib/api';
 import SkeletonCard from '../components/SkeletonCard';
 import OfflineEmptyState from '../components/OfflineEmptyState';
 import PlayerModal from '../components/PlayerModal';
@@ -89,20 +89,13 @@ const SeriesPage = () => {
 
   const handleDownload = async (content: ContentItem) => {
     try {
-      const qualities = Object.keys(content.video_urls);
-      const preferredQuality = qualities.includes('720p') ? '720p' : qualities[0];
-      
-      if (!preferredQuality) {
-        console.error('No video URLs available');
-        return;
-      }
+      // The item's remembered quality, then the preferred_download_quality setting
+      const selection = await resolveBestQuality(content.claim_id);
 
-      const videoUrl = content.video_urls[preferredQuality];
-      
       await downloadContent({
         claim_id: content.claim_id,
-        quality: preferredQuality,
-        url: videoUrl.url
+        quality: selection.quality,
+        url: selection.url
       });
     } catch (err) {
       console.error('Download failed:', err);
@@ -12,6 +12,57 @@ export interface ContentItem {
   etag?: string;
   content_hash?: string;
   raw_json?: string;
+  stale?: boolean;
+}
+
+export interface ChannelClaimsResult {
+  items: ContentItem[];
+  source: 'cache' | 'network';
+  fetched_at: number;
+  stale: boolean;
+  page_info: PageInfo;
+}
+
+// Claim ids; `removed` are cached claims missing from the remote listing
+export interface DiffResult {
+  new: string[];
+  changed: string[];
+  unchanged: string[];
+  removed: string[];
+}
+
+// Snippets are escaped HTML; only the <mark> tags around matches are markup
+export interface SearchHit {
+  item: ContentItem;
+  title_snippet: string | null;
+  description_snippet: string | null;
+}
+
+export interface WarmCacheTarget {
+  channel_id: string;
+  tags?: string[];
+}
+
+export interface ChannelWarmResult {
+  channel_id: string;
+  tags: string[];
+  fetched: number;
+  stored: number;
+  skipped: number;
+  error: string | null;
+}
+
+export interface WarmCacheSummary {
+  channels: ChannelWarmResult[];
+}
+
+// Totals are null unless the gateway reported them
+export interface PageInfo {
+  page: number;
+  page_size: number;
+  total_items: number | null;
+  total_pages: number | null;
+  has_more: boolean;
 }
 
 export interface VideoUrl {
@@ -44,6 +95,11 @@ export interface PlaylistItem {
   season_number?: number;
 }
 
+export interface PlaylistNeighbors {
+  prev?: PlaylistItem | null;
+  next?: PlaylistItem | null;
+}
+
 export interface SeriesInfo {
   series_key: string;
   title: string;
@@ -82,6 +138,73 @@ export interface FavoriteItem {
   inserted_at: number;
 }
 
+export interface FavoriteChange extends FavoriteItem {
+  deleted_at?: number;
+  changed_at: number;
+}
+
+export interface ProgressChange extends ProgressData {
+  deleted_at?: number;
+  changed_at: number;
+}
+
+export interface SyncChanges {
+  since: number;
+  until: number;
+  favorites: FavoriteChange[];
+  progress: ProgressChange[];
+}
+
+export interface ViewHistoryEntry {
+  claim_id: string;
+  viewed_at: number;
+  title?: string;
+  thumbnail_url?: string;
+}
+
+export interface PersonalDataReport {
+  favorites_removed: number;
+  progress_removed: number;
+  view_history_removed: number;
+  settings_removed: number;
+  content_prefs_removed: number;
+  offline_removed: number;
+  offline_failed: number;
+}
+
+export interface UserDataImportReport {
+  favorites_imported: number;
+  progress_imported: number;
+  views_imported: number;
+  playlists_imported: number;
+  content_prefs_imported: number;
+  skipped: number;
+  conflicted: number;
+  conflicts: ImportConflict[];
+}
+
+export interface ImportConflict {
+  kind: 'duplicate_claim_id' | 'progress_timestamp' | 'orphaned_playlist_item';
+  section: 'favorites' | 'progress' | 'view_history' | 'playlists';
+  id: string;
+  detail: string;
+}
+
+export interface QueryTiming {
+  name: string;
+  duration_ms: number;
+  result_count: number;
+}
+
+export interface MediaProbe {
+  duration_seconds?: number;
+  width?: number;
+  height?: number;
+  video_codec?: string;
+  audio_codec?: string;
+  bitrate_bps?: number;
+}
+
 export interface OfflineMetadata {
   claim_id: string;
   quality: string;
@@ -89,19 +212,58 @@ export interface OfflineMetadata {
   file_size: number;
   encrypted: boolean;
   added_at: number;
+  probe?: MediaProbe | null;
+  title?: string;
+}
+
+export interface OfflineSort {
+  by: 'date' | 'size' | 'title';
+  ascending?: boolean;
+}
+
+export interface OfflineFilter {
+  encrypted_only?: boolean;
+  quality?: string;
 }
 
 // Application configuration
 export interface AppConfig {
   theme: 'dark' | 'light';
   last_used_quality: string;
+  preferred_download_quality?: string;
   encrypt_downloads: boolean;
   auto_upgrade_quality: boolean;
   cache_ttl_minutes: number;
+  tag_ttl_overrides?: Record<string, number>;
   max_cache_items: number;
+  max_cache_bytes?: number;
+  allow_stale_on_error?: boolean;
+  min_cache_results?: number;
+  prebuffer_seconds?: number;
+  proxy_thumbnails?: boolean;
+  cache_eviction_policy?: 'lru' | 'lfu' | 'fifo';
+  finished_threshold_percent?: number;
+  content_pipeline_tracing?: boolean;
+  prewarm_favorites?: boolean;
+  warm_connection_pool?: boolean;
+  raw_json_format?: 'compact' | 'pretty';
+  thumbnail_url_policy?: 'upgrade' | 'strict' | 'lenient';
+  max_title_length?: number;
+  max_description_length?: number;
+  gateway_max_requests_per_second?: number;
+  allow_plaintext_fallback?: boolean;
+  download_temp_dir?: string;
   vault_path: string;
   version: string;
   gateways: string[];
+  storage?: StorageInfo;
+}
+
+// Live disk usage sent with the config; null means it could not be measured
+export interface StorageInfo {
+  vault_usage_bytes: number | null;
+  free_disk_bytes: number | null;
+  database_size_bytes: number | null;
 }
 
 // Update system types
@@ -147,14 +309,98 @@ export interface StreamOfflineResponse {
 }
 
 // Diagnostics types
+export interface SecurityEventRecord {
+  timestamp: string;
+  severity: 'INFO' | 'WARNING' | 'CRITICAL';
+  event_type: string;
+  details: string;
+}
+
+export interface IoPathStats {
+  bytes: number;
+  operations: number;
+  average_latency_ms: number;
+  recent_latency_ms: number;
+}
+
+export interface DiskIoStats {
+  download_writes: IoPathStats;
+  stream_reads: IoPathStats;
+  stream_decryption: IoPathStats;
+}
+
+export interface EncryptionBenchmark {
+  size_mb: number;
+  encrypt_mb_per_sec: number;
+  decrypt_mb_per_sec: number;
+  measured_at: number;
+}
+
 export interface DiagnosticsData {
   gateway_health: GatewayHealth[];
   database_version: number;
   free_disk_bytes: number;
   local_server_status: ServerStatus;
   last_manifest_fetch?: number;
+  cdn_gateway?: CdnGatewayInfo;
   cache_stats: CacheStats;
   download_stats: DownloadStats;
+  cache_consistency?: ConsistencyReport;
+  schema_summary?: SchemaSummary;
+  runtime?: RuntimeStats;
+  offline_duration?: OfflineDuration;
+}
+
+// Runtime available offline; each claim counts once whatever its qualities
+export interface OfflineDuration {
+  total_seconds: number;
+  counted_claims: number;
+  // Claims without a known runtime, left out of total_seconds
+  uncounted_claims: number;
+}
+
+export interface RuntimeStats {
+  workers: number;
+  alive_tasks: number;
+  global_queue_depth: number;
+  // Only measured in builds with --cfg tokio_unstable
+  blocking_pool: BlockingPoolStats | null;
+}
+
+export interface BlockingPoolStats {
+  threads: number;
+  idle_threads: number;
+  queue_depth: number;
+}
+
+export interface ClaimIdValidation {
+  id: string;
+  valid: boolean;
+  error?: string;
+}
+
+export interface SchemaSummary {
+  current_version: number;
+  migrations_applied: number;
+  latest_applied_at?: number;
+  has_unvalidated: boolean;
+  unvalidated_versions: number[];
+}
+
+export interface ConsistencyReport {
+  fts5_available: boolean;
+  cache_rows: number;
+  fts_rows: number;
+  missing_from_fts: string[];
+  orphaned_fts_rows: number;
+  consistent: boolean;
+  rebuilt: boolean;
+}
+
+export interface CdnGatewayInfo {
+  gateway: string;
+  source: 'env' | 'default';
+  is_default: boolean;
 }
 
 export interface GatewayHealth {
@@ -163,6 +409,19 @@ export interface GatewayHealth {
   last_success?: number;
   last_error?: string;
   response_time_ms?: number;
+  recent_successes: number;
+  recent_failures: number;
+  consecutive_failures: number;
+  last_failure?: number;
+  /** Unix time until which the gateway is skipped */
+  cooldown_until?: number;
+}
+
+export interface InflightRequestInfo {
+  key: string;
+  method: string;
+  age_ms: number;
+  waiters: number;
 }
 
 export interface ServerStatus {
@@ -187,6 +446,27 @@ export interface MemoryStats {
   database_file_size: number;
 }
 
+// Payload of the `library-changed` event; claim_id is null for bulk changes
+export interface LibraryChangedEvent {
+  kind: 'favorite' | 'progress' | 'offline' | 'cache';
+  action: 'added' | 'updated' | 'removed' | 'cleared';
+  claim_id: string | null;
+}
+
+export interface StorageRecommendation {
+  description: string;
+  command: string;
+  args: Record<string, unknown>;
+  estimated_bytes: number;
+}
+
+export interface StorageRecommendations {
+  vault_bytes: number;
+  database_bytes: number;
+  wal_bytes: number;
+  recommendations: StorageRecommendation[];
+}
+
 export interface DownloadStats {
   total_downloads: number;
   total_bytes_downloaded: number;
@@ -320,6 +600,29 @@ export type Breakpoint = 'sm' | 'md' | 'lg' | 'xl' | '2xl';
 // Theme types
 export type Theme = 'dark' | 'light';
 
+// Quality picked by resolveBestQuality; preferred_available is false for a fallback
+export interface QualitySelection {
+  claim_id: string;
+  quality: string;
+  url: string;
+  preferred_available: boolean;
+}
+
+// Whether a claim's content can actually be downloaded
+export type DownloadAvailability = 'available' | 'not_stream' | 'unreachable' | 'region_blocked';
+
+// Where a download stands this session; only 'queued' and 'active' count as downloading
+export type DownloadState = 'not_started' | 'queued' | 'active' | 'completed' | 'failed';
+
+// A claim the CDN refused in the user's region (HTTP 403 or 451)
+export interface RegionBlock {
+  claim_id: string;
+  status: number;
+  block_count: number;
+  first_seen: number;
+  last_seen: number;
+}
+
 // Quality types
 export type Quality = '1080p' | '720p' | '480p' | '360p' | '240p' | 'master';
 
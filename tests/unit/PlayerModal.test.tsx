@@ -77,7 +77,9 @@ vi.mock('../../src/lib/api', () => ({
   saveProgress: vi.fn(),
   getProgress: vi.fn(),
   streamOffline: vi.fn(),
-  openExternal: vi.fn()
+  openExternal: vi.fn(),
+  getContentQualityPref: vi.fn(),
+  setContentQualityPref: vi.fn()
 }));
 
 describe('PlayerModal', () => {
@@ -119,6 +121,8 @@ describe('PlayerModal', () => {
     (api.getProgress as any).mockResolvedValue(null);
     (api.saveProgress as any).mockResolvedValue(undefined);
     (api.streamOffline as any).mockResolvedValue({ url: 'http://localhost:8080/test', port: 8080 });
+    (api.getContentQualityPref as any).mockResolvedValue(null);
+    (api.setContentQualityPref as any).mockResolvedValue(undefined);
   });
 
   afterEach(() => {
@@ -381,6 +385,38 @@ describe('PlayerModal', () => {
 
     // Should update to 480p
     expect(screen.getByText(/Quality: 480p/)).toBeInTheDocument();
+    expect(api.setContentQualityPref).toHaveBeenCalledWith('test-claim-123', '480p');
+  });
+
+  it('should start in the quality remembered for the item', async () => {
+    let resolvePref: (quality: string | null) => void = () => {};
+    (api.getContentQualityPref as any).mockReturnValue(
+      new Promise((resolve) => {
+        resolvePref = resolve;
+      })
+    );
+
+    const { container } = render(
+      <PlayerModal
+        content={mockContent}
+        isOpen={true}
+        onClose={mockOnClose}
+      />
+    );
+    const video = container.querySelector('video') as HTMLVideoElement;
+
+    // Nothing loads in the default quality while the preference is pending
+    expect(api.getContentQualityPref).toHaveBeenCalledWith('test-claim-123');
+    expect(video.getAttribute('src')).toBeNull();
+
+    await act(async () => {
+      resolvePref('480p');
+    });
+
+    await waitFor(() => {
+      expect(screen.getByText(/Quality: 480p/)).toBeInTheDocument();
+      expect(video.src).toBe('https://example.com/video-480p.mp4');
+    });
   });
 
   it('should render video element', () => {
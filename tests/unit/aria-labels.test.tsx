@@ -50,6 +50,8 @@ vi.mock('../../src/lib/api', () => ({
   getFavorites: vi.fn(() => Promise.resolve([])),
   saveFavorite: vi.fn(),
   removeFavorite: vi.fn(),
+  getContentQualityPref: vi.fn(() => Promise.resolve(null)),
+  setContentQualityPref: vi.fn(() => Promise.resolve()),
 }));
 
 // Mock GSAP
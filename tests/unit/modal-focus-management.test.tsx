@@ -44,6 +44,8 @@ vi.mock('../../src/lib/api', () => ({
   getProgress: vi.fn(() => Promise.resolve(null)),
   streamOffline: vi.fn(() => Promise.resolve({ url: 'http://localhost:8080/test' })),
   openExternal: vi.fn(),
+  getContentQualityPref: vi.fn(() => Promise.resolve(null)),
+  setContentQualityPref: vi.fn(() => Promise.resolve()),
 }));
 
 describe('Modal Focus Management', () => {